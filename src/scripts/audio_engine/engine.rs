@@ -0,0 +1,179 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use rodio::{OutputStream, Source};
+
+use super::{
+    engine_core::{EngineCore, EngineEvent, Mix, ScheduledVoice, VoiceGroup},
+    mixer::{Channel, MeterBank, MixGraph},
+    voices::Voice,
+};
+
+pub const ENGINE_SAMPLE_RATE: u32 = 44100;
+pub const ENGINE_CHANNELS: u16 = 2;
+
+/// How far ahead of the audio clock the sequencers queue their events.
+pub const SCHEDULE_LOOKAHEAD: Duration = Duration::from_millis(100);
+/// How often the sequencer threads wake up to top up the schedule.
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_millis(5);
+// How many replaced mixes can wait for the handle to free them. The handle collects
+// them whenever it sends a new one, so only a burst of changes within one event poll
+// could fill this up.
+const RETIRED_MIXES: usize = 32;
+
+/// Owns the single output stream of the application. Everything that makes a
+/// sound goes through the `EngineHandle` of this engine.
+pub struct AudioEngine {
+    _output_stream: Option<OutputStream>,
+    handle: EngineHandle,
+}
+
+/// Cheap, clonable and `Send` access to the engine for the sequencer threads.
+#[derive(Clone)]
+pub struct EngineHandle {
+    sender: Sender<EngineEvent>,
+    retired_mixes: Receiver<Box<Mix>>,
+    clock: Arc<AtomicU64>,
+    pub meters: Arc<MeterBank>,
+    pub sample_rate: u32,
+}
+
+impl AudioEngine {
+    pub fn new() -> Self {
+        // The same pair an offline render pulls from, with the core handed to the device.
+        let (handle, core) = EngineHandle::offline();
+
+        let output_stream = match OutputStream::try_default() {
+            Ok((stream, stream_handle)) => {
+                if let Err(e) = stream_handle.play_raw(EngineSource::new(core)) {
+                    println!("Error starting audio engine: {:?}", e);
+                }
+                Some(stream)
+            }
+            Err(e) => {
+                println!("Error opening audio output: {:?}", e);
+                None
+            }
+        };
+
+        AudioEngine {
+            _output_stream: output_stream,
+            handle,
+        }
+    }
+
+    pub fn handle(&self) -> EngineHandle {
+        self.handle.clone()
+    }
+}
+
+impl EngineHandle {
+    /// Creates a handle that is not connected to any output device, together with the
+    /// core it feeds. Pulling frames from the core renders faster than real time.
+    pub fn offline() -> (EngineHandle, EngineCore) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let (retired_sender, retired_mixes) = crossbeam_channel::bounded(RETIRED_MIXES);
+        let clock = Arc::new(AtomicU64::new(0));
+        let meters = Arc::new(MeterBank::new());
+        let core = EngineCore::new(receiver, retired_sender, clock.clone(), meters.clone());
+        (
+            EngineHandle {
+                sender,
+                retired_mixes,
+                clock,
+                meters,
+                sample_rate: ENGINE_SAMPLE_RATE,
+            },
+            core,
+        )
+    }
+
+    /// The number of frames the engine has rendered so far.
+    pub fn now(&self) -> u64 {
+        self.clock.load(Ordering::Acquire)
+    }
+
+    /// The earliest frame a sequence can start on without its first events arriving late.
+    pub fn lookahead_frame(&self) -> u64 {
+        self.now() + self.duration_to_frames(SCHEDULE_LOOKAHEAD)
+    }
+
+    pub fn duration_to_frames(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.sample_rate as f64).round() as u64
+    }
+
+    pub fn schedule(&self, frame: u64, channel: Channel, voice: Box<dyn Voice>) {
+        let _ = self.sender.send(EngineEvent::Schedule(ScheduledVoice {
+            frame,
+            channel,
+            voice,
+        }));
+    }
+
+    /// Replaces the gains of the mixer, taking effect on the next event poll. The mixes
+    /// replaced before are freed here rather than on the audio thread.
+    pub fn set_mix(&self, graph: MixGraph) {
+        self.retired_mixes.try_iter().for_each(drop);
+        let _ = self.sender.send(EngineEvent::SetMix(Mix::new(graph)));
+    }
+
+    /// Drops every voice of `group` that has been queued but has not started yet.
+    pub fn clear_pending(&self, group: VoiceGroup) {
+        let _ = self.sender.send(EngineEvent::ClearPending(group));
+    }
+}
+
+/// Adapts the engine core to a rodio source producing interleaved stereo samples.
+struct EngineSource {
+    core: EngineCore,
+    frame: [f32; 2],
+    channel: usize,
+}
+
+impl EngineSource {
+    fn new(core: EngineCore) -> Self {
+        EngineSource {
+            core,
+            frame: [0.0, 0.0],
+            channel: ENGINE_CHANNELS as usize,
+        }
+    }
+}
+
+impl Iterator for EngineSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel >= ENGINE_CHANNELS as usize {
+            self.frame = self.core.render_frame();
+            self.channel = 0;
+        }
+        let sample = self.frame[self.channel];
+        self.channel += 1;
+        Some(sample)
+    }
+}
+
+impl Source for EngineSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        ENGINE_CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        ENGINE_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
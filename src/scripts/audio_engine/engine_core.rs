@@ -0,0 +1,265 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc,
+    },
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use super::{
+    mixer::{Channel, MeterBank, MeterState, MixGraph, Route, StripGains},
+    voices::Voice,
+};
+
+// How many frames are rendered between two checks of the event channel.
+const EVENT_POLL_INTERVAL: u64 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceGroup {
+    Drums,
+    Synth,
+    Preview,
+}
+
+pub enum EngineEvent {
+    Schedule(ScheduledVoice),
+    ClearPending(VoiceGroup),
+    SetMix(Box<Mix>),
+}
+
+pub struct ScheduledVoice {
+    pub frame: u64,
+    pub channel: Channel,
+    pub voice: Box<dyn Voice>,
+}
+
+// BinaryHeap is a max-heap, so the ordering is reversed to pop the earliest frame first.
+impl Ord for ScheduledVoice {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.frame.cmp(&self.frame)
+    }
+}
+
+impl PartialOrd for ScheduledVoice {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ScheduledVoice {
+    fn eq(&self, other: &Self) -> bool {
+        self.frame == other.frame
+    }
+}
+
+impl Eq for ScheduledVoice {}
+
+/// Renders every voice against one sample-position clock and mixes them through the
+/// channel strips, group buses and master. This is the part of the engine that runs
+/// inside the audio callback, so it never blocks or touches the disk.
+pub struct EngineCore {
+    receiver: Receiver<EngineEvent>,
+    pending: BinaryHeap<ScheduledVoice>,
+    active: Vec<(Channel, Box<dyn Voice>)>,
+    frame: u64,
+    clock: Arc<AtomicU64>,
+    mix: Box<Mix>,
+    /// Takes replaced mixes back to the handle, to be freed away from the audio thread.
+    retired_mixes: Sender<Box<Mix>>,
+    meters: Arc<MeterBank>,
+    meter_states: MeterStates,
+}
+
+/// A mix graph along with the frames its strips are mixed in, allocated to its size
+/// before it is sent to the engine so swapping it in never allocates.
+pub struct Mix {
+    graph: MixGraph,
+    frames: MixFrames,
+}
+
+impl Mix {
+    pub fn new(graph: MixGraph) -> Box<Self> {
+        Box::new(Mix {
+            frames: MixFrames {
+                drums: vec![[0.0, 0.0]; graph.drums.len()],
+                buses: vec![[0.0, 0.0]; graph.buses.len()],
+                ..MixFrames::default()
+            },
+            graph,
+        })
+    }
+}
+
+/// The frame of every strip while it is being mixed.
+#[derive(Default)]
+struct MixFrames {
+    drums: Vec<[f32; 2]>,
+    synth: [f32; 2],
+    buses: Vec<[f32; 2]>,
+    /// Drum voices of tracks the graph has no strip for yet, played at unity.
+    unrouted: [f32; 2],
+    preview: [f32; 2],
+}
+
+struct MeterStates {
+    drums: Vec<MeterState>,
+    synth: MeterState,
+    buses: Vec<MeterState>,
+    master: MeterState,
+}
+
+impl EngineCore {
+    pub fn new(
+        receiver: Receiver<EngineEvent>,
+        retired_mixes: Sender<Box<Mix>>,
+        clock: Arc<AtomicU64>,
+        meters: Arc<MeterBank>,
+    ) -> Self {
+        EngineCore {
+            receiver,
+            pending: BinaryHeap::new(),
+            active: Vec::with_capacity(64),
+            frame: clock.load(AtomicOrdering::Acquire),
+            clock,
+            mix: Mix::new(MixGraph {
+                master: 1.0,
+                ..MixGraph::default()
+            }),
+            retired_mixes,
+            meter_states: MeterStates {
+                drums: vec![MeterState::default(); meters.drums.len()],
+                synth: MeterState::default(),
+                buses: vec![MeterState::default(); meters.buses.len()],
+                master: MeterState::default(),
+            },
+            meters,
+        }
+    }
+
+    fn poll_events(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                EngineEvent::Schedule(scheduled) => self.pending.push(scheduled),
+                EngineEvent::ClearPending(group) => {
+                    self.pending
+                        .retain(|scheduled| scheduled.channel.group() != group);
+                }
+                EngineEvent::SetMix(mix) => {
+                    let retired = std::mem::replace(&mut self.mix, mix);
+                    // Only dropped here if the handle has stopped collecting them.
+                    let _ = self.retired_mixes.try_send(retired);
+                }
+            }
+        }
+    }
+
+    fn publish_meters(&mut self) {
+        let (states, meters) = (&mut self.meter_states, &self.meters);
+        for (index, state) in states.drums.iter_mut().enumerate() {
+            state.publish(meters.drums.get(index));
+        }
+        states.synth.publish(Some(&meters.synth));
+        for (index, state) in states.buses.iter_mut().enumerate() {
+            state.publish(meters.buses.get(index));
+        }
+        states.master.publish(Some(&meters.master));
+    }
+
+    /// Runs the strips over the voice frames gathered in `mix` and returns the output.
+    fn mix_frame(&mut self) -> [f32; 2] {
+        let (graph, mix) = (&self.mix.graph, &mut self.mix.frames);
+        let meters = &mut self.meter_states;
+        let mut master = mix.unrouted;
+        mix.buses.iter_mut().for_each(|bus| *bus = [0.0, 0.0]);
+
+        for (index, (frame, strip)) in mix.drums.iter().zip(graph.drums.iter()).enumerate() {
+            let out = apply_gains(*frame, strip);
+            if let Some(meter) = meters.drums.get_mut(index) {
+                meter.add(out);
+            }
+            send(out, strip.route, &mut mix.buses, &mut master);
+        }
+        let synth = apply_gains(mix.synth, &graph.synth);
+        meters.synth.add(synth);
+        send(synth, graph.synth.route, &mut mix.buses, &mut master);
+
+        for (index, (frame, strip)) in mix.buses.iter().zip(graph.buses.iter()).enumerate() {
+            let out = apply_gains(*frame, strip);
+            if let Some(meter) = meters.buses.get_mut(index) {
+                meter.add(out);
+            }
+            master[0] += out[0];
+            master[1] += out[1];
+        }
+
+        let master = [master[0] * graph.master, master[1] * graph.master];
+        meters.master.add(master);
+        [master[0] + mix.preview[0], master[1] + mix.preview[1]]
+    }
+
+    /// Whether every voice has finished and none is waiting to start.
+    pub fn is_idle(&self) -> bool {
+        self.active.is_empty() && self.pending.is_empty() && self.receiver.is_empty()
+    }
+
+    pub fn render_frame(&mut self) -> [f32; 2] {
+        if self.frame.is_multiple_of(EVENT_POLL_INTERVAL) {
+            self.publish_meters();
+            self.poll_events();
+        }
+
+        // Anything scheduled in the past (a late event) starts right away.
+        while self
+            .pending
+            .peek()
+            .is_some_and(|scheduled| scheduled.frame <= self.frame)
+        {
+            if let Some(scheduled) = self.pending.pop() {
+                if let Some(key) = scheduled.voice.retrigger_key() {
+                    for (channel, voice) in self.active.iter_mut() {
+                        if *channel == scheduled.channel && voice.retrigger_key() == Some(key) {
+                            voice.choke();
+                        }
+                    }
+                }
+                self.active.push((scheduled.channel, scheduled.voice));
+            }
+        }
+
+        let mix = &mut self.mix.frames;
+        mix.drums.iter_mut().for_each(|frame| *frame = [0.0, 0.0]);
+        mix.synth = [0.0, 0.0];
+        mix.unrouted = [0.0, 0.0];
+        mix.preview = [0.0, 0.0];
+        self.active.retain_mut(|(channel, voice)| {
+            let out = match channel {
+                Channel::Drum(index) => mix.drums.get_mut(*index).unwrap_or(&mut mix.unrouted),
+                Channel::Synth => &mut mix.synth,
+                Channel::Preview => &mut mix.preview,
+            };
+            voice.render(out)
+        });
+        let out = self.mix_frame();
+
+        self.frame += 1;
+        self.clock.store(self.frame, AtomicOrdering::Release);
+        out
+    }
+}
+
+fn apply_gains(frame: [f32; 2], strip: &StripGains) -> [f32; 2] {
+    [frame[0] * strip.gains[0], frame[1] * strip.gains[1]]
+}
+
+/// Adds the output of a strip to the bus it is routed to, or to the master.
+fn send(out: [f32; 2], route: Route, buses: &mut [[f32; 2]], master: &mut [f32; 2]) {
+    let target = match route {
+        Route::Bus(bus) => buses.get_mut(bus).unwrap_or(master),
+        Route::Master => master,
+    };
+    target[0] += out[0];
+    target[1] += out[1];
+}
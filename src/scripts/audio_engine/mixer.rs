@@ -0,0 +1,161 @@
+use std::{
+    f32::consts::{FRAC_PI_4, SQRT_2},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::engine_core::VoiceGroup;
+
+/// Drum tracks past this many still play through their strip but are not metered.
+pub const MAX_METERED_DRUMS: usize = 64;
+pub const MAX_BUSES: usize = 8;
+
+// How fast a held peak falls back, per metering block.
+const PEAK_DECAY: f32 = 0.95;
+// The share of a new block in the RMS average, about 300 ms at 64 frame blocks.
+const RMS_SMOOTHING: f32 = 0.015;
+
+/// The mixer channel a voice plays through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Drum(usize),
+    Synth,
+    /// Auditioned samples, straight to the output past every strip.
+    Preview,
+}
+
+impl Channel {
+    pub fn group(&self) -> VoiceGroup {
+        match self {
+            Channel::Drum(_) => VoiceGroup::Drums,
+            Channel::Synth => VoiceGroup::Synth,
+            Channel::Preview => VoiceGroup::Preview,
+        }
+    }
+}
+
+/// Where a strip sends its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Master,
+    Bus(usize),
+}
+
+/// The left and right gain of a strip, with mute, solo, level and pan already folded in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StripGains {
+    pub gains: [f32; 2],
+    pub route: Route,
+}
+
+impl StripGains {
+    /// Constant power pan, scaled so the centre keeps both channels at `amplitude`.
+    pub fn new(amplitude: f32, pan: f32, route: Route) -> Self {
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        StripGains {
+            gains: [
+                amplitude * angle.cos() * SQRT_2,
+                amplitude * angle.sin() * SQRT_2,
+            ],
+            route,
+        }
+    }
+}
+
+impl Default for StripGains {
+    fn default() -> Self {
+        StripGains::new(1.0, 0.0, Route::Master)
+    }
+}
+
+/// Every gain of the mixer, built from the project by the UI and sent to the engine
+/// whole whenever it changes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MixGraph {
+    pub drums: Vec<StripGains>,
+    pub synth: StripGains,
+    /// Buses always route to the master.
+    pub buses: Vec<StripGains>,
+    pub master: f32,
+}
+
+/// One peak and RMS reading, written by the audio thread and read by the UI.
+#[derive(Default)]
+pub struct Meter {
+    peak: AtomicU32,
+    rms: AtomicU32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeterReading {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl Meter {
+    pub fn read(&self) -> MeterReading {
+        MeterReading {
+            peak: f32::from_bits(self.peak.load(Ordering::Relaxed)),
+            rms: f32::from_bits(self.rms.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn publish(&self, reading: MeterReading) {
+        self.peak.store(reading.peak.to_bits(), Ordering::Relaxed);
+        self.rms.store(reading.rms.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// The meters of every strip, shared between the engine core and its handles.
+pub struct MeterBank {
+    pub drums: Vec<Meter>,
+    pub synth: Meter,
+    pub buses: Vec<Meter>,
+    pub master: Meter,
+}
+
+impl MeterBank {
+    pub fn new() -> Self {
+        MeterBank {
+            drums: (0..MAX_METERED_DRUMS).map(|_| Meter::default()).collect(),
+            synth: Meter::default(),
+            buses: (0..MAX_BUSES).map(|_| Meter::default()).collect(),
+            master: Meter::default(),
+        }
+    }
+}
+
+/// Accumulates the frames of one strip between two publishes of its meter.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeterState {
+    block_peak: f32,
+    block_squares: f32,
+    frames: u32,
+    held: MeterReading,
+}
+
+impl MeterState {
+    pub fn add(&mut self, frame: [f32; 2]) {
+        let level = frame[0].abs().max(frame[1].abs());
+        self.block_peak = self.block_peak.max(level);
+        self.block_squares += (frame[0] * frame[0] + frame[1] * frame[1]) / 2.0;
+        self.frames += 1;
+    }
+
+    pub fn publish(&mut self, meter: Option<&Meter>) {
+        if self.frames == 0 {
+            return;
+        }
+        let mean_square = self.block_squares / self.frames as f32;
+        let previous_square = self.held.rms * self.held.rms;
+        self.held = MeterReading {
+            peak: self.block_peak.max(self.held.peak * PEAK_DECAY),
+            rms: (previous_square + (mean_square - previous_square) * RMS_SMOOTHING).sqrt(),
+        };
+        if let Some(meter) = meter {
+            meter.publish(self.held);
+        }
+        self.block_peak = 0.0;
+        self.block_squares = 0.0;
+        self.frames = 0;
+    }
+}
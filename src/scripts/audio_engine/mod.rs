@@ -0,0 +1,4 @@
+pub mod engine;
+pub mod engine_core;
+pub mod mixer;
+pub mod voices;
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use fundsp::hacker32::AudioUnit;
+
+use crate::scripts::synth::envelope::EnvelopeState;
+
+pub trait Voice: Send {
+    /// Adds the next stereo frame of this voice to `out`. Returns `false` once the
+    /// voice has finished and can be dropped.
+    fn render(&mut self, out: &mut [f32; 2]) -> bool;
+
+    /// Voices on one channel sharing a key take over from each other: a new one chokes
+    /// the ones still ringing instead of stacking on top of them.
+    fn retrigger_key(&self) -> Option<u32> {
+        None
+    }
+
+    /// Fades the voice out quickly, called when a voice with the same key starts.
+    fn choke(&mut self) {}
+}
+
+/// Plays back an already decoded, interleaved stereo buffer at the engine rate.
+pub struct SampleVoice {
+    samples: Arc<Vec<f32>>,
+    position: usize,
+    gain: f32,
+}
+
+impl SampleVoice {
+    pub fn new(samples: Arc<Vec<f32>>, gain: f32) -> Self {
+        SampleVoice {
+            samples,
+            position: 0,
+            gain,
+        }
+    }
+}
+
+impl Voice for SampleVoice {
+    fn render(&mut self, out: &mut [f32; 2]) -> bool {
+        if self.position + 1 >= self.samples.len() {
+            return false;
+        }
+        out[0] += self.samples[self.position] * self.gain;
+        out[1] += self.samples[self.position + 1] * self.gain;
+        self.position += 2;
+        true
+    }
+}
+
+/// A synth note, the fundsp graph of its patch shaped by an amplitude envelope. The
+/// note lasts as long as its envelope.
+pub struct SynthVoice {
+    graph: Box<dyn AudioUnit>,
+    envelope: EnvelopeState,
+    amplitude: f32,
+    frequency: f32,
+}
+
+impl SynthVoice {
+    pub fn new(
+        graph: Box<dyn AudioUnit>,
+        envelope: EnvelopeState,
+        amplitude: f32,
+        frequency: f32,
+    ) -> Self {
+        SynthVoice {
+            graph,
+            envelope,
+            amplitude,
+            frequency,
+        }
+    }
+}
+
+impl Voice for SynthVoice {
+    fn render(&mut self, out: &mut [f32; 2]) -> bool {
+        let Some(level) = self.envelope.next_level() else {
+            return false;
+        };
+        let (left, right) = self.graph.get_stereo();
+        let gain = self.amplitude * level;
+        out[0] += left * gain;
+        out[1] += right * gain;
+        true
+    }
+
+    /// A note retriggers the still releasing notes of the same pitch.
+    fn retrigger_key(&self) -> Option<u32> {
+        Some(self.frequency.to_bits())
+    }
+
+    fn choke(&mut self) {
+        self.envelope.choke();
+    }
+}
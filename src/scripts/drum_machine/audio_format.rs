@@ -0,0 +1,112 @@
+use std::{fmt, fs::File, io::Read, path::Path};
+
+/// The sample formats the drum machine can load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+    Ogg,
+    Mp3,
+    Aiff,
+}
+
+impl fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AudioFormat::Wav => write!(f, "WAV"),
+            AudioFormat::Flac => write!(f, "FLAC"),
+            AudioFormat::Ogg => write!(f, "Ogg Vorbis"),
+            AudioFormat::Mp3 => write!(f, "MP3"),
+            AudioFormat::Aiff => write!(f, "AIFF"),
+        }
+    }
+}
+
+impl AudioFormat {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "wav" | "wave" => Some(AudioFormat::Wav),
+            "flac" => Some(AudioFormat::Flac),
+            "ogg" | "oga" => Some(AudioFormat::Ogg),
+            "mp3" => Some(AudioFormat::Mp3),
+            "aif" | "aiff" | "aifc" => Some(AudioFormat::Aiff),
+            _ => None,
+        }
+    }
+
+    /// Recognizes a format from the first bytes of the file.
+    pub fn from_header(header: &[u8]) -> Option<Self> {
+        match header {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => {
+                Some(AudioFormat::Wav)
+            }
+            [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => {
+                Some(AudioFormat::Aiff)
+            }
+            [b'f', b'L', b'a', b'C', ..] => Some(AudioFormat::Flac),
+            [b'O', b'g', b'g', b'S', ..] => Some(AudioFormat::Ogg),
+            [b'I', b'D', b'3', ..] => Some(AudioFormat::Mp3),
+            // An MPEG layer III frame sync without an ID3 tag in front of it. ADTS AAC
+            // shares the sync but leaves the layer bits at zero.
+            [0xFF, second, ..] if second & 0xE6 == 0xE2 && second & 0x18 != 0x08 => {
+                Some(AudioFormat::Mp3)
+            }
+            _ => None,
+        }
+    }
+
+    /// Detects the format from the file contents, falling back to the extension
+    /// when the header is not recognized. Contents win so a mislabeled file still
+    /// goes to the right decoder.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let mut header = [0; 12];
+        let read = File::open(path)
+            .and_then(|mut file| file.read(&mut header))
+            .unwrap_or(0);
+        Self::from_header(&header[..read]).or_else(|| Self::from_extension(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_recognized() {
+        assert_eq!(
+            AudioFormat::from_header(b"RIFF\x24\0\0\0WAVEfmt "),
+            Some(AudioFormat::Wav)
+        );
+        assert_eq!(
+            AudioFormat::from_header(b"FORM\0\0\0\0AIFC"),
+            Some(AudioFormat::Aiff)
+        );
+        assert_eq!(AudioFormat::from_header(b"fLaC"), Some(AudioFormat::Flac));
+        assert_eq!(AudioFormat::from_header(b"OggS"), Some(AudioFormat::Ogg));
+        assert_eq!(AudioFormat::from_header(b"ID3\x04"), Some(AudioFormat::Mp3));
+        assert_eq!(AudioFormat::from_header(b"RIFF\0\0\0\0AVI "), None);
+    }
+
+    #[test]
+    fn only_mpeg_layer_three_frames_count_as_mp3() {
+        // MPEG-1 and MPEG-2 layer III, with and without CRC.
+        for second in [0xFB, 0xFA, 0xF3, 0xE3] {
+            assert_eq!(
+                AudioFormat::from_header(&[0xFF, second, 0x90, 0x00]),
+                Some(AudioFormat::Mp3),
+                "{:#x}",
+                second
+            );
+        }
+        // ADTS AAC, an MPEG layer II frame and the reserved version.
+        for second in [0xF1, 0xF9, 0xFD, 0xEB] {
+            assert_eq!(
+                AudioFormat::from_header(&[0xFF, second, 0x50, 0x80]),
+                None,
+                "{:#x}",
+                second
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::ui::drum_machine::{DrumMachine, SampleFolder};
+
+/// Extra sample roots added in the settings, kept across sessions.
+pub const SAMPLE_ROOTS_FILE: &str = "sample_roots.json";
+
+impl DrumMachine {
+    /// Walks every root recursively and returns each folder that directly holds audio
+    /// files, so nested kit collections show up without any configuration.
+    pub fn discover_kits(roots: &[String]) -> Vec<SampleFolder> {
+        let mut kits = Vec::new();
+        for root in roots {
+            collect_kits(Path::new(root), &mut kits);
+        }
+        kits.sort();
+        kits.dedup();
+        kits
+    }
+
+    pub fn load_sample_roots() -> Vec<String> {
+        fs::read_to_string(SAMPLE_ROOTS_FILE)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_sample_roots(roots: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(SAMPLE_ROOTS_FILE, serde_json::to_string_pretty(roots)?)?;
+        Ok(())
+    }
+
+    /// Collects every path that changes beneath one of the roots into `changed`. The set
+    /// is polled from the UI, which rescans the kits when it finds it filled.
+    pub fn watch_sample_roots(
+        roots: &[String],
+        changed: Arc<Mutex<HashSet<PathBuf>>>,
+    ) -> Option<RecommendedWatcher> {
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event {
+                    changed.lock().unwrap().extend(event.paths);
+                }
+            })
+            .map_err(|e| println!("Error watching sample folders: {:?}", e))
+            .ok()?;
+
+        for root in roots {
+            if let Err(e) = watcher.watch(Path::new(root), RecursiveMode::Recursive) {
+                println!("Error watching sample folder {}: {:?}", root, e);
+            }
+        }
+        Some(watcher)
+    }
+}
+
+// Symlinked folders are not followed, which keeps link cycles from recursing forever.
+fn collect_kits(dir: &Path, kits: &mut Vec<SampleFolder>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut has_audio = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => collect_kits(&path, kits),
+            Ok(file_type) if file_type.is_file() => has_audio |= DrumMachine::is_audio_file(&path),
+            _ => {}
+        }
+    }
+    if has_audio {
+        kits.push(SampleFolder::new(dir));
+    }
+}
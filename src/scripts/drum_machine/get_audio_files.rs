@@ -1,18 +1,26 @@
-use std::fs;
+use std::{fs, path::Path};
 
-use crate::ui::drum_machine::DrumMachine;
+use crate::{scripts::drum_machine::audio_format::AudioFormat, ui::drum_machine::DrumMachine};
 
 impl DrumMachine {
-    pub fn get_audio_files(dir: &str) -> Vec<String> {
+    /// Known extensions are matched case-insensitively. Only files without an
+    /// extension are opened, and accepted when their contents look like a supported
+    /// format, so scanning a kit does not read every text file and image in it.
+    pub fn is_audio_file(path: &Path) -> bool {
+        match path.extension() {
+            Some(_) => AudioFormat::from_extension(path).is_some(),
+            None => AudioFormat::detect(path).is_some(),
+        }
+    }
+
+    pub fn get_audio_files(dir: &Path) -> Vec<String> {
         let mut audio_files = Vec::new();
         if let Ok(entries) = fs::read_dir(dir) {
             for entry in entries.flatten() {
                 if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_file() {
+                    if file_type.is_file() && Self::is_audio_file(&entry.path()) {
                         if let Some(file_name) = entry.file_name().to_str() {
-                            if file_name.ends_with(".wav") {
-                                audio_files.push(file_name.to_string());
-                            }
+                            audio_files.push(file_name.to_string());
                         }
                     }
                 }
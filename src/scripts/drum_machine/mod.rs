@@ -1,3 +1,6 @@
+pub mod audio_format;
+pub mod discover_kits;
 pub mod get_audio_files;
 pub mod play_drum_audio;
 pub mod play_drum_pattern;
+pub mod sample_cache;
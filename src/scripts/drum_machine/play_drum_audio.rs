@@ -1,28 +1,94 @@
-use std::{fs::File, io::BufReader, path::Path, sync::Arc, thread, time::Duration};
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use rodio::{Decoder, Sink, Source};
+use rodio::{source::UniformSourceIterator, Decoder};
 
-use crate::ui::drum_machine::DrumMachine;
+use crate::{
+    scripts::{
+        audio_engine::{
+            engine::{EngineHandle, ENGINE_CHANNELS, ENGINE_SAMPLE_RATE},
+            mixer::Channel,
+            voices::SampleVoice,
+        },
+        drum_machine::{audio_format::AudioFormat, sample_cache::SampleCache},
+    },
+    ui::drum_machine::DrumMachine,
+};
+
+/// Why a sample could not be loaded, naming the file and the format it was read as.
+#[derive(Debug, Clone)]
+pub struct SampleLoadError {
+    pub path: PathBuf,
+    pub format: Option<AudioFormat>,
+    pub reason: String,
+}
+
+impl fmt::Display for SampleLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            Some(format) => write!(
+                f,
+                "could not decode {} as {}: {}",
+                self.path.display(),
+                format,
+                self.reason
+            ),
+            None => write!(f, "could not load {}: {}", self.path.display(), self.reason),
+        }
+    }
+}
+
+impl Error for SampleLoadError {}
 
 impl DrumMachine {
+    /// Decodes a sample into an interleaved stereo buffer at the engine sample rate.
+    pub fn load_sample(file_name: &str, path: &Path) -> Result<Arc<Vec<f32>>, SampleLoadError> {
+        let path = path.join(file_name);
+        let error = |format: Option<AudioFormat>, reason: String| SampleLoadError {
+            path: path.clone(),
+            format,
+            reason,
+        };
+
+        let format = AudioFormat::detect(&path)
+            .ok_or_else(|| error(None, "unsupported audio format".to_string()))?;
+        let file = BufReader::new(File::open(&path).map_err(|e| error(None, e.to_string()))?);
+        let source = match format {
+            AudioFormat::Wav => Decoder::new_wav(file),
+            AudioFormat::Flac => Decoder::new_flac(file),
+            AudioFormat::Ogg => Decoder::new_vorbis(file),
+            AudioFormat::Mp3 => Decoder::new_mp3(file),
+            // Probed by symphonia, which has no dedicated constructor for it in rodio.
+            AudioFormat::Aiff => Decoder::new(file),
+        }
+        .map_err(|e| error(Some(format), e.to_string()))?;
+
+        let samples: Vec<f32> =
+            UniformSourceIterator::new(source, ENGINE_CHANNELS, ENGINE_SAMPLE_RATE).collect();
+        if samples.is_empty() {
+            return Err(error(Some(format), "the file holds no audio".to_string()));
+        }
+        Ok(Arc::new(samples))
+    }
+
+    /// Queues a sample to start exactly at `frame` of the engine clock.
     pub fn play_audio(
-        stream_handle: &Arc<rodio::OutputStreamHandle>,
-        note_duration: Duration,
-        file_name: String,
-        path: &str,
-    ) {
-        let path = std::path::Path::new(path).join(&file_name);
-        let file = std::fs::File::open(path).unwrap();
-        let source = rodio::Decoder::new(std::io::BufReader::new(file)).unwrap();
-
-        let sink = Sink::try_new(stream_handle).unwrap();
-        sink.append(source);
-        sink.sleep_until_end()
-        // Use a separate thread to stop the sink after the note duration
-        // let sink_handle = sink.clone();
-        // std::thread::spawn(move || {
-        //     std::thread::sleep(note_duration);
-        //     sink.stop();
-        // });
+        engine: &EngineHandle,
+        sample_cache: &SampleCache,
+        frame: u64,
+        channel: Channel,
+        gain: f32,
+        file_name: &str,
+        path: &Path,
+    ) -> Result<(), SampleLoadError> {
+        let samples = sample_cache.get(file_name, path)?;
+        engine.schedule(frame, channel, Box::new(SampleVoice::new(samples, gain)));
+        Ok(())
     }
 }
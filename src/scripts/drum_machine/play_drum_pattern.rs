@@ -1,70 +1,77 @@
-use std::{
-    collections::{BTreeMap, HashMap},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex, RwLock,
-    },
-    thread,
-    time::{Duration, Instant},
-};
-
-use crossbeam_channel::{select, Receiver};
-use rodio::OutputStreamHandle;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::ui::{
-    drum_machine::{DrumMachine, SampleFolder, SequenceScale},
-    SequenceState,
+use crate::{
+    scripts::{
+        audio_engine::{engine::EngineHandle, mixer::Channel, voices::SampleVoice},
+        drum_machine::sample_cache::SampleCache,
+        transport::{StepWindow, Transport, TransportTrack},
+        trig_conditions::TrigContext,
+    },
+    ui::{
+        drum_machine::{DrumMachine, SampleFolder},
+        drum_step::DrumStep,
+        SequenceState,
+    },
 };
 
 impl DrumMachine {
+    /// Queues the steps of `beat_pattern` starting within the window into the audio engine,
+    /// each on the exact frame the transport maps its tick to.
     pub fn play_pattern(
-        sequence_state: Arc<Mutex<SequenceState>>,
-        is_playing: Arc<Mutex<bool>>,
-        stream_handle: &OutputStreamHandle,
-        selected_samples: Arc<RwLock<BTreeMap<usize, HashMap<String, SampleFolder>>>>,
-        root_sample_folder: &str,
+        sequence_state: &SequenceState,
+        beat_pattern: &[Vec<DrumStep>],
+        transport: &Transport,
+        window: StepWindow,
+        engine: &EngineHandle,
+        selected_samples: &BTreeMap<usize, HashMap<String, SampleFolder>>,
+        sample_cache: &SampleCache,
     ) {
-        while *is_playing.lock().unwrap() {
-            let sequence_state = sequence_state.lock().unwrap();
-            let beat_pattern = sequence_state.beat_pattern.clone();
-            let sequence_length = sequence_state.sequence_length;
-            let bpm = sequence_state.bpm;
+        let trig_context = TrigContext::new(sequence_state, TransportTrack::Drums);
 
-            let drum_scale = sequence_state.drum_scale;
-            drop(sequence_state);
-            let scale = match drum_scale {
-                SequenceScale::OneFourth => 1,
-                SequenceScale::OneEighth => 2,
-                SequenceScale::OneSixteenth => 4,
-                _ => 1,
-            };
-            let beat_duration = Duration::from_millis((60_000 / bpm) as u64) / scale;
+        // Every track walks its own row at its own resolution, so rows of different
+        // lengths drift against each other.
+        for (file_index, file_pattern) in beat_pattern.iter().enumerate() {
+            if file_pattern.is_empty() {
+                continue;
+            }
+            let step_ticks = sequence_state.drum_row_step_ticks(file_index);
 
-            for beat in 0..sequence_length {
-                if !*is_playing.lock().unwrap() {
-                    return;
+            for (step, frame) in transport.steps_in(
+                TransportTrack::Drums,
+                window,
+                step_ticks,
+                sequence_state.drum_row_groove(file_index),
+            ) {
+                let drum_step = &file_pattern[(step % file_pattern.len() as u64) as usize];
+                if !trig_context.fires(file_pattern, file_index, step) {
+                    continue;
                 }
-                let selected_samples = selected_samples.read().unwrap();
-                for (file_index, file_pattern) in beat_pattern.iter().enumerate() {
-                    if file_pattern[beat as usize] {
-                        if let Some(sample_map) = selected_samples.get(&file_index) {
-                            let sample_folder = sample_map.values().next().unwrap().to_string();
-                            let full_path = root_sample_folder.to_string() + "/" + &sample_folder;
-                            let sample_name = sample_map.keys().next().unwrap().clone();
-                            let stream_handle = Arc::new(stream_handle.clone());
+                if let Some(sample_map) = selected_samples.get(&file_index) {
+                    let sample_folder = sample_map.values().next().unwrap();
+                    let sample_name = sample_map.keys().next().unwrap();
+                    // A sample still being decoded sits this hit out, one that failed to
+                    // decode has already been reported to the UI by the cache.
+                    let Some(Ok(samples)) =
+                        sample_cache.get_or_queue(sample_name, sample_folder.path())
+                    else {
+                        continue;
+                    };
 
-                            thread::spawn(move || {
-                                Self::play_audio(
-                                    &stream_handle,
-                                    beat_duration,
-                                    sample_name,
-                                    &full_path,
-                                );
-                            });
-                        }
+                    // A ratchet splits the step into evenly spaced repeats.
+                    let ratchet = drum_step.ratchet;
+                    let frame = transport.offset_frame(frame, drum_step.nudge as f64);
+                    let repeat_ticks = step_ticks / ratchet.count.max(1) as f64;
+                    for repeat in 0..ratchet.count.max(1) {
+                        engine.schedule(
+                            frame + transport.frames_in(repeat as f64 * repeat_ticks),
+                            Channel::Drum(file_index),
+                            Box::new(SampleVoice::new(
+                                samples.clone(),
+                                drum_step.gain() * ratchet.level(repeat),
+                            )),
+                        );
                     }
                 }
-                thread::sleep(beat_duration);
             }
         }
     }
@@ -0,0 +1,138 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    thread,
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{
+    scripts::drum_machine::play_drum_audio::SampleLoadError,
+    ui::drum_machine::{DrumMachine, SampleFolder},
+};
+
+type CachedSample = Result<Arc<Vec<f32>>, SampleLoadError>;
+
+#[derive(Default)]
+struct CacheState {
+    samples: RwLock<HashMap<PathBuf, CachedSample>>,
+    /// Files queued for the decoder thread, so a file is only queued once.
+    queued: Mutex<HashSet<PathBuf>>,
+    /// Decodes that failed since the UI last asked, each reported once.
+    failures: Mutex<Vec<SampleLoadError>>,
+}
+
+impl CacheState {
+    fn decode(&self, file_name: &str, path: &Path) -> CachedSample {
+        let sample = DrumMachine::load_sample(file_name, path);
+        if let Err(e) = &sample {
+            self.failures.lock().unwrap().push(e.clone());
+        }
+        self.samples
+            .write()
+            .unwrap()
+            .insert(path.join(file_name), sample.clone());
+        sample
+    }
+}
+
+/// Decoded samples, already resampled to the engine rate, shared by live playback,
+/// previews and offline renders. Each file is decoded once; failures are remembered
+/// too so a broken file is not reread on every hit. Cloning shares the same cache.
+///
+/// The scheduler never decodes: it only takes what is cached and queues the rest for
+/// a decoder thread of the cache's own.
+#[derive(Clone)]
+pub struct SampleCache {
+    state: Arc<CacheState>,
+    decoder: Sender<(String, PathBuf)>,
+}
+
+impl Default for SampleCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SampleCache {
+    pub fn new() -> Self {
+        let state = Arc::new(CacheState::default());
+        let (decoder, requests) = crossbeam_channel::unbounded::<(String, PathBuf)>();
+        let decoder_state = state.clone();
+        thread::spawn(move || {
+            for (file_name, path) in requests {
+                let _ = decoder_state.decode(&file_name, &path);
+                decoder_state
+                    .queued
+                    .lock()
+                    .unwrap()
+                    .remove(&path.join(&file_name));
+            }
+        });
+        SampleCache { state, decoder }
+    }
+
+    /// Returns the sample, decoding it first when it is not cached yet. Blocks for the
+    /// decode, so it is for the UI and offline renders only.
+    pub fn get(&self, file_name: &str, path: &Path) -> CachedSample {
+        if let Some(sample) = self
+            .state
+            .samples
+            .read()
+            .unwrap()
+            .get(&path.join(file_name))
+        {
+            return sample.clone();
+        }
+        self.state.decode(file_name, path)
+    }
+
+    /// Returns the sample when it is cached and queues it for the decoder thread when
+    /// it is not, without ever blocking on a decode.
+    pub fn get_or_queue(&self, file_name: &str, path: &Path) -> Option<CachedSample> {
+        let key = path.join(file_name);
+        if let Some(sample) = self.state.samples.read().unwrap().get(&key) {
+            return Some(sample.clone());
+        }
+        if self.state.queued.lock().unwrap().insert(key) {
+            let _ = self
+                .decoder
+                .send((file_name.to_string(), path.to_path_buf()));
+        }
+        None
+    }
+
+    /// Decodes every selected sample ahead of playback.
+    pub fn preload(&self, selected_samples: &BTreeMap<usize, HashMap<String, SampleFolder>>) {
+        for (file_name, sample_folder) in selected_samples.values().flatten() {
+            let _ = self.get(file_name, sample_folder.path());
+        }
+    }
+
+    /// The decodes that failed since the last call.
+    pub fn take_failures(&self) -> Vec<SampleLoadError> {
+        std::mem::take(&mut *self.state.failures.lock().unwrap())
+    }
+
+    /// Drops the samples a change on disk at `path` may have made stale: everything
+    /// beneath a changed folder, or the whole kit of anything else. They are decoded
+    /// again on their next use.
+    pub fn evict(&self, path: &Path) {
+        let stale = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        };
+        self.state
+            .samples
+            .write()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(stale));
+    }
+
+    /// Drops every decoded sample.
+    pub fn clear(&self) {
+        self.state.samples.write().unwrap().clear();
+    }
+}
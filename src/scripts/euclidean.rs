@@ -0,0 +1,63 @@
+/// Spreads `hits` onsets as evenly as possible over `length` steps, the first one on
+/// step 0, then rotates the result `rotation` steps to the right.
+///
+/// Bresenham's line gives the same necklaces as Bjorklund's algorithm, e.g. 3 hits
+/// over 8 steps make the tresillo `x..x..x.`.
+pub fn euclidean_pattern(hits: u32, length: u32, rotation: u32) -> Vec<bool> {
+    let length = length as usize;
+    if length == 0 {
+        return Vec::new();
+    }
+    let hits = (hits as usize).min(length);
+    let rotation = rotation as usize % length;
+    (0..length)
+        .map(|step| {
+            let step = (step + length - rotation) % length;
+            step * hits % length < hits
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(hits: u32, length: u32, rotation: u32) -> String {
+        euclidean_pattern(hits, length, rotation)
+            .into_iter()
+            .map(|hit| if hit { 'x' } else { '.' })
+            .collect()
+    }
+
+    #[test]
+    fn spreads_hits_evenly() {
+        assert_eq!(pattern(3, 8, 0), "x..x..x.");
+        assert_eq!(pattern(4, 16, 0), "x...x...x...x...");
+        assert_eq!(pattern(5, 8, 0), "x.x.xx.x");
+        assert_eq!(pattern(2, 5, 0), "x..x.");
+        for length in 1..=16 {
+            for hits in 0..=length {
+                let hit_count = euclidean_pattern(hits, length, 0)
+                    .into_iter()
+                    .filter(|hit| *hit)
+                    .count();
+                assert_eq!(hit_count, hits as usize, "{} over {}", hits, length);
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_the_hits_to_the_length() {
+        assert_eq!(pattern(0, 4, 0), "....");
+        assert_eq!(pattern(6, 4, 0), "xxxx");
+        assert!(euclidean_pattern(3, 0, 1).is_empty());
+    }
+
+    #[test]
+    fn rotates_to_the_right() {
+        assert_eq!(pattern(3, 8, 1), ".x..x..x");
+        assert_eq!(pattern(3, 8, 2), "x.x..x..");
+        assert_eq!(pattern(3, 8, 8), pattern(3, 8, 0));
+        assert_eq!(pattern(3, 8, 11), pattern(3, 8, 3));
+    }
+}
@@ -0,0 +1,303 @@
+use std::{fmt, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    scripts::transport::{TransportTrack, TICKS_PER_BEAT},
+    ui::SequenceState,
+};
+
+/// Folder holding user groove templates, one JSON file per template.
+pub const GROOVES_FOLDER: &str = "grooves";
+pub const MIN_SWING: u32 = 50;
+pub const MAX_SWING: u32 = 75;
+
+/// The note value swing and template offsets are counted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SwingResolution {
+    Eighth,
+    #[default]
+    Sixteenth,
+}
+
+impl fmt::Display for SwingResolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SwingResolution::Eighth => write!(f, "1/8"),
+            SwingResolution::Sixteenth => write!(f, "1/16"),
+        }
+    }
+}
+
+impl SwingResolution {
+    pub fn unit_ticks(&self) -> f64 {
+        match self {
+            SwingResolution::Eighth => TICKS_PER_BEAT as f64 / 2.0,
+            SwingResolution::Sixteenth => TICKS_PER_BEAT as f64 / 4.0,
+        }
+    }
+}
+
+/// Timing offsets for consecutive units of `resolution`, repeating over the pattern.
+/// Each offset is a fraction of a unit, positive values play late.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrooveTemplate {
+    pub name: String,
+    pub resolution: SwingResolution,
+    pub offsets: Vec<f64>,
+}
+
+impl fmt::Display for GrooveTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Default for GrooveTemplate {
+    fn default() -> Self {
+        GrooveTemplate {
+            name: "Straight".to_string(),
+            resolution: SwingResolution::Sixteenth,
+            offsets: Vec::new(),
+        }
+    }
+}
+
+impl GrooveTemplate {
+    /// An MPC-style swing, where the first unit of each pair takes `swing` percent
+    /// of the pair.
+    fn swing(name: &str, resolution: SwingResolution, swing: f64) -> Self {
+        GrooveTemplate {
+            name: name.to_string(),
+            resolution,
+            offsets: vec![0.0, 2.0 * swing / 100.0 - 1.0],
+        }
+    }
+
+    fn offset_ticks(&self, tick: f64) -> f64 {
+        if self.offsets.is_empty() {
+            return 0.0;
+        }
+        let unit_ticks = self.resolution.unit_ticks();
+        let unit = (tick / unit_ticks).floor().max(0.0) as usize;
+        // Offsets are limited to half a unit so steps never swap places.
+        self.offsets[unit % self.offsets.len()].clamp(-0.5, 0.5) * unit_ticks
+    }
+}
+
+/// How one sequencer track leans off the grid: a swing amount on top of a template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Groove {
+    /// Percent of each pair of units taken by the first one, 50 is straight.
+    pub swing: u32,
+    pub resolution: SwingResolution,
+    pub template: GrooveTemplate,
+}
+
+impl Default for Groove {
+    fn default() -> Self {
+        Groove {
+            swing: MIN_SWING,
+            resolution: SwingResolution::default(),
+            template: GrooveTemplate::default(),
+        }
+    }
+}
+
+impl Groove {
+    /// Whether `other` differs from this groove in its swing amount at most, as it
+    /// does while the swing slider is dragged.
+    pub fn same_feel(&self, other: &Groove) -> bool {
+        self.resolution == other.resolution && self.template == other.template
+    }
+
+    /// How far a step lying `tick` ticks after the pattern start is moved, in ticks.
+    pub fn shift_ticks(&self, tick: f64) -> f64 {
+        self.swing_ticks(tick) + self.template.offset_ticks(tick)
+    }
+
+    // Stretches the first unit of every pair and squeezes the second, which delays
+    // the off-beat units and anything between them proportionally.
+    fn swing_ticks(&self, tick: f64) -> f64 {
+        let swing = self.swing.clamp(MIN_SWING, MAX_SWING) as f64 / 100.0;
+        let unit_ticks = self.resolution.unit_ticks();
+        let pair_ticks = 2.0 * unit_ticks;
+        let position = tick.rem_euclid(pair_ticks);
+        let swung = if position < unit_ticks {
+            position * 2.0 * swing
+        } else {
+            pair_ticks * swing + (position - unit_ticks) * 2.0 * (1.0 - swing)
+        };
+        swung - position
+    }
+}
+
+impl SequenceState {
+    /// The groove of a track, its own when it has one and the global one otherwise.
+    pub fn track_groove(&self, track: TransportTrack) -> &Groove {
+        let own = match track {
+            TransportTrack::Drums => &self.drum_groove,
+            TransportTrack::Synth => &self.synth_groove,
+        };
+        own.as_ref().unwrap_or(&self.groove)
+    }
+
+    /// The groove of a drum row: its own, the drums' or the global one.
+    pub fn drum_row_groove(&self, index: usize) -> &Groove {
+        self.drum_row_grooves
+            .get(index)
+            .and_then(Option::as_ref)
+            .unwrap_or_else(|| self.track_groove(TransportTrack::Drums))
+    }
+
+    pub fn set_drum_row_groove(&mut self, index: usize, groove: Option<Groove>) {
+        if self.drum_row_grooves.len() <= index {
+            self.drum_row_grooves.resize(index + 1, None);
+        }
+        self.drum_row_grooves[index] = groove;
+    }
+}
+
+/// The built-in templates followed by every template found in `GROOVES_FOLDER`.
+pub fn load_groove_templates() -> Vec<GrooveTemplate> {
+    let mut templates = vec![
+        GrooveTemplate::default(),
+        GrooveTemplate::swing("MPC 54%", SwingResolution::Sixteenth, 54.0),
+        GrooveTemplate::swing("MPC 58%", SwingResolution::Sixteenth, 58.0),
+        GrooveTemplate::swing("MPC 62%", SwingResolution::Sixteenth, 62.0),
+        GrooveTemplate::swing("MPC 66%", SwingResolution::Sixteenth, 66.0),
+        GrooveTemplate::swing("MPC 71%", SwingResolution::Sixteenth, 71.0),
+        GrooveTemplate::swing("MPC 75%", SwingResolution::Sixteenth, 75.0),
+        // Triplet feel: the off-beat eighth lands on the last note of a triplet.
+        GrooveTemplate::swing("Shuffle", SwingResolution::Eighth, 200.0 / 3.0),
+    ];
+
+    let Ok(entries) = fs::read_dir(GROOVES_FOLDER) else {
+        return templates;
+    };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        match load_groove_template(&path) {
+            Ok(template) => templates.push(template),
+            Err(e) => println!("Error loading groove {}: {}", path.display(), e),
+        }
+    }
+    templates
+}
+
+fn load_groove_template(path: &Path) -> Result<GrooveTemplate, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groove(swing: u32, resolution: SwingResolution) -> Groove {
+        Groove {
+            swing,
+            resolution,
+            template: GrooveTemplate::default(),
+        }
+    }
+
+    fn assert_ticks(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn straight_grooves_leave_steps_on_the_grid() {
+        let straight = Groove::default();
+        for tick in [0.0, 12.0, 24.0, 36.0, 48.0, 1000.0] {
+            assert_ticks(straight.shift_ticks(tick), 0.0);
+        }
+    }
+
+    #[test]
+    fn swing_delays_the_off_beat_unit() {
+        let swing = groove(66, SwingResolution::Sixteenth);
+        // Sixteenths are 24 ticks, the off-beat one moves to 66% of the pair of 48.
+        assert_ticks(swing.shift_ticks(0.0), 0.0);
+        assert_ticks(swing.shift_ticks(24.0), 48.0 * 0.66 - 24.0);
+        assert_ticks(swing.shift_ticks(48.0), 0.0);
+        assert_ticks(swing.shift_ticks(72.0 + 96.0), 48.0 * 0.66 - 24.0);
+        // Anything between moves proportionally.
+        assert_ticks(swing.shift_ticks(12.0), 12.0 * 1.32 - 12.0);
+        assert_ticks(swing.shift_ticks(36.0), 48.0 * 0.66 + 12.0 * 0.68 - 36.0);
+
+        let shuffle = groove(75, SwingResolution::Eighth);
+        assert_ticks(shuffle.shift_ticks(48.0), 24.0);
+    }
+
+    #[test]
+    fn swing_is_clamped() {
+        let too_much = groove(90, SwingResolution::Sixteenth);
+        assert_ticks(too_much.shift_ticks(24.0), 12.0);
+        let too_little = groove(10, SwingResolution::Sixteenth);
+        assert_ticks(too_little.shift_ticks(24.0), 0.0);
+    }
+
+    #[test]
+    fn templates_add_their_offsets() {
+        let mpc = GrooveTemplate::swing("MPC 62%", SwingResolution::Sixteenth, 62.0);
+        let templated = Groove {
+            template: mpc,
+            ..Groove::default()
+        };
+        // The MPC template matches the same amount of swing on the off-beats.
+        let swung = groove(62, SwingResolution::Sixteenth);
+        for tick in [0.0, 24.0, 48.0, 72.0] {
+            assert_ticks(templated.shift_ticks(tick), swung.shift_ticks(tick));
+        }
+
+        let both = Groove {
+            swing: 66,
+            ..templated
+        };
+        assert_ticks(
+            both.shift_ticks(24.0),
+            groove(66, SwingResolution::Sixteenth).shift_ticks(24.0) + 0.24 * 24.0,
+        );
+
+        // Offsets stop at half a unit.
+        let wild = Groove {
+            template: GrooveTemplate {
+                offsets: vec![0.0, 0.9, -0.8],
+                ..GrooveTemplate::default()
+            },
+            ..Groove::default()
+        };
+        assert_ticks(wild.shift_ticks(24.0), 12.0);
+        assert_ticks(wild.shift_ticks(48.0), -12.0);
+        assert_ticks(wild.shift_ticks(72.0), 0.0);
+    }
+
+    #[test]
+    fn drum_rows_fall_back_to_the_drum_and_global_grooves() {
+        let mut sequence_state = SequenceState {
+            groove: groove(54, SwingResolution::Sixteenth),
+            ..SequenceState::default()
+        };
+        assert_eq!(sequence_state.drum_row_groove(0).swing, 54);
+
+        sequence_state.drum_groove = Some(groove(58, SwingResolution::Sixteenth));
+        sequence_state.set_drum_row_groove(2, Some(groove(66, SwingResolution::Eighth)));
+        assert_eq!(sequence_state.drum_row_groove(0).swing, 58);
+        assert_eq!(sequence_state.drum_row_groove(1).swing, 58);
+        assert_eq!(sequence_state.drum_row_groove(2).swing, 66);
+        assert_eq!(sequence_state.track_groove(TransportTrack::Synth).swing, 54);
+    }
+}
@@ -1,3 +1,12 @@
+pub mod audio_engine;
 pub mod drum_machine;
+pub mod euclidean;
+pub mod groove;
+pub mod pattern_tools;
+pub mod project_file;
 pub mod record_pattern;
+pub mod render_project;
+pub mod sequencer;
 pub mod synth;
+pub mod transport;
+pub mod trig_conditions;
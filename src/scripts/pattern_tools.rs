@@ -0,0 +1,285 @@
+use std::ops::RangeInclusive;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    scripts::transport::TransportTrack,
+    ui::{
+        drum_step::{MAX_NUDGE, MAX_VELOCITY},
+        step_trigger::Step,
+        SequenceState,
+    },
+};
+
+/// The rows and steps a pattern tool works on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    /// `None` selects every row of the track.
+    pub row: Option<usize>,
+    /// Zero based, clipped to the length of each row.
+    pub steps: RangeInclusive<usize>,
+}
+
+/// The density of random fill for rows without one of their own.
+pub const DEFAULT_DENSITY: u8 = 25;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternTool {
+    /// Clears the selection, then turns on each step with a percent chance of the
+    /// density of its row, indexed like the rows of the track.
+    RandomFill {
+        densities: Vec<u8>,
+    },
+    /// Flips each step with an `amount` percent chance.
+    Mutate {
+        amount: u8,
+    },
+    /// Rotates the selected steps by one, to the right when `right` is set.
+    Rotate {
+        right: bool,
+    },
+    Reverse,
+    /// Moves the velocity of each active step by up to `velocity` percent of
+    /// `MAX_VELOCITY` and its timing by up to `timing` percent of `MAX_NUDGE`, either
+    /// way from where they are.
+    Humanize {
+        velocity: u8,
+        timing: u8,
+    },
+}
+
+impl SequenceState {
+    /// Runs `tool` over the selected steps of the working pattern of `track`.
+    ///
+    /// Each row draws from its own generator seeded with `seed` and the row's place, so
+    /// a tool gives the same result every time it runs on the same selection.
+    pub fn apply_pattern_tool(
+        &mut self,
+        track: TransportTrack,
+        selection: &Selection,
+        tool: &PatternTool,
+        seed: u64,
+    ) {
+        match track {
+            TransportTrack::Drums => apply_to_rows(
+                &mut self.beat_pattern,
+                track,
+                selection,
+                tool,
+                seed,
+                |step, rng| {
+                    if let PatternTool::Humanize { velocity, .. } = *tool {
+                        let range = (velocity as i32 * MAX_VELOCITY as i32 / 100).max(0);
+                        let offset = rng.gen_range(-range..=range);
+                        step.velocity =
+                            (step.velocity as i32 + offset).clamp(1, MAX_VELOCITY as i32) as u8;
+                    }
+                },
+            ),
+            TransportTrack::Synth => apply_to_rows(
+                &mut self.note_pattern,
+                track,
+                selection,
+                tool,
+                seed,
+                |_, _| {},
+            ),
+        }
+    }
+}
+
+fn apply_to_rows<S: Step>(
+    rows: &mut [Vec<S>],
+    track: TransportTrack,
+    selection: &Selection,
+    tool: &PatternTool,
+    seed: u64,
+    humanize_step: impl Fn(&mut S, &mut StdRng),
+) {
+    for (row_index, row) in rows.iter_mut().enumerate() {
+        if selection.row.is_some_and(|selected| selected != row_index) || row.is_empty() {
+            continue;
+        }
+        let end = (*selection.steps.end()).min(row.len() - 1);
+        if *selection.steps.start() > end {
+            continue;
+        }
+        let steps = &mut row[*selection.steps.start()..=end];
+        let row_id = ((track as u64) << 32) | row_index as u64;
+        let mut rng = StdRng::seed_from_u64(seed ^ row_id.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+
+        match tool {
+            PatternTool::RandomFill { densities } => {
+                let density = densities.get(row_index).copied().unwrap_or(DEFAULT_DENSITY);
+                for step in steps.iter_mut() {
+                    step.set_active(rng.gen_range(0..100) < density);
+                }
+            }
+            PatternTool::Mutate { amount } => {
+                for step in steps.iter_mut() {
+                    if rng.gen_range(0..100) < *amount {
+                        step.set_active(!step.is_active());
+                    }
+                }
+            }
+            PatternTool::Rotate { right: true } => steps.rotate_right(1),
+            PatternTool::Rotate { right: false } => steps.rotate_left(1),
+            PatternTool::Reverse => steps.reverse(),
+            PatternTool::Humanize { timing, .. } => {
+                let range = (*timing as i32 * MAX_NUDGE as i32 / 100).max(0);
+                for step in steps.iter_mut().filter(|step| step.is_active()) {
+                    let nudge = step.nudge() as i32 + rng.gen_range(-range..=range);
+                    step.set_nudge(nudge.clamp(-(MAX_NUDGE as i32), MAX_NUDGE as i32) as i8);
+                    humanize_step(step, &mut rng);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::{drum_step::DrumStep, note_step::NoteStep};
+
+    use super::*;
+
+    const SEED: u64 = 42;
+
+    fn sequence_state() -> SequenceState {
+        let mut sequence_state = SequenceState {
+            beat_pattern: vec![vec![DrumStep::default(); 16]; 3],
+            ..SequenceState::default()
+        };
+        for (row_index, row) in sequence_state.beat_pattern.iter_mut().enumerate() {
+            for (step_index, step) in row.iter_mut().enumerate() {
+                step.active = (step_index + row_index) % 3 == 0;
+            }
+        }
+        sequence_state
+    }
+
+    fn tools() -> Vec<PatternTool> {
+        vec![
+            PatternTool::RandomFill {
+                densities: vec![50, 80],
+            },
+            PatternTool::Mutate { amount: 50 },
+            PatternTool::Rotate { right: true },
+            PatternTool::Rotate { right: false },
+            PatternTool::Reverse,
+            PatternTool::Humanize {
+                velocity: 50,
+                timing: 100,
+            },
+        ]
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_result() {
+        let selection = Selection {
+            row: None,
+            steps: 0..=15,
+        };
+        for tool in tools() {
+            let mut first = sequence_state();
+            first.apply_pattern_tool(TransportTrack::Drums, &selection, &tool, SEED);
+            let mut second = sequence_state();
+            second.apply_pattern_tool(TransportTrack::Drums, &selection, &tool, SEED);
+            assert_eq!(first.beat_pattern, second.beat_pattern, "{:?}", tool);
+        }
+
+        // Another seed fills differently.
+        let fill = PatternTool::RandomFill {
+            densities: vec![50; 3],
+        };
+        let mut first = sequence_state();
+        first.apply_pattern_tool(TransportTrack::Drums, &selection, &fill, SEED);
+        let mut other = sequence_state();
+        other.apply_pattern_tool(TransportTrack::Drums, &selection, &fill, SEED + 1);
+        assert_ne!(first.beat_pattern, other.beat_pattern);
+    }
+
+    #[test]
+    fn steps_outside_the_selection_are_left_alone() {
+        let selection = Selection {
+            row: Some(1),
+            steps: 4..=11,
+        };
+        for tool in tools() {
+            let original = sequence_state();
+            let mut edited = original.clone();
+            edited.apply_pattern_tool(TransportTrack::Drums, &selection, &tool, SEED);
+            for (row_index, (row, original_row)) in edited
+                .beat_pattern
+                .iter()
+                .zip(&original.beat_pattern)
+                .enumerate()
+            {
+                for (step_index, (step, original_step)) in row.iter().zip(original_row).enumerate()
+                {
+                    if row_index != 1 || !selection.steps.contains(&step_index) {
+                        assert_eq!(step, original_step, "{:?}", tool);
+                    }
+                }
+            }
+        }
+
+        // A selection past the end of the rows is clipped to them.
+        let mut notes = SequenceState {
+            note_pattern: vec![vec![NoteStep::default(); 8]; 2],
+            ..SequenceState::default()
+        };
+        notes.apply_pattern_tool(
+            TransportTrack::Synth,
+            &Selection {
+                row: None,
+                steps: 4..=40,
+            },
+            &PatternTool::RandomFill {
+                densities: vec![100; 2],
+            },
+            SEED,
+        );
+        for row in &notes.note_pattern {
+            let active: Vec<bool> = row.iter().map(|step| step.active).collect();
+            assert_eq!(active, [false, false, false, false, true, true, true, true]);
+        }
+    }
+
+    #[test]
+    fn humanize_offsets_velocity_and_timing_from_where_they_are() {
+        let mut sequence_state = sequence_state();
+        for step in sequence_state.beat_pattern.iter_mut().flatten() {
+            step.velocity = 100;
+            step.nudge = MAX_NUDGE;
+        }
+        let original = sequence_state.clone();
+        sequence_state.apply_pattern_tool(
+            TransportTrack::Drums,
+            &Selection {
+                row: None,
+                steps: 0..=15,
+            },
+            &PatternTool::Humanize {
+                velocity: 10,
+                timing: 50,
+            },
+            SEED,
+        );
+        let velocity_range = 10 * MAX_VELOCITY as i32 / 100;
+        let nudge_range = 50 * MAX_NUDGE as i32 / 100;
+        for (step, original) in sequence_state
+            .beat_pattern
+            .iter()
+            .flatten()
+            .zip(original.beat_pattern.iter().flatten())
+        {
+            if !original.active {
+                assert_eq!(step, original);
+                continue;
+            }
+            assert!((step.velocity as i32 - 100).abs() <= velocity_range);
+            assert!(step.nudge >= MAX_NUDGE - nudge_range as i8 && step.nudge <= MAX_NUDGE);
+        }
+    }
+}
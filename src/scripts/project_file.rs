@@ -0,0 +1,261 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ui::{drum_machine::SampleFolder, track_timing::TrackTiming, SequenceState};
+
+pub const PROJECT_VERSION: u32 = 2;
+
+/// The on-disk layout of a project. The sequence state is flattened into the top
+/// level of the JSON document so the file stays easy to read and diff.
+#[derive(Serialize, Deserialize)]
+pub struct ProjectFile {
+    pub version: u32,
+    #[serde(flatten)]
+    pub sequence_state: SequenceState,
+    pub selected_samples: Vec<ProjectSample>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectSample {
+    pub file_name: String,
+    pub sample_folder: SampleFolder,
+}
+
+pub struct LoadedProject {
+    pub sequence_state: SequenceState,
+    pub selected_samples: BTreeMap<usize, HashMap<String, SampleFolder>>,
+    /// Samples referenced by the project that could not be found on disk.
+    pub missing_samples: Vec<PathBuf>,
+}
+
+pub fn save_project(
+    path: &Path,
+    sequence_state: &SequenceState,
+    selected_samples: &BTreeMap<usize, HashMap<String, SampleFolder>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let selected_samples = selected_samples
+        .values()
+        .filter_map(|file_map| file_map.iter().next())
+        .map(|(file_name, sample_folder)| ProjectSample {
+            file_name: file_name.clone(),
+            sample_folder: sample_folder.clone(),
+        })
+        .collect();
+
+    let mut sequence_state = sequence_state.clone();
+    sequence_state.sync_current_pattern();
+    let project = ProjectFile {
+        version: PROJECT_VERSION,
+        sequence_state,
+        selected_samples,
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&project)?)?;
+    Ok(())
+}
+
+pub fn load_project(
+    path: &Path,
+    root_sample_folder: &str,
+) -> Result<LoadedProject, Box<dyn std::error::Error>> {
+    let project: ProjectFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if project.version > PROJECT_VERSION {
+        return Err(format!(
+            "project version {} is newer than the supported version {}",
+            project.version, PROJECT_VERSION
+        )
+        .into());
+    }
+
+    let mut sequence_state = project.sequence_state;
+
+    // Projects saved before the pattern bank existed hold a single pattern.
+    sequence_state.ensure_pattern_bank();
+
+    // Every drum row needs a sample and every row needs a step for each step of its
+    // track, the views index into the patterns directly.
+    let sample_count = project.selected_samples.len();
+    let current_pattern = sequence_state.current_pattern;
+    sequence_state
+        .drum_timing
+        .resize(sample_count, TrackTiming::default());
+    sequence_state.drum_row_grooves.resize(sample_count, None);
+    for pattern in sequence_state.patterns.iter_mut() {
+        pattern.beat_pattern.resize(sample_count, Vec::new());
+        pattern.note_pattern.resize(12, Vec::new());
+    }
+    sequence_state.beat_pattern = sequence_state.patterns[current_pattern]
+        .beat_pattern
+        .clone();
+    sequence_state.note_pattern = sequence_state.patterns[current_pattern]
+        .note_pattern
+        .clone();
+    sequence_state.resize_rows();
+
+    // Version 1 stored kit folders relative to the root sample folder.
+    let mut project_samples = project.selected_samples;
+    if project.version < 2 {
+        for sample in project_samples.iter_mut() {
+            sample.sample_folder =
+                SampleFolder::new(Path::new(root_sample_folder).join(sample.sample_folder.path()));
+        }
+    }
+
+    let pattern_count = sequence_state.patterns.len();
+    for track in sequence_state.arrangement.tracks.iter_mut() {
+        track.clips.retain(|clip| clip.pattern < pattern_count);
+    }
+    // The arranger needs at least one bar and a loop region inside the song.
+    let length_bars = sequence_state.arrangement.length_bars;
+    sequence_state.arrangement.set_length(length_bars);
+
+    let missing_samples = project_samples
+        .iter()
+        .map(|sample| sample.sample_folder.path().join(&sample.file_name))
+        .filter(|sample_path| !sample_path.is_file())
+        .collect();
+
+    let selected_samples = project_samples
+        .into_iter()
+        .map(|sample| HashMap::from([(sample.file_name, sample.sample_folder)]))
+        .enumerate()
+        .collect();
+
+    Ok(LoadedProject {
+        sequence_state,
+        selected_samples,
+        missing_samples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        scripts::groove::Groove,
+        ui::{drum_machine::SequenceScale, drum_step::DrumStep},
+    };
+
+    use super::*;
+
+    // A folder of its own under the system temp folder, so tests can run side by side.
+    fn scratch_folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("project_file_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn saved_projects_load_back_unchanged() {
+        let folder = scratch_folder("round_trip");
+        let kit = SampleFolder::new(folder.join("kit"));
+        fs::create_dir_all(kit.path()).unwrap();
+        fs::write(kit.path().join("kick.wav"), []).unwrap();
+
+        let mut sequence_state = SequenceState {
+            bpm: 97,
+            beat_pattern: vec![vec![DrumStep::default(); 16]; 2],
+            ..SequenceState::default()
+        };
+        sequence_state.beat_pattern[0][4].active = true;
+        sequence_state.beat_pattern[1][2].velocity = 40;
+        sequence_state.note_pattern[5][7].active = true;
+        sequence_state.set_drum_timing(
+            1,
+            TrackTiming {
+                length: Some(12),
+                scale: Some(SequenceScale::OneEighth),
+            },
+        );
+        sequence_state.set_drum_row_groove(
+            1,
+            Some(Groove {
+                swing: 60,
+                ..Groove::default()
+            }),
+        );
+        sequence_state.ensure_pattern_bank();
+        let selected_samples = BTreeMap::from([
+            (0, HashMap::from([("kick.wav".to_string(), kit.clone())])),
+            (1, HashMap::from([("snare.wav".to_string(), kit.clone())])),
+        ]);
+
+        let path = folder.join("song.json");
+        save_project(&path, &sequence_state, &selected_samples).unwrap();
+        let loaded = load_project(&path, "unused").unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&loaded.sequence_state).unwrap(),
+            serde_json::to_value(&sequence_state).unwrap()
+        );
+        assert_eq!(loaded.selected_samples, selected_samples);
+        assert_eq!(loaded.missing_samples, vec![kit.path().join("snare.wav")]);
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn version_one_projects_still_load() {
+        let folder = scratch_folder("version_one");
+        fs::create_dir_all(folder.join("909")).unwrap();
+        fs::write(folder.join("909").join("kick.wav"), []).unwrap();
+
+        // Version 1 stored plain bool steps and kit folders relative to the root.
+        let path = folder.join("old.json");
+        let row = |active: &[usize]| (0..8).map(|step| active.contains(&step)).collect();
+        let beat_pattern: Vec<Vec<bool>> = vec![row(&[0, 4]), row(&[2, 6])];
+        let note_pattern: Vec<Vec<bool>> = (0..12).map(|note| row(&[note % 8])).collect();
+        let project = serde_json::json!({
+            "version": 1,
+            "sequence_length": 8,
+            "beat_pattern": beat_pattern,
+            "note_pattern": note_pattern,
+            "bpm": 128,
+            "drum_scale": "OneSixteenth",
+            "synth_scale": "OneEighth",
+            "octave": 0,
+            "frequency": 440.0,
+            "selected_samples": [
+                { "file_name": "kick.wav", "sample_folder": "909" },
+                { "file_name": "hat.wav", "sample_folder": "909" },
+            ],
+        });
+        fs::write(&path, project.to_string()).unwrap();
+
+        let loaded = load_project(&path, folder.to_str().unwrap()).unwrap();
+        let sequence_state = &loaded.sequence_state;
+        assert_eq!(sequence_state.bpm, 128);
+        assert_eq!(sequence_state.synth_scale, SequenceScale::OneEighth);
+        let active = |row: &[DrumStep]| -> Vec<usize> {
+            (0..row.len()).filter(|step| row[*step].active).collect()
+        };
+        assert_eq!(active(&sequence_state.beat_pattern[0]), [0, 4]);
+        assert_eq!(active(&sequence_state.beat_pattern[1]), [2, 6]);
+        assert!(sequence_state.beat_pattern[0]
+            .iter()
+            .all(|drum_step| drum_step.velocity == DrumStep::default().velocity));
+        assert!(sequence_state.note_pattern[3][3].active);
+        assert!(!sequence_state.note_pattern[3][4].active);
+        assert_eq!(sequence_state.patterns.len(), 1);
+        assert_eq!(
+            sequence_state.patterns[0].beat_pattern,
+            sequence_state.beat_pattern
+        );
+
+        let kit = SampleFolder::new(folder.join("909"));
+        assert_eq!(
+            loaded.selected_samples[&0],
+            HashMap::from([("kick.wav".to_string(), kit.clone())])
+        );
+        assert_eq!(loaded.missing_samples, vec![kit.path().join("hat.wav")]);
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
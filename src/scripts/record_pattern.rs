@@ -1,78 +1,36 @@
 use hound::{WavSpec, WavWriter};
-use rodio::{Decoder, Source};
 use std::collections::{BTreeMap, HashMap};
-use std::fs::File;
-use std::io::BufReader;
+use std::fs;
 use std::path::Path;
-use std::sync::{Arc, RwLock};
 
-use crate::ui::drum_machine::SampleFolder;
+use crate::scripts::{
+    audio_engine::engine::ENGINE_SAMPLE_RATE, drum_machine::sample_cache::SampleCache,
+    render_project::render_project,
+};
+use crate::ui::{drum_machine::SampleFolder, SequenceState};
 
 pub fn record_pattern(
-    beat_pattern: &Vec<Vec<bool>>,
-    audio_files: &Vec<String>,
-    bpm: u32,
-    sequence_length: u32,
-    selected_samples: &Arc<RwLock<BTreeMap<usize, HashMap<String, SampleFolder>>>>,
+    sequence_state: &SequenceState,
+    selected_samples: &BTreeMap<usize, HashMap<String, SampleFolder>>,
+    sample_cache: &SampleCache,
     output_file: &str,
-    root_sample_path: &str,
-    beat_scale: u32,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let spec = WavSpec {
         channels: 2,
-        sample_rate: 44100,
+        sample_rate: ENGINE_SAMPLE_RATE,
         bits_per_sample: 16,
         sample_format: hound::SampleFormat::Int,
     };
-    let selected_samples = selected_samples.read().unwrap(); //might cause deadlock
 
-    let path = Path::new("recorded_patterns").join(output_file);
-    let mut writer = WavWriter::create(path, spec)?;
+    let directory = Path::new("recorded_patterns");
+    fs::create_dir_all(directory)?;
+    let mut writer = WavWriter::create(directory.join(output_file), spec)?;
 
-    let beat_duration =
-        (60.0 / bpm as f32 * spec.sample_rate as f32) as usize / beat_scale as usize;
-    let total_samples = beat_duration * sequence_length as usize;
-    let mut mixed_buffer = vec![(0i16, 0i16); total_samples];
-
-    // Load and decode all audio samples
-    let mut decoded_samples = BTreeMap::new();
-    for (index, file_map) in selected_samples.iter() {
-        let sample_folder = file_map.values().next().unwrap().to_string();
-        let full_path = root_sample_path.to_string() + &sample_folder;
-
-        let path = Path::new(&full_path).join(file_map.keys().next().unwrap());
-        let file = BufReader::new(File::open(path)?);
-        let source = Decoder::new(file)?;
-        let samples: Vec<i16> = source.convert_samples().collect();
-        decoded_samples.insert(*index, samples);
-    }
-    // Mix samples according to the beat pattern
-    for (file_index, file_pattern) in beat_pattern.iter().enumerate() {
-        if let Some(samples) = decoded_samples.get(&file_index) {
-            for (beat, &active) in file_pattern.iter().enumerate() {
-                if active {
-                    let start = beat * beat_duration;
-                    for (i, &sample) in samples.iter().enumerate().step_by(2) {
-                        if start + i < total_samples {
-                            mixed_buffer[start + i].0 =
-                                mixed_buffer[start + i].0.saturating_add(sample);
-                            if i + 1 < samples.len() {
-                                mixed_buffer[start + i].1 =
-                                    mixed_buffer[start + i].1.saturating_add(samples[i + 1]);
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                }
-            }
-        }
-    }
+    let mixed_buffer = render_project(sequence_state, selected_samples, sample_cache, 1);
 
     // Write mixed buffer to WAV file
-    for &(left, right) in &mixed_buffer {
-        writer.write_sample(left)?;
-        writer.write_sample(right)?;
+    for sample in mixed_buffer {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
     }
 
     writer.finalize()?;
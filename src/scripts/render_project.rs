@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    scripts::{
+        audio_engine::engine::{EngineHandle, ENGINE_SAMPLE_RATE},
+        drum_machine::sample_cache::SampleCache,
+        sequencer::schedule_until,
+        transport::{Transport, TransportTrack},
+    },
+    ui::{drum_machine::SampleFolder, SequenceState},
+};
+
+// The longest the notes still sounding at the end are left to ring out for.
+const MAX_TAIL_SECONDS: u64 = 10;
+
+/// Renders `loops` passes of the selected pattern, or the whole song once in song mode,
+/// into an interleaved stereo buffer at the engine sample rate. It goes through the same
+/// transport, schedulers and voices as live playback, so the result matches what is
+/// heard, without needing a device. Notes still sounding at the end ring out after it,
+/// for up to `MAX_TAIL_SECONDS`.
+pub fn render_project(
+    sequence_state: &SequenceState,
+    selected_samples: &BTreeMap<usize, HashMap<String, SampleFolder>>,
+    sample_cache: &SampleCache,
+    loops: u32,
+) -> Vec<f32> {
+    // The schedulers skip samples that are not decoded yet, which live playback makes up
+    // for on the next pass, but a render only gets the one.
+    sample_cache.preload(selected_samples);
+    let (engine, mut core) = EngineHandle::offline();
+    engine.set_mix(sequence_state.mix_graph());
+    let mut transport = Transport::new(sequence_state.bpm, engine.sample_rate);
+    transport.set_time_signature(sequence_state.time_signature);
+    transport.play(TransportTrack::Drums, 0);
+    transport.play(TransportTrack::Synth, 0);
+
+    let end_tick = if sequence_state.arrangement.song_mode {
+        (sequence_state.arrangement.length_bars as f64) * transport.ticks_per_bar()
+    } else {
+        sequence_state.pattern_ticks() * loops as f64
+    };
+    let total_frames = transport.frame_at_tick(end_tick);
+    schedule_until(
+        total_frames,
+        &mut transport,
+        sequence_state,
+        selected_samples,
+        sample_cache,
+        &engine,
+    );
+
+    let mut buffer = Vec::with_capacity(total_frames as usize * 2);
+    for _ in 0..total_frames {
+        buffer.extend_from_slice(&core.render_frame());
+    }
+    for _ in 0..MAX_TAIL_SECONDS * ENGINE_SAMPLE_RATE as u64 {
+        if core.is_idle() {
+            break;
+        }
+        buffer.extend_from_slice(&core.render_frame());
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_pattern_and_lets_the_last_note_ring_out() {
+        let mut sequence_state = SequenceState::default();
+        sequence_state.ensure_pattern_bank();
+        let last_step = sequence_state.synth_row_length() as usize - 1;
+        sequence_state.note_pattern[0][0].active = true;
+        sequence_state.note_pattern[0][last_step].active = true;
+
+        let buffer = render_project(
+            &sequence_state,
+            &BTreeMap::new(),
+            &SampleCache::default(),
+            1,
+        );
+
+        let pattern_frames = Transport::new(sequence_state.bpm, ENGINE_SAMPLE_RATE)
+            .frame_at_tick(sequence_state.pattern_ticks());
+        let frames = buffer.len() as u64 / 2;
+        assert!(
+            frames > pattern_frames,
+            "the release of the last note is cut off"
+        );
+        assert!(frames < pattern_frames + MAX_TAIL_SECONDS * ENGINE_SAMPLE_RATE as u64);
+        assert!(buffer.iter().any(|sample| sample.abs() > 0.01));
+        assert!(buffer[pattern_frames as usize * 2..]
+            .iter()
+            .any(|sample| sample.abs() > 0.001));
+    }
+}
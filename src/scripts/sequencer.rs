@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, RwLock},
+    thread,
+};
+
+use crate::{
+    scripts::{
+        audio_engine::engine::{EngineHandle, SCHEDULER_INTERVAL},
+        drum_machine::sample_cache::SampleCache,
+        transport::{StepWindow, Transport, TransportState, TransportTrack},
+    },
+    ui::{
+        drum_machine::{DrumMachine, SampleFolder},
+        synth::Synth,
+        SequenceState,
+    },
+};
+
+/// Queues every track's events between the transport's scheduled tick and `horizon_frame`,
+/// wrapping around the transport's loop region and stopping at the end of a song.
+pub fn schedule_until(
+    horizon_frame: u64,
+    transport: &mut Transport,
+    sequence_state: &SequenceState,
+    selected_samples: &BTreeMap<usize, HashMap<String, SampleFolder>>,
+    sample_cache: &SampleCache,
+    engine: &EngineHandle,
+) {
+    let song_end_tick = sequence_state
+        .arrangement
+        .song_mode
+        .then(|| sequence_state.arrangement.length_bars as f64 * transport.ticks_per_bar());
+
+    while transport.state == TransportState::Playing {
+        let from_tick = transport.scheduled_tick;
+        let mut to_tick = transport.tick_at_frame(horizon_frame);
+        if to_tick <= from_tick {
+            return;
+        }
+
+        let loop_region = transport
+            .loop_region
+            .filter(|(start, end)| start < end && from_tick < *end && to_tick >= *end);
+        if let Some((_, loop_end)) = loop_region {
+            to_tick = loop_end;
+        }
+        let song_ended = song_end_tick.is_some_and(|end_tick| to_tick >= end_tick);
+        if let Some(end_tick) = song_end_tick {
+            to_tick = to_tick.min(end_tick);
+        }
+
+        schedule_window(
+            transport,
+            from_tick,
+            to_tick,
+            sequence_state,
+            selected_samples,
+            sample_cache,
+            engine,
+        );
+        transport.scheduled_tick = to_tick;
+
+        transport.release_finished_tracks();
+        if let Some((loop_start, loop_end)) = loop_region {
+            transport.jump(loop_end, loop_start);
+        } else if song_ended {
+            transport.stop_all();
+        } else {
+            return;
+        }
+    }
+}
+
+fn schedule_window(
+    transport: &Transport,
+    from_tick: f64,
+    to_tick: f64,
+    sequence_state: &SequenceState,
+    selected_samples: &BTreeMap<usize, HashMap<String, SampleFolder>>,
+    sample_cache: &SampleCache,
+    engine: &EngineHandle,
+) {
+    if !sequence_state.arrangement.song_mode {
+        if let Some(window) = transport.track_window(TransportTrack::Drums, from_tick, to_tick) {
+            DrumMachine::play_pattern(
+                sequence_state,
+                &sequence_state.beat_pattern,
+                transport,
+                window,
+                engine,
+                selected_samples,
+                sample_cache,
+            );
+        }
+        if let Some(window) = transport.track_window(TransportTrack::Synth, from_tick, to_tick) {
+            Synth::play_sequence(
+                sequence_state,
+                &sequence_state.note_pattern,
+                transport,
+                window,
+                engine,
+            );
+        }
+        return;
+    }
+
+    // In song mode every clip plays its pattern counted from the clip's first bar.
+    let ticks_per_bar = transport.ticks_per_bar();
+    for track in sequence_state.arrangement.tracks.iter() {
+        for clip in track.clips.iter() {
+            let clip_start = clip.start_bar as f64 * ticks_per_bar;
+            let clip_end = clip.end_bar() as f64 * ticks_per_bar;
+            let window = StepWindow {
+                origin_tick: clip_start,
+                from_tick: from_tick.max(clip_start),
+                to_tick: to_tick.min(clip_end),
+            };
+            if window.from_tick >= window.to_tick {
+                continue;
+            }
+            if let Some((beat_pattern, note_pattern)) = sequence_state.pattern_rows(clip.pattern) {
+                DrumMachine::play_pattern(
+                    sequence_state,
+                    beat_pattern,
+                    transport,
+                    window,
+                    engine,
+                    selected_samples,
+                    sample_cache,
+                );
+                Synth::play_sequence(sequence_state, note_pattern, transport, window, engine);
+            }
+        }
+    }
+}
+
+/// Runs the one sequencer thread that feeds both the drum machine and the synth
+/// from the shared transport.
+pub fn spawn_sequencer(
+    transport: Arc<Mutex<Transport>>,
+    sequence_state: Arc<Mutex<SequenceState>>,
+    selected_samples: Arc<RwLock<BTreeMap<usize, HashMap<String, SampleFolder>>>>,
+    sample_cache: SampleCache,
+    engine: EngineHandle,
+) {
+    thread::spawn(move || loop {
+        thread::sleep(SCHEDULER_INTERVAL);
+
+        let mut transport = transport.lock().unwrap();
+        if transport.state != TransportState::Playing {
+            continue;
+        }
+        let sequence_state = sequence_state.lock().unwrap();
+        let arrangement = &sequence_state.arrangement;
+        transport.loop_region = (arrangement.song_mode && arrangement.loop_enabled).then(|| {
+            let ticks_per_bar = transport.ticks_per_bar();
+            (
+                arrangement.loop_region.start_bar as f64 * ticks_per_bar,
+                arrangement.loop_region.end_bar as f64 * ticks_per_bar,
+            )
+        });
+        let selected_samples = selected_samples.read().unwrap();
+        schedule_until(
+            engine.lookahead_frame(),
+            &mut transport,
+            &sequence_state,
+            &selected_samples,
+            &sample_cache,
+            &engine,
+        );
+    });
+}
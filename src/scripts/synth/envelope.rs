@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ATTACK_MS: f32 = 2000.0;
+pub const MAX_DECAY_MS: f32 = 2000.0;
+pub const MAX_RELEASE_MS: f32 = 4000.0;
+
+// Even a zero attack or release ramps over this long, a jump in level clicks.
+const MIN_RAMP_MS: f32 = 1.0;
+// How fast a note fades out when a new note of the same pitch takes over.
+const CHOKE_MS: f32 = 3.0;
+
+/// The attack, decay, sustain and release of the synth's amplitude.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub attack_ms: f32,
+    pub decay_ms: f32,
+    /// The level held after the decay, 0 to 1.
+    pub sustain: f32,
+    pub release_ms: f32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope {
+            attack_ms: 10.0,
+            decay_ms: 100.0,
+            sustain: 0.8,
+            release_ms: 150.0,
+        }
+    }
+}
+
+impl Envelope {
+    /// The level `t` seconds into a note held for `gate` seconds, for envelopes read
+    /// as a function of time. `EnvelopeState` steps through the same curve.
+    pub fn level_at(&self, t: f32, gate: f32) -> f32 {
+        let stages = Stages::new(*self);
+        if t < gate {
+            stages.held(t)
+        } else {
+            released(stages.held(gate), t - gate, stages.release)
+        }
+    }
+}
+
+/// The stage lengths of an envelope in seconds, each at least `MIN_RAMP_MS`, and its
+/// sustain level.
+#[derive(Debug, Clone, Copy)]
+struct Stages {
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+}
+
+impl Stages {
+    fn new(envelope: Envelope) -> Self {
+        let seconds = |ms: f32| ms.max(MIN_RAMP_MS) / 1000.0;
+        Stages {
+            attack: seconds(envelope.attack_ms),
+            decay: seconds(envelope.decay_ms),
+            sustain: envelope.sustain.clamp(0.0, 1.0),
+            release: seconds(envelope.release_ms),
+        }
+    }
+
+    /// The level `t` seconds into the note while it is held.
+    fn held(&self, t: f32) -> f32 {
+        if t <= self.attack {
+            t / self.attack
+        } else if t <= self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (t - self.attack) / self.decay
+        } else {
+            self.sustain
+        }
+    }
+}
+
+/// The level `t` seconds into a release lasting `duration` seconds, from `from_level`.
+fn released(from_level: f32, t: f32, duration: f32) -> f32 {
+    from_level * (1.0 - t / duration).max(0.0)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Release {
+    from_level: f32,
+    frames: u64,
+    elapsed: u64,
+}
+
+/// The envelope of one note, stepping one frame at a time.
+pub struct EnvelopeState {
+    stages: Stages,
+    /// How long the note is held before it is released.
+    gate_frames: u64,
+    sample_rate: u32,
+    elapsed: u64,
+    level: f32,
+    release: Option<Release>,
+}
+
+impl EnvelopeState {
+    pub fn new(envelope: Envelope, gate_frames: u64, sample_rate: u32) -> Self {
+        EnvelopeState {
+            stages: Stages::new(envelope),
+            gate_frames,
+            sample_rate,
+            elapsed: 0,
+            level: 0.0,
+            release: None,
+        }
+    }
+
+    /// The level of the next frame, `None` once the release has finished.
+    pub fn next_level(&mut self) -> Option<f32> {
+        if self.release.is_none() && self.elapsed >= self.gate_frames {
+            self.start_release(seconds_to_frames(self.stages.release, self.sample_rate));
+        }
+        if let Some(release) = &mut self.release {
+            if release.elapsed >= release.frames {
+                return None;
+            }
+            release.elapsed += 1;
+            self.level = released(
+                release.from_level,
+                release.elapsed as f32,
+                release.frames as f32,
+            );
+            return Some(self.level);
+        }
+
+        self.elapsed += 1;
+        self.level = self
+            .stages
+            .held(self.elapsed as f32 / self.sample_rate as f32);
+        Some(self.level)
+    }
+
+    /// Fades the note out within a few milliseconds, from wherever it is.
+    pub fn choke(&mut self) {
+        let frames = seconds_to_frames(CHOKE_MS / 1000.0, self.sample_rate);
+        let remaining = self
+            .release
+            .map_or(u64::MAX, |release| release.frames - release.elapsed);
+        if remaining > frames {
+            self.start_release(frames);
+        }
+    }
+
+    fn start_release(&mut self, frames: u64) {
+        self.release = Some(Release {
+            from_level: self.level,
+            frames: frames.max(1),
+            elapsed: 0,
+        });
+    }
+}
+
+fn seconds_to_frames(seconds: f32, sample_rate: u32) -> u64 {
+    (seconds * sample_rate as f32).round().max(1.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    // Attack 10 ms, decay 20 ms to half, release 40 ms: at 1 kHz one frame per ms.
+    fn envelope() -> Envelope {
+        Envelope {
+            attack_ms: 10.0,
+            decay_ms: 20.0,
+            sustain: 0.5,
+            release_ms: 40.0,
+        }
+    }
+
+    fn assert_level(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn levels_follow_attack_decay_sustain_and_release() {
+        let envelope = envelope();
+        let gate = 0.1;
+        assert_level(envelope.level_at(0.0, gate), 0.0);
+        assert_level(envelope.level_at(0.005, gate), 0.5);
+        assert_level(envelope.level_at(0.01, gate), 1.0);
+        assert_level(envelope.level_at(0.02, gate), 0.75);
+        assert_level(envelope.level_at(0.03, gate), 0.5);
+        assert_level(envelope.level_at(0.08, gate), 0.5);
+        assert_level(envelope.level_at(0.12, gate), 0.25);
+        assert_level(envelope.level_at(0.14, gate), 0.0);
+        assert_level(envelope.level_at(1.0, gate), 0.0);
+    }
+
+    #[test]
+    fn a_short_gate_releases_from_the_level_reached() {
+        // Released halfway through the attack, the release starts from half.
+        let envelope = envelope();
+        assert_level(envelope.level_at(0.005, 0.005), 0.5);
+        assert_level(envelope.level_at(0.025, 0.005), 0.25);
+        assert_level(envelope.level_at(0.045, 0.005), 0.0);
+
+        // Zero length stages still ramp, over `MIN_RAMP_MS`.
+        let instant = Envelope {
+            attack_ms: 0.0,
+            decay_ms: 0.0,
+            sustain: 1.0,
+            release_ms: 0.0,
+        };
+        assert_level(instant.level_at(0.0005, 1.0), 0.5);
+        assert_level(instant.level_at(1.0005, 1.0), 0.5);
+    }
+
+    #[test]
+    fn stepping_frames_matches_the_curve() {
+        let envelope = envelope();
+        for gate_frames in [5, 50] {
+            let mut state = EnvelopeState::new(envelope, gate_frames, SAMPLE_RATE);
+            let gate = gate_frames as f32 / SAMPLE_RATE as f32;
+            let mut frame = 1;
+            while let Some(level) = state.next_level() {
+                let t = frame as f32 / SAMPLE_RATE as f32;
+                assert_level(level, envelope.level_at(t, gate));
+                frame += 1;
+            }
+            // The note ends once its release has run.
+            assert_eq!(frame - 1, gate_frames + 40);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+
+use fundsp::hacker32::{bandpass, highpass, lowpass, AudioUnit};
+use serde::{Deserialize, Serialize};
+
+use super::envelope::Envelope;
+
+pub const MIN_CUTOFF_HZ: f32 = 20.0;
+pub const MAX_CUTOFF_HZ: f32 = 20_000.0;
+/// How far the filter envelope sweeps the cutoff at full depth, either way.
+pub const MAX_ENVELOPE_OCTAVES: f32 = 6.0;
+
+// The Q at no and at full resonance. The first is the flattest response without a peak.
+const MIN_Q: f32 = 0.5;
+const MAX_Q: f32 = 15.0;
+// Key tracking moves the cutoff relative to the note it would have at middle C.
+const KEY_TRACKING_REFERENCE_HZ: f32 = 261.63;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FilterMode {
+    #[default]
+    Lowpass,
+    Highpass,
+    Bandpass,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 3] = [
+        FilterMode::Lowpass,
+        FilterMode::Highpass,
+        FilterMode::Bandpass,
+    ];
+
+    /// The state variable filter of the mode, taking the signal, the cutoff and the Q.
+    pub fn svf(&self) -> Box<dyn AudioUnit> {
+        match self {
+            FilterMode::Lowpass => Box::new(lowpass()),
+            FilterMode::Highpass => Box::new(highpass()),
+            FilterMode::Bandpass => Box::new(bandpass()),
+        }
+    }
+}
+
+impl fmt::Display for FilterMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterMode::Lowpass => write!(f, "Low-pass"),
+            FilterMode::Highpass => write!(f, "High-pass"),
+            FilterMode::Bandpass => write!(f, "Band-pass"),
+        }
+    }
+}
+
+/// The filter settings of the synth track.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    /// Off by default, so projects saved before the filter existed sound the same.
+    pub enabled: bool,
+    pub mode: FilterMode,
+    pub cutoff_hz: f32,
+    /// 0 to 1, how sharply the filter peaks around the cutoff.
+    pub resonance: f32,
+    /// 0 to 1, how much the cutoff follows the pitch of the note. At 1 every note is
+    /// filtered alike relative to its own pitch.
+    pub key_tracking: f32,
+    pub envelope: Envelope,
+    /// The octaves the envelope at full level moves the cutoff by, negative sweeps down.
+    pub envelope_octaves: f32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            enabled: false,
+            mode: FilterMode::Lowpass,
+            cutoff_hz: 2000.0,
+            resonance: 0.2,
+            key_tracking: 0.0,
+            envelope: Envelope {
+                attack_ms: 5.0,
+                decay_ms: 300.0,
+                sustain: 0.0,
+                release_ms: 150.0,
+            },
+            envelope_octaves: 2.0,
+        }
+    }
+}
+
+/// Where the filter of one note sits over time, its own envelope sweeping the cutoff.
+pub struct FilterSweep {
+    envelope: Envelope,
+    /// How long the note is held before the envelope is released, in seconds.
+    gate: f32,
+    /// The cutoff before the envelope, key tracking already applied.
+    base_cutoff_hz: f32,
+    envelope_octaves: f32,
+    max_cutoff_hz: f32,
+    q: f32,
+}
+
+impl FilterSweep {
+    pub fn new(settings: Filter, frequency: f32, gate_frames: u64, sample_rate: u32) -> Self {
+        let key_tracking = settings.key_tracking.clamp(0.0, 1.0);
+        let resonance = settings.resonance.clamp(0.0, 1.0);
+        FilterSweep {
+            envelope: settings.envelope,
+            gate: gate_frames as f32 / sample_rate as f32,
+            base_cutoff_hz: settings.cutoff_hz
+                * (frequency / KEY_TRACKING_REFERENCE_HZ).powf(key_tracking),
+            envelope_octaves: settings
+                .envelope_octaves
+                .clamp(-MAX_ENVELOPE_OCTAVES, MAX_ENVELOPE_OCTAVES),
+            // Just under Nyquist, where the filter would turn unstable.
+            max_cutoff_hz: MAX_CUTOFF_HZ.min(sample_rate as f32 * 0.45),
+            // Resonance is felt exponentially, so it is spread evenly over the slider.
+            q: MIN_Q * (MAX_Q / MIN_Q).powf(resonance),
+        }
+    }
+
+    pub fn q(&self) -> f32 {
+        self.q
+    }
+
+    /// The cutoff `t` seconds into the note, moved by `cutoff_octaves` on top of the
+    /// filter envelope.
+    pub fn cutoff_hz(&self, t: f32, cutoff_octaves: f32) -> f32 {
+        let level = self.envelope.level_at(t, self.gate);
+        let octaves = self.envelope_octaves * level + cutoff_octaves;
+        (self.base_cutoff_hz * 2.0_f32.powf(octaves)).clamp(MIN_CUTOFF_HZ, self.max_cutoff_hz)
+    }
+}
@@ -0,0 +1,351 @@
+use std::{f64::consts::TAU, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::scripts::transport::TICKS_PER_BEAT;
+
+pub const MAX_LFOS: usize = 4;
+pub const MIN_LFO_RATE_HZ: f32 = 0.05;
+pub const MAX_LFO_RATE_HZ: f32 = 20.0;
+
+// What a route at a depth of 1 does to its target.
+const MAX_PITCH_SEMITONES: f32 = 12.0;
+const MAX_CUTOFF_OCTAVES: f32 = 4.0;
+const MAX_PULSE_WIDTH_OFFSET: f32 = 0.45;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    /// A new random level every cycle, held until the next.
+    SampleAndHold,
+}
+
+impl LfoShape {
+    pub const ALL: [LfoShape; 4] = [
+        LfoShape::Sine,
+        LfoShape::Triangle,
+        LfoShape::Square,
+        LfoShape::SampleAndHold,
+    ];
+}
+
+impl fmt::Display for LfoShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LfoShape::Sine => write!(f, "Sine"),
+            LfoShape::Triangle => write!(f, "Triangle"),
+            LfoShape::Square => write!(f, "Square"),
+            LfoShape::SampleAndHold => write!(f, "S&H"),
+        }
+    }
+}
+
+/// A tempo-synced LFO cycle length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SyncDivision {
+    TwoWholes,
+    Whole,
+    Half,
+    #[default]
+    Quarter,
+    DottedQuarter,
+    QuarterTriplet,
+    Eighth,
+    DottedEighth,
+    EighthTriplet,
+    Sixteenth,
+}
+
+impl SyncDivision {
+    pub const ALL: [SyncDivision; 10] = [
+        SyncDivision::TwoWholes,
+        SyncDivision::Whole,
+        SyncDivision::Half,
+        SyncDivision::DottedQuarter,
+        SyncDivision::Quarter,
+        SyncDivision::QuarterTriplet,
+        SyncDivision::DottedEighth,
+        SyncDivision::Eighth,
+        SyncDivision::EighthTriplet,
+        SyncDivision::Sixteenth,
+    ];
+
+    /// The length of one cycle in quarter notes.
+    pub fn quarters(&self) -> f64 {
+        match self {
+            SyncDivision::TwoWholes => 8.0,
+            SyncDivision::Whole => 4.0,
+            SyncDivision::Half => 2.0,
+            SyncDivision::DottedQuarter => 1.5,
+            SyncDivision::Quarter => 1.0,
+            SyncDivision::QuarterTriplet => 2.0 / 3.0,
+            SyncDivision::DottedEighth => 0.75,
+            SyncDivision::Eighth => 0.5,
+            SyncDivision::EighthTriplet => 1.0 / 3.0,
+            SyncDivision::Sixteenth => 0.25,
+        }
+    }
+}
+
+impl fmt::Display for SyncDivision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncDivision::TwoWholes => write!(f, "2/1"),
+            SyncDivision::Whole => write!(f, "1/1"),
+            SyncDivision::Half => write!(f, "1/2"),
+            SyncDivision::DottedQuarter => write!(f, "1/4."),
+            SyncDivision::Quarter => write!(f, "1/4"),
+            SyncDivision::QuarterTriplet => write!(f, "1/4T"),
+            SyncDivision::DottedEighth => write!(f, "1/8."),
+            SyncDivision::Eighth => write!(f, "1/8"),
+            SyncDivision::EighthTriplet => write!(f, "1/8T"),
+            SyncDivision::Sixteenth => write!(f, "1/16"),
+        }
+    }
+}
+
+/// One low frequency oscillator of the synth.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Lfo {
+    pub shape: LfoShape,
+    pub rate_hz: f32,
+    /// Follows the tempo at this division instead of `rate_hz` when set.
+    pub sync: Option<SyncDivision>,
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Lfo {
+            shape: LfoShape::Sine,
+            rate_hz: 5.0,
+            sync: None,
+        }
+    }
+}
+
+impl Lfo {
+    pub fn rate_hz(&self, bpm: f64) -> f64 {
+        match self.sync {
+            Some(division) => bpm / 60.0 / division.quarters(),
+            None => self.rate_hz.clamp(MIN_LFO_RATE_HZ, MAX_LFO_RATE_HZ) as f64,
+        }
+    }
+
+    /// The cycles run through by `frame` of the engine clock, which is at `tick` of the
+    /// song. Free LFOs run on the clock, synced ones on the song, so every note sees
+    /// the same LFO whenever it starts.
+    fn cycles_at(&self, bpm: f64, frame: u64, tick: f64, sample_rate: u32) -> f64 {
+        match self.sync {
+            Some(division) => tick.max(0.0) / (division.quarters() * TICKS_PER_BEAT as f64),
+            None => frame as f64 / sample_rate as f64 * self.rate_hz(bpm),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ModTarget {
+    #[default]
+    Pitch,
+    Cutoff,
+    Amplitude,
+    PulseWidth,
+}
+
+impl ModTarget {
+    pub const ALL: [ModTarget; 4] = [
+        ModTarget::Pitch,
+        ModTarget::Cutoff,
+        ModTarget::Amplitude,
+        ModTarget::PulseWidth,
+    ];
+}
+
+impl fmt::Display for ModTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModTarget::Pitch => write!(f, "Pitch"),
+            ModTarget::Cutoff => write!(f, "Cutoff"),
+            ModTarget::Amplitude => write!(f, "Amplitude"),
+            ModTarget::PulseWidth => write!(f, "Pulse width"),
+        }
+    }
+}
+
+/// Sends one LFO to one target.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModRoute {
+    pub lfo: usize,
+    pub target: ModTarget,
+    /// -1 to 1, negative turns the LFO upside down. At 1 pitch swings an octave,
+    /// the cutoff four octaves and the amplitude all the way to silence.
+    pub depth: f32,
+}
+
+/// The LFOs of the synth and where they are routed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Modulation {
+    pub lfos: Vec<Lfo>,
+    pub routes: Vec<ModRoute>,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Modulation {
+            lfos: vec![Lfo::default()],
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl Modulation {
+    pub fn add_lfo(&mut self) {
+        if self.lfos.len() < MAX_LFOS {
+            self.lfos.push(Lfo::default());
+        }
+    }
+
+    /// Removes an LFO along with its routes.
+    pub fn remove_lfo(&mut self, index: usize) {
+        if index >= self.lfos.len() {
+            return;
+        }
+        self.lfos.remove(index);
+        self.routes.retain(|route| route.lfo != index);
+        for route in self.routes.iter_mut() {
+            if route.lfo > index {
+                route.lfo -= 1;
+            }
+        }
+    }
+
+    /// Starts the LFOs of a note beginning at `frame` of the engine clock, which is at
+    /// `tick` of the song.
+    pub fn start(&self, bpm: f64, frame: u64, tick: f64, sample_rate: u32) -> ModulationState {
+        let lfos = self
+            .lfos
+            .iter()
+            .enumerate()
+            .map(|(index, lfo)| LfoState {
+                shape: lfo.shape,
+                seed: index as u64,
+                start_cycles: lfo.cycles_at(bpm, frame, tick, sample_rate),
+                rate_hz: lfo.rate_hz(bpm),
+            })
+            .collect();
+        ModulationState {
+            lfos,
+            routes: self
+                .routes
+                .iter()
+                .copied()
+                .filter(|route| route.lfo < self.lfos.len() && route.depth != 0.0)
+                .collect(),
+        }
+    }
+}
+
+/// One LFO of a note, read as a function of the time since the note started.
+struct LfoState {
+    shape: LfoShape,
+    seed: u64,
+    /// The cycles the LFO had run through when the note started.
+    start_cycles: f64,
+    rate_hz: f64,
+}
+
+impl LfoState {
+    /// The value `t` seconds into the note, -1 to 1.
+    fn value_at(&self, t: f64) -> f32 {
+        let cycles = self.start_cycles + self.rate_hz * t;
+        let phase = cycles.fract();
+        match self.shape {
+            LfoShape::Sine => (phase * TAU).sin() as f32,
+            LfoShape::Triangle => (4.0 * ((phase - 0.25).rem_euclid(1.0) - 0.5).abs() - 1.0) as f32,
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::SampleAndHold => self.sample_and_hold(cycles.floor() as u64),
+        }
+    }
+
+    /// The random level of a cycle. It is hashed from the cycle rather than drawn, so
+    /// overlapping notes hold the same level.
+    fn sample_and_hold(&self, cycle: u64) -> f32 {
+        // splitmix64
+        let mut hash = (cycle ^ (self.seed << 32))
+            .wrapping_add(1)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        hash ^= hash >> 31;
+        (hash >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+    }
+}
+
+/// What the routed LFOs do to a note in one frame.
+#[derive(Debug, Clone, Copy)]
+pub struct ModValues {
+    pub pitch_ratio: f32,
+    pub cutoff_octaves: f32,
+    pub amplitude: f32,
+    pub pulse_width_offset: f32,
+}
+
+impl Default for ModValues {
+    fn default() -> Self {
+        ModValues {
+            pitch_ratio: 1.0,
+            cutoff_octaves: 0.0,
+            amplitude: 1.0,
+            pulse_width_offset: 0.0,
+        }
+    }
+}
+
+/// The LFOs of one note and where they are routed.
+pub struct ModulationState {
+    lfos: Vec<LfoState>,
+    routes: Vec<ModRoute>,
+}
+
+impl ModulationState {
+    /// What the routed LFOs do to the note `t` seconds after it started.
+    pub fn values_at(&self, t: f64) -> ModValues {
+        let mut values = ModValues::default();
+        if self.routes.is_empty() {
+            return values;
+        }
+        let mut lfo_values = [0.0; MAX_LFOS];
+        for (value, lfo) in lfo_values.iter_mut().zip(self.lfos.iter()) {
+            *value = lfo.value_at(t);
+        }
+
+        let mut semitones = 0.0;
+        for route in &self.routes {
+            let value = lfo_values.get(route.lfo).copied().unwrap_or(0.0);
+            let depth = route.depth.clamp(-1.0, 1.0);
+            match route.target {
+                ModTarget::Pitch => semitones += value * depth * MAX_PITCH_SEMITONES,
+                ModTarget::Cutoff => values.cutoff_octaves += value * depth * MAX_CUTOFF_OCTAVES,
+                // Tremolo dips from full level, down to silence at a depth of 1.
+                ModTarget::Amplitude => {
+                    values.amplitude *= 1.0 - depth.abs() * (1.0 - value * depth.signum()) / 2.0
+                }
+                ModTarget::PulseWidth => {
+                    values.pulse_width_offset += value * depth * MAX_PULSE_WIDTH_OFFSET
+                }
+            }
+        }
+        if semitones != 0.0 {
+            values.pitch_ratio = 2.0_f32.powf(semitones / 12.0);
+        }
+        values
+    }
+}
@@ -1,2 +1,7 @@
+pub mod envelope;
+pub mod filter;
+pub mod lfo;
+pub mod oscillator;
+pub mod patch;
 pub mod play_synth_note;
 pub mod play_synth_sequence;
@@ -0,0 +1,177 @@
+use std::{f32::consts::TAU, fmt};
+
+use fundsp::hacker32::{dc, An, AudioNode, Frame, U0, U1, U2};
+use serde::{Deserialize, Serialize};
+
+pub const MIN_PULSE_WIDTH: f32 = 0.05;
+pub const MAX_PULSE_WIDTH: f32 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Saw,
+    /// A pulse wave, square at a pulse width of 0.5.
+    Square,
+    Triangle,
+    Noise,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 5] = [
+        Waveform::Sine,
+        Waveform::Saw,
+        Waveform::Square,
+        Waveform::Triangle,
+        Waveform::Noise,
+    ];
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Waveform::Sine => write!(f, "Sine"),
+            Waveform::Saw => write!(f, "Saw"),
+            Waveform::Square => write!(f, "Square"),
+            Waveform::Triangle => write!(f, "Triangle"),
+            Waveform::Noise => write!(f, "Noise"),
+        }
+    }
+}
+
+/// The oscillator settings of the synth track.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Oscillator {
+    pub waveform: Waveform,
+    /// The share of the period a pulse wave is high for.
+    pub pulse_width: f32,
+    /// Smooths the discontinuities of saw, pulse and triangle waves so their harmonics
+    /// above the Nyquist frequency do not fold back as aliasing. The raw waves are
+    /// brighter and grittier, especially on high notes.
+    pub band_limited: bool,
+}
+
+impl Default for Oscillator {
+    fn default() -> Self {
+        Oscillator {
+            waveform: Waveform::Sine,
+            pulse_width: 0.5,
+            band_limited: true,
+        }
+    }
+}
+
+/// The oscillator as a fundsp node. fundsp's own oscillators are all band-limited, so
+/// the raw waves of the settings need a node of their own.
+/// - Input 0: frequency in Hz
+/// - Input 1: pulse width
+/// - Output 0: the wave
+#[derive(Clone)]
+pub struct OscillatorNode {
+    waveform: Waveform,
+    band_limited: bool,
+    sample_rate: f32,
+    phase: f32,
+    /// The integrated pulse the band-limited triangle is made of.
+    triangle: f32,
+    noise: u32,
+}
+
+impl OscillatorNode {
+    pub fn new(settings: Oscillator) -> Self {
+        let mut node = OscillatorNode {
+            waveform: settings.waveform,
+            band_limited: settings.band_limited,
+            sample_rate: 44_100.0,
+            phase: 0.0,
+            triangle: 0.0,
+            noise: 0,
+        };
+        node.reset();
+        node
+    }
+
+    fn pulse(&self, t: f32, dt: f32, width: f32) -> f32 {
+        let value = if t < width { 1.0 } else { -1.0 };
+        if self.band_limited {
+            value + poly_blep(t, dt) - poly_blep((t + 1.0 - width).fract(), dt)
+        } else {
+            value
+        }
+    }
+}
+
+impl AudioNode for OscillatorNode {
+    const ID: u64 = 0x4f73_6369_6c6c;
+    type Inputs = U2;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.triangle = -1.0;
+        // Any non-zero seed works, a fixed one keeps renders reproducible.
+        self.noise = 0x9E37_79B9;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let (t, dt) = (self.phase, (input[0] / self.sample_rate).clamp(0.0, 0.5));
+        let band_limited = self.band_limited;
+        let value = match self.waveform {
+            Waveform::Sine => (t * TAU).sin(),
+            Waveform::Saw if band_limited => 2.0 * t - 1.0 - poly_blep(t, dt),
+            Waveform::Saw => 2.0 * t - 1.0,
+            Waveform::Square => {
+                // A narrow pulse sits mostly low, the offset centres it around zero.
+                let width = input[1].clamp(MIN_PULSE_WIDTH, MAX_PULSE_WIDTH);
+                self.pulse(t, dt, width) - (2.0 * width - 1.0)
+            }
+            Waveform::Triangle if band_limited => {
+                // Integrating a band-limited square gives a band-limited triangle,
+                // the leak keeps rounding errors from drifting it off centre.
+                self.triangle = self.triangle * 0.9995 + 4.0 * dt * self.pulse(t, dt, 0.5);
+                self.triangle
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+            Waveform::Noise => {
+                // xorshift32
+                self.noise ^= self.noise << 13;
+                self.noise ^= self.noise >> 17;
+                self.noise ^= self.noise << 5;
+                self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+            }
+        };
+        self.phase = (self.phase + dt).fract();
+        [value].into()
+    }
+}
+
+/// A band-limited wave at a fixed frequency. It peaks at full scale on every note, where
+/// the wavetable oscillators of fundsp get louder the higher they play.
+pub fn wave_hz(
+    waveform: Waveform,
+    frequency: f32,
+) -> An<impl AudioNode<Inputs = U0, Outputs = U1>> {
+    let settings = Oscillator {
+        waveform,
+        ..Oscillator::default()
+    };
+    dc((frequency, settings.pulse_width)) >> An(OscillatorNode::new(settings))
+}
+
+/// The polynomial band-limited step, the correction that rounds off a jump of 2 at
+/// phase 0 over the sample on either side of it.
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
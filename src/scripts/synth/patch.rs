@@ -0,0 +1,204 @@
+use std::{fmt, sync::Arc};
+
+use fundsp::hacker32::{
+    envelope, lowpass_hz, moog_hz, pass, pluck, sine, sine_hz, sink, zero, An, AudioUnit, Net,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ui::SequenceState;
+
+use super::{
+    envelope::Envelope,
+    filter::FilterSweep,
+    lfo::ModulationState,
+    oscillator::{wave_hz, OscillatorNode, Waveform},
+};
+
+/// The sound the synth track plays its notes with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SynthPatch {
+    /// The oscillator, filter, envelope and LFOs edited on the Synth page.
+    #[default]
+    Custom,
+    Pluck,
+    Pad,
+    Bass,
+    FmBell,
+}
+
+impl SynthPatch {
+    pub const ALL: [SynthPatch; 5] = [
+        SynthPatch::Custom,
+        SynthPatch::Pluck,
+        SynthPatch::Pad,
+        SynthPatch::Bass,
+        SynthPatch::FmBell,
+    ];
+
+    /// Whether the patch plays the oscillator, envelope, filter and LFO settings. The
+    /// built-in patches bring their own and ignore them.
+    pub fn uses_sound_settings(&self) -> bool {
+        matches!(self, SynthPatch::Custom)
+    }
+
+    /// The fundsp graph of one note of the patch, with the amplitude envelope the voice
+    /// shapes it by. Both are built here, away from the audio thread, which only runs
+    /// them.
+    pub fn build(
+        &self,
+        sequence_state: &SequenceState,
+        note: &NoteContext,
+    ) -> (Box<dyn AudioUnit>, Envelope) {
+        let frequency = note.frequency;
+        let (mut graph, envelope): (Box<dyn AudioUnit>, Envelope) = match self {
+            SynthPatch::Custom => (
+                Box::new(custom_graph(sequence_state, note)),
+                sequence_state.envelope,
+            ),
+            // A Karplus-Strong string, decaying on its own.
+            SynthPatch::Pluck => (
+                Box::new(zero() >> pluck(frequency, 0.5, 0.4)),
+                Envelope {
+                    attack_ms: 0.0,
+                    decay_ms: 0.0,
+                    sustain: 1.0,
+                    release_ms: 300.0,
+                },
+            ),
+            // Detuned saws, spread apart across the stereo field. The patches mixing two
+            // oscillators halve them, so like the others they peak at no more than full
+            // scale and overlapping notes leave the same headroom.
+            SynthPatch::Pad => {
+                let cutoff = (frequency * 4.0).min(8000.0);
+                let saw = |frequency: f32| wave_hz(Waveform::Saw, frequency);
+                let left = (saw(frequency * 0.997) + saw(frequency * 1.004)) * 0.5;
+                let right = (saw(frequency * 1.003) + saw(frequency * 0.995)) * 0.5;
+                (
+                    Box::new((left | right) >> (lowpass_hz(cutoff, 0.7) | lowpass_hz(cutoff, 0.7))),
+                    Envelope {
+                        attack_ms: 400.0,
+                        decay_ms: 300.0,
+                        sustain: 0.8,
+                        release_ms: 1200.0,
+                    },
+                )
+            }
+            // A saw over a square an octave down, through a ladder filter.
+            SynthPatch::Bass => (
+                Box::new(
+                    ((wave_hz(Waveform::Saw, frequency)
+                        + wave_hz(Waveform::Square, frequency * 0.5))
+                        * 0.5)
+                        >> moog_hz((frequency * 8.0).min(4000.0), 0.3),
+                ),
+                Envelope {
+                    attack_ms: 2.0,
+                    decay_ms: 200.0,
+                    sustain: 0.7,
+                    release_ms: 60.0,
+                },
+            ),
+            // Two-operator FM at an inharmonic ratio, the modulation index and the level
+            // dying away like a struck bell.
+            SynthPatch::FmBell => {
+                let index = envelope(move |t: f32| frequency * 3.0 * (-t * 2.0).exp());
+                let modulator = sine_hz(frequency * 3.5) * index;
+                (
+                    Box::new(
+                        ((modulator + frequency) >> sine()) * envelope(|t: f32| (-t * 1.2).exp()),
+                    ),
+                    Envelope {
+                        attack_ms: 0.0,
+                        decay_ms: 0.0,
+                        sustain: 1.0,
+                        release_ms: 2500.0,
+                    },
+                )
+            }
+        };
+        graph.set_sample_rate(note.sample_rate as f64);
+        graph.allocate();
+        (graph, envelope)
+    }
+}
+
+impl fmt::Display for SynthPatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SynthPatch::Custom => write!(f, "Custom"),
+            SynthPatch::Pluck => write!(f, "Pluck"),
+            SynthPatch::Pad => write!(f, "Pad"),
+            SynthPatch::Bass => write!(f, "Bass"),
+            SynthPatch::FmBell => write!(f, "FM bell"),
+        }
+    }
+}
+
+/// The note a patch builds a graph for.
+pub struct NoteContext {
+    pub frequency: f32,
+    /// How long the note is held before it is released.
+    pub gate_frames: u64,
+    /// Where the note starts on the engine clock, and in the song.
+    pub frame: u64,
+    pub tick: f64,
+    pub sample_rate: u32,
+}
+
+/// The custom patch: the oscillator through the filter, then scaled by the amplitude.
+/// Where pitch, pulse width, cutoff and amplitude sit over the note, with the LFOs and
+/// the filter envelope, is a control envelope feeding all of them.
+fn custom_graph(sequence_state: &SequenceState, note: &NoteContext) -> Net {
+    let controls = Arc::new(CustomControls {
+        frequency: note.frequency,
+        pulse_width: sequence_state.oscillator.pulse_width,
+        filter: sequence_state.filter.enabled.then(|| {
+            FilterSweep::new(
+                sequence_state.filter,
+                note.frequency,
+                note.gate_frames,
+                note.sample_rate,
+            )
+        }),
+        modulation: sequence_state.modulation.start(
+            sequence_state.bpm as f64,
+            note.frame,
+            note.tick,
+            note.sample_rate,
+        ),
+    });
+    // Frequency, pulse width, cutoff, Q and amplitude, to the oscillator and the filter
+    // with the amplitude carried past them.
+    let voice = envelope(move |t: f32| controls.at(t))
+        >> (An(OscillatorNode::new(sequence_state.oscillator)) | pass() | pass() | pass());
+    let filter = if sequence_state.filter.enabled {
+        Net::wrap(sequence_state.filter.mode.svf()) | pass()
+    } else {
+        Net::wrap(Box::new(pass() | sink() | sink() | pass()))
+    };
+    Net::wrap(Box::new(voice)) >> filter >> (pass() * pass())
+}
+
+/// The controls of one custom note over time.
+struct CustomControls {
+    frequency: f32,
+    pulse_width: f32,
+    filter: Option<FilterSweep>,
+    modulation: ModulationState,
+}
+
+impl CustomControls {
+    fn at(&self, t: f32) -> (f32, f32, f32, f32, f32) {
+        let modulation = self.modulation.values_at(t as f64);
+        let (cutoff_hz, q) = self.filter.as_ref().map_or((0.0, 0.0), |filter| {
+            (filter.cutoff_hz(t, modulation.cutoff_octaves), filter.q())
+        });
+        (
+            self.frequency * modulation.pitch_ratio,
+            self.pulse_width + modulation.pulse_width_offset,
+            cutoff_hz,
+            q,
+            modulation.amplitude,
+        )
+    }
+}
@@ -1,19 +1,36 @@
 use std::time::Duration;
 
-use rodio::{OutputStreamHandle, Sink, Source};
-
-use crate::ui::synth::Synth;
+use crate::{
+    scripts::{
+        audio_engine::{engine::EngineHandle, mixer::Channel, voices::SynthVoice},
+        synth::{envelope::EnvelopeState, patch::NoteContext},
+        transport::Transport,
+    },
+    ui::{synth::Synth, SequenceState},
+};
 
 impl Synth {
-    pub fn play_note(frequency: f32, duration: Duration, stream_handle: &OutputStreamHandle) {
-        let sink = Sink::try_new(stream_handle).unwrap();
-
-        let source = rodio::source::SineWave::new(frequency)
-            .take_duration(duration)
-            .amplify(0.20)
-            .fade_in(duration / 4);
-
-        sink.append(source);
-        sink.sleep_until_end();
+    /// Queues a note of the synth's patch to start exactly at `frame` of the engine
+    /// clock. It is held for `duration` and then released, so its tail rings past the
+    /// step.
+    pub fn play_note(
+        sequence_state: &SequenceState,
+        transport: &Transport,
+        frequency: f32,
+        duration: Duration,
+        frame: u64,
+        engine: &EngineHandle,
+    ) {
+        let note = NoteContext {
+            frequency,
+            gate_frames: engine.duration_to_frames(duration),
+            frame,
+            tick: transport.tick_at_frame(frame),
+            sample_rate: engine.sample_rate,
+        };
+        let (graph, envelope) = sequence_state.patch.build(sequence_state, &note);
+        let envelope = EnvelopeState::new(envelope, note.gate_frames, note.sample_rate);
+        let voice = SynthVoice::new(graph, envelope, 0.20, frequency);
+        engine.schedule(frame, Channel::Synth, Box::new(voice));
     }
 }
@@ -1,52 +1,47 @@
-use std::{
-    sync::{Arc, Mutex},
-    thread,
-    time::Duration,
+use crate::{
+    scripts::{
+        audio_engine::engine::EngineHandle,
+        transport::{StepWindow, Transport, TransportTrack},
+        trig_conditions::TrigContext,
+    },
+    ui::{note_step::NoteStep, synth::Synth, SequenceState},
 };
 
-use rodio::OutputStreamHandle;
-
-use crate::ui::{drum_machine::SequenceScale, synth::Synth, SequenceState};
-
 impl Synth {
+    /// Queues the notes of `note_pattern` starting within the window into the audio
+    /// engine, the same way `DrumMachine::play_pattern` does for drum hits.
     pub fn play_sequence(
-        sequence_state: Arc<Mutex<SequenceState>>,
-        is_playing: Arc<Mutex<bool>>,
-        stream_handle: &OutputStreamHandle,
+        sequence_state: &SequenceState,
+        note_pattern: &[Vec<NoteStep>],
+        transport: &Transport,
+        window: StepWindow,
+        engine: &EngineHandle,
     ) {
-        while *is_playing.lock().unwrap() {
-            let sequence_state = sequence_state.lock().unwrap();
-            let note_pattern = sequence_state.note_pattern.clone();
-            let sequence_length = sequence_state.sequence_length;
-            let bpm = sequence_state.bpm;
-            let octave = sequence_state.octave;
-            let frequency = sequence_state.frequency;
-            let sequence_scale = match sequence_state.synth_scale {
-                SequenceScale::OneFourth => 1,
-                SequenceScale::OneEighth => 2,
-                SequenceScale::OneSixteenth => 4,
-            };
-            drop(sequence_state);
+        let step_ticks = sequence_state.synth_step_ticks();
+        let trig_context = TrigContext::new(sequence_state, TransportTrack::Synth);
+        let note_duration = sequence_state.synth_scale.step_duration(transport.bpm());
+        let base_frequency = sequence_state.frequency * 2.0_f32.powf(sequence_state.octave as f32);
 
-            let beat_duration = Duration::from_millis((60_000 / bpm) as u64);
-            let note_duration = beat_duration / sequence_scale;
-
-            for beat in 0..sequence_length {
-                if !*is_playing.lock().unwrap() {
-                    return;
-                }
-                for (note_index, note_row) in note_pattern.iter().enumerate() {
-                    if note_row[beat as usize] {
-                        let base_frequency = frequency * 2.0_f32.powf((octave) as f32);
-                        let frequency =
-                            base_frequency * 2.0_f32.powf((note_index as f32 - 9.0) / 12.0);
-                        let stream_handle = stream_handle.clone();
-                        thread::spawn(move || {
-                            Self::play_note(frequency, note_duration, &stream_handle);
-                        });
-                    }
+        for (step, frame) in transport.steps_in(
+            TransportTrack::Synth,
+            window,
+            step_ticks,
+            sequence_state.track_groove(TransportTrack::Synth),
+        ) {
+            for (note_index, note_row) in note_pattern.iter().enumerate() {
+                if trig_context.fires(note_row, note_index, step) {
+                    let frequency = base_frequency * 2.0_f32.powf((note_index as f32 - 9.0) / 12.0);
+                    let nudge = note_row[(step % note_row.len() as u64) as usize].nudge;
+                    let frame = transport.offset_frame(frame, nudge as f64);
+                    Self::play_note(
+                        sequence_state,
+                        transport,
+                        frequency,
+                        note_duration,
+                        frame,
+                        engine,
+                    );
                 }
-                thread::sleep(note_duration);
             }
         }
     }
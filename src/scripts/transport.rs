@@ -0,0 +1,503 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scripts::groove::Groove;
+
+/// Ticks per quarter note, the note the tempo counts.
+pub const TICKS_PER_BEAT: u32 = 96;
+
+/// Beats per bar over the note value of one beat, like 6/8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSignature {
+    pub beats: u32,
+    pub note_value: u32,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        TimeSignature {
+            beats: 4,
+            note_value: 4,
+        }
+    }
+}
+
+impl fmt::Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.beats, self.note_value)
+    }
+}
+
+impl TimeSignature {
+    pub const OPTIONS: [TimeSignature; 9] = [
+        TimeSignature::new(2, 4),
+        TimeSignature::new(3, 4),
+        TimeSignature::new(4, 4),
+        TimeSignature::new(5, 4),
+        TimeSignature::new(7, 4),
+        TimeSignature::new(5, 8),
+        TimeSignature::new(6, 8),
+        TimeSignature::new(7, 8),
+        TimeSignature::new(12, 8),
+    ];
+
+    pub const fn new(beats: u32, note_value: u32) -> Self {
+        TimeSignature { beats, note_value }
+    }
+
+    /// The length of one beat in ticks.
+    pub fn beat_ticks(&self) -> f64 {
+        (TICKS_PER_BEAT * 4) as f64 / self.note_value.max(1) as f64
+    }
+
+    pub fn bar_ticks(&self) -> f64 {
+        self.beats.max(1) as f64 * self.beat_ticks()
+    }
+
+    /// The ticks between two felt beats. Compound meters like 6/8 and 12/8 pulse in
+    /// dotted quarters rather than in eighths.
+    pub fn pulse_ticks(&self) -> f64 {
+        if self.note_value == 8 && self.beats > 3 && self.beats.is_multiple_of(3) {
+            3.0 * self.beat_ticks()
+        } else {
+            self.beat_ticks()
+        }
+    }
+
+    pub fn is_downbeat(&self, tick: f64) -> bool {
+        tick % self.bar_ticks() == 0.0
+    }
+
+    pub fn is_pulse(&self, tick: f64) -> bool {
+        tick % self.pulse_ticks() == 0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportTrack {
+    Drums,
+    Synth,
+}
+
+/// The span of ticks in which a track is audible. Both ends sit on bar boundaries
+/// while the transport is running, so tracks always join and leave in phase.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrackArm {
+    pub start_tick: Option<f64>,
+    pub stop_tick: Option<f64>,
+}
+
+impl TrackArm {
+    pub fn is_armed(&self) -> bool {
+        self.start_tick.is_some()
+    }
+
+    pub fn is_active_at(&self, tick: f64) -> bool {
+        self.start_tick.is_some_and(|start| tick >= start)
+            && self.stop_tick.is_none_or(|stop| tick < stop)
+    }
+}
+
+/// A span of ticks to schedule, together with the tick its steps are counted from.
+#[derive(Debug, Clone, Copy)]
+pub struct StepWindow {
+    pub origin_tick: f64,
+    pub from_tick: f64,
+    pub to_tick: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub bar: u32,
+    pub beat: u32,
+    pub tick: u32,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{:02}", self.bar, self.beat, self.tick)
+    }
+}
+
+/// The single musical clock of the application. It maps ticks to frames of the audio
+/// engine, and every sequencer derives its step positions from it.
+pub struct Transport {
+    pub state: TransportState,
+    bpm: f64,
+    sample_rate: u32,
+    anchor_frame: f64,
+    anchor_tick: f64,
+    /// Tick up to which the sequencer has already queued its events.
+    pub scheduled_tick: f64,
+    /// Start and end tick of the region playback jumps back from, in song mode.
+    pub loop_region: Option<(f64, f64)>,
+    drums: TrackArm,
+    synth: TrackArm,
+    time_signature: TimeSignature,
+}
+
+impl Transport {
+    pub fn new(bpm: u32, sample_rate: u32) -> Self {
+        Transport {
+            state: TransportState::Stopped,
+            bpm: bpm as f64,
+            sample_rate,
+            anchor_frame: 0.0,
+            anchor_tick: 0.0,
+            scheduled_tick: 0.0,
+            loop_region: None,
+            drums: TrackArm::default(),
+            synth: TrackArm::default(),
+            time_signature: TimeSignature::default(),
+        }
+    }
+
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    fn frames_per_tick(&self) -> f64 {
+        60.0 / self.bpm * self.sample_rate as f64 / TICKS_PER_BEAT as f64
+    }
+
+    /// The number of frames `ticks` last at the current tempo.
+    pub fn frames_in(&self, ticks: f64) -> u64 {
+        (ticks * self.frames_per_tick()).round().max(0.0) as u64
+    }
+
+    /// Moves `frame` by `ticks`, which may be negative.
+    pub fn offset_frame(&self, frame: u64, ticks: f64) -> u64 {
+        if ticks < 0.0 {
+            frame.saturating_sub(self.frames_in(-ticks))
+        } else {
+            frame + self.frames_in(ticks)
+        }
+    }
+
+    pub fn ticks_per_bar(&self) -> f64 {
+        self.time_signature.bar_ticks()
+    }
+
+    /// Changes where bars begin. Ticks keep their place, so playback carries on
+    /// without a jump.
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.time_signature = time_signature;
+    }
+
+    pub fn tick_at_frame(&self, frame: u64) -> f64 {
+        self.anchor_tick + (frame as f64 - self.anchor_frame) / self.frames_per_tick()
+    }
+
+    pub fn frame_at_tick(&self, tick: f64) -> u64 {
+        (self.anchor_frame + (tick - self.anchor_tick) * self.frames_per_tick())
+            .round()
+            .max(0.0) as u64
+    }
+
+    pub fn next_bar_tick(&self, tick: f64) -> f64 {
+        (tick / self.ticks_per_bar()).ceil() * self.ticks_per_bar()
+    }
+
+    pub fn arm(&self, track: TransportTrack) -> &TrackArm {
+        match track {
+            TransportTrack::Drums => &self.drums,
+            TransportTrack::Synth => &self.synth,
+        }
+    }
+
+    fn arm_mut(&mut self, track: TransportTrack) -> &mut TrackArm {
+        match track {
+            TransportTrack::Drums => &mut self.drums,
+            TransportTrack::Synth => &mut self.synth,
+        }
+    }
+
+    /// Whether the track is playing or about to, ignoring a pending quantized stop.
+    pub fn is_playing(&self, track: TransportTrack) -> bool {
+        let arm = self.arm(track);
+        self.state != TransportState::Stopped && arm.is_armed() && arm.stop_tick.is_none()
+    }
+
+    /// Starts a track. A stopped transport starts from the first bar at `start_frame`,
+    /// a running one brings the track in on the next bar boundary.
+    pub fn play(&mut self, track: TransportTrack, start_frame: u64) {
+        match self.state {
+            TransportState::Stopped => {
+                self.anchor_frame = start_frame as f64;
+                self.anchor_tick = 0.0;
+                self.scheduled_tick = 0.0;
+                self.state = TransportState::Playing;
+                *self.arm_mut(track) = TrackArm {
+                    start_tick: Some(0.0),
+                    stop_tick: None,
+                };
+            }
+            TransportState::Paused => {
+                self.resume(start_frame);
+                self.play(track, start_frame);
+            }
+            TransportState::Playing => {
+                let next_bar = self.next_bar_tick(self.scheduled_tick);
+                let arm = self.arm_mut(track);
+                if arm.is_armed() {
+                    arm.stop_tick = None;
+                } else {
+                    arm.start_tick = Some(next_bar);
+                }
+            }
+        }
+    }
+
+    /// Stops a track on the next bar boundary, or right away when nothing is running.
+    pub fn stop(&mut self, track: TransportTrack) {
+        if self.state == TransportState::Playing {
+            let next_bar = self.next_bar_tick(self.scheduled_tick);
+            let arm = self.arm_mut(track);
+            if arm.start_tick.is_some_and(|start| start >= next_bar) {
+                *arm = TrackArm::default();
+            } else if arm.is_armed() {
+                arm.stop_tick = Some(next_bar);
+            }
+        } else {
+            *self.arm_mut(track) = TrackArm::default();
+        }
+        self.release_finished_tracks();
+    }
+
+    /// Stops every track at once, used when a song reaches its end.
+    pub fn stop_all(&mut self) {
+        self.drums = TrackArm::default();
+        self.synth = TrackArm::default();
+        self.release_finished_tracks();
+    }
+
+    /// Continues the timeline at `tick` from the frame `from_tick` would have played
+    /// on, which is how a loop region wraps around.
+    pub fn jump(&mut self, from_tick: f64, tick: f64) {
+        self.anchor_frame = self.frame_at_tick(from_tick) as f64;
+        self.anchor_tick = tick;
+        self.scheduled_tick = tick;
+    }
+
+    /// Freezes the transport at the position currently heard at `now_frame`.
+    pub fn pause(&mut self, now_frame: u64) {
+        if self.state == TransportState::Playing {
+            let tick = self.tick_at_frame(now_frame).max(0.0);
+            self.anchor_tick = tick;
+            self.scheduled_tick = tick;
+            self.state = TransportState::Paused;
+        }
+    }
+
+    pub fn resume(&mut self, start_frame: u64) {
+        if self.state == TransportState::Paused {
+            self.anchor_frame = start_frame as f64;
+            self.state = TransportState::Playing;
+        }
+    }
+
+    /// Changes the tempo at the tick the sequencer has scheduled up to, so every
+    /// track picks up the new tempo at the same musical position.
+    pub fn set_bpm(&mut self, bpm: u32) {
+        if self.state == TransportState::Playing {
+            self.anchor_frame = self.frame_at_tick(self.scheduled_tick) as f64;
+            self.anchor_tick = self.scheduled_tick;
+        }
+        self.bpm = bpm as f64;
+    }
+
+    /// Clears tracks whose quantized stop has been reached and stops the transport
+    /// once no track is left.
+    pub fn release_finished_tracks(&mut self) {
+        let scheduled_tick = self.scheduled_tick;
+        for arm in [&mut self.drums, &mut self.synth] {
+            if arm.stop_tick.is_some_and(|stop| stop <= scheduled_tick) {
+                *arm = TrackArm::default();
+            }
+        }
+        if !self.drums.is_armed() && !self.synth.is_armed() {
+            self.state = TransportState::Stopped;
+            self.anchor_tick = 0.0;
+            self.scheduled_tick = 0.0;
+        }
+    }
+
+    /// The window a track plays its looping pattern in, counted from the tick it
+    /// was started on.
+    pub fn track_window(
+        &self,
+        track: TransportTrack,
+        from_tick: f64,
+        to_tick: f64,
+    ) -> Option<StepWindow> {
+        Some(StepWindow {
+            origin_tick: self.arm(track).start_tick?,
+            from_tick,
+            to_tick,
+        })
+    }
+
+    /// Returns the step number (counted from the window's origin) and the engine frame
+    /// of every step of `track` that begins within the window while the track plays.
+    /// Steps are picked on the grid and then moved by the groove.
+    pub fn steps_in(
+        &self,
+        track: TransportTrack,
+        window: StepWindow,
+        step_ticks: f64,
+        groove: &Groove,
+    ) -> Vec<(u64, u64)> {
+        let arm = *self.arm(track);
+        let mut steps = Vec::new();
+        let mut step = ((window.from_tick - window.origin_tick) / step_ticks)
+            .ceil()
+            .max(0.0) as u64;
+        loop {
+            let tick = window.origin_tick + step as f64 * step_ticks;
+            if tick >= window.to_tick {
+                break;
+            }
+            if arm.is_active_at(tick) {
+                let shift = groove.shift_ticks(tick - window.origin_tick);
+                steps.push((step, self.frame_at_tick(tick + shift)));
+            }
+            step += 1;
+        }
+        steps
+    }
+
+    /// The step of `track` heard at `now_frame`, counted from the tick the track
+    /// started on, for a track stepping every `step_ticks`.
+    pub fn step_at(&self, track: TransportTrack, now_frame: u64, step_ticks: f64) -> Option<u64> {
+        let tick = match self.state {
+            TransportState::Stopped => return None,
+            TransportState::Playing => self.tick_at_frame(now_frame).max(0.0),
+            TransportState::Paused => self.anchor_tick,
+        };
+        let arm = self.arm(track);
+        let start_tick = arm.start_tick.filter(|_| arm.is_active_at(tick))?;
+        Some(((tick - start_tick) / step_ticks).floor() as u64)
+    }
+
+    pub fn position(&self, now_frame: u64) -> Position {
+        let tick = match self.state {
+            TransportState::Playing => self.tick_at_frame(now_frame).max(0.0),
+            _ => self.anchor_tick,
+        } as u64;
+        let ticks_per_bar = self.ticks_per_bar() as u64;
+        let beat_ticks = self.time_signature.beat_ticks() as u64;
+        Position {
+            bar: (tick / ticks_per_bar) as u32 + 1,
+            beat: ((tick % ticks_per_bar) / beat_ticks) as u32 + 1,
+            tick: (tick % beat_ticks) as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // At 120 BPM and 48 kHz a beat lasts 24000 frames, a tick 250.
+    fn transport() -> Transport {
+        Transport::new(120, 48_000)
+    }
+
+    #[test]
+    fn ticks_map_to_frames_and_back() {
+        let mut transport = transport();
+        assert_eq!(transport.frames_in(TICKS_PER_BEAT as f64), 24_000);
+        assert_eq!(transport.frames_in(1.5), 375);
+        assert_eq!(transport.frame_at_tick(384.0), 96_000);
+        assert_eq!(transport.tick_at_frame(96_000), 384.0);
+        assert_eq!(transport.offset_frame(1_000, 2.0), 1_500);
+        assert_eq!(transport.offset_frame(1_000, -8.0), 0);
+
+        // Playing anchors tick 0 on the start frame.
+        transport.play(TransportTrack::Drums, 10_000);
+        assert_eq!(transport.frame_at_tick(0.0), 10_000);
+        assert_eq!(transport.frame_at_tick(96.0), 34_000);
+        assert_eq!(transport.tick_at_frame(10_250), 1.0);
+        for tick in [0.0, 7.0, 96.0, 1_000.0] {
+            assert_eq!(transport.tick_at_frame(transport.frame_at_tick(tick)), tick);
+        }
+    }
+
+    #[test]
+    fn tempo_changes_keep_the_scheduled_position() {
+        let mut transport = transport();
+        transport.play(TransportTrack::Drums, 0);
+        transport.scheduled_tick = 384.0;
+        transport.set_bpm(60);
+        assert_eq!(transport.frame_at_tick(384.0), 96_000);
+        assert_eq!(transport.frame_at_tick(480.0), 96_000 + 48_000);
+    }
+
+    #[test]
+    fn tracks_join_and_leave_on_the_next_bar() {
+        let mut transport = transport();
+        transport.play(TransportTrack::Drums, 0);
+        assert_eq!(transport.arm(TransportTrack::Drums).start_tick, Some(0.0));
+
+        transport.scheduled_tick = 100.0;
+        transport.play(TransportTrack::Synth, 0);
+        assert_eq!(transport.arm(TransportTrack::Synth).start_tick, Some(384.0));
+        transport.stop(TransportTrack::Drums);
+        assert_eq!(transport.arm(TransportTrack::Drums).stop_tick, Some(384.0));
+
+        // The synth has no steps before its bar, the drums none after theirs.
+        let window = |origin_tick| StepWindow {
+            origin_tick,
+            from_tick: 0.0,
+            to_tick: 768.0,
+        };
+        let synth = transport.steps_in(
+            TransportTrack::Synth,
+            window(384.0),
+            96.0,
+            &Groove::default(),
+        );
+        assert_eq!(
+            synth,
+            vec![(0, 96_000), (1, 120_000), (2, 144_000), (3, 168_000)]
+        );
+        let drums =
+            transport.steps_in(TransportTrack::Drums, window(0.0), 96.0, &Groove::default());
+        assert_eq!(drums.last(), Some(&(3, 72_000)));
+
+        // In 3/4 the next bar comes sooner.
+        transport.set_time_signature(TimeSignature::new(3, 4));
+        assert_eq!(transport.next_bar_tick(100.0), 288.0);
+        assert_eq!(transport.next_bar_tick(288.0), 288.0);
+    }
+
+    #[test]
+    fn time_signatures_count_bars_and_pulses() {
+        let six_eight = TimeSignature::new(6, 8);
+        assert_eq!(six_eight.bar_ticks(), 288.0);
+        assert_eq!(six_eight.pulse_ticks(), 144.0);
+        assert_eq!(TimeSignature::new(7, 8).pulse_ticks(), 48.0);
+        assert!(six_eight.is_downbeat(576.0));
+        assert!(!six_eight.is_downbeat(144.0));
+
+        let mut transport = transport();
+        transport.play(TransportTrack::Drums, 0);
+        let frame = transport.frame_at_tick(384.0 + 96.0 + 10.0);
+        assert_eq!(
+            transport.position(frame),
+            Position {
+                bar: 2,
+                beat: 2,
+                tick: 10
+            }
+        );
+    }
+}
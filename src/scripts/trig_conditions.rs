@@ -0,0 +1,181 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    scripts::transport::TransportTrack,
+    ui::{
+        step_trigger::{Step, TrigCondition, MAX_PROBABILITY},
+        SequenceState,
+    },
+};
+
+/// Decides which active steps of a track play on each pass over the pattern.
+///
+/// Every decision is a function of the seed and the step's position only, so a
+/// pattern plays the same way live and in offline renders however the sequencer
+/// happens to split up its scheduling windows.
+#[derive(Debug, Clone, Copy)]
+pub struct TrigContext {
+    seed: u64,
+    fill: bool,
+    track: TransportTrack,
+}
+
+impl TrigContext {
+    pub fn new(sequence_state: &SequenceState, track: TransportTrack) -> Self {
+        TrigContext {
+            seed: sequence_state.seed,
+            fill: sequence_state.fill,
+            track,
+        }
+    }
+
+    /// Whether `step` of row `row_index` plays, with `step` counted from the start of
+    /// the row across all of its loops. Every row loops over its own length.
+    pub fn fires<S: Step>(&self, row: &[S], row_index: usize, step: u64) -> bool {
+        let Some(trigger) = self.active_step(row, step).map(Step::trigger) else {
+            return false;
+        };
+        let condition_met = match trigger.condition {
+            TrigCondition::Always => true,
+            TrigCondition::Ratio { a, b } => {
+                let loop_index = step / row.len() as u64;
+                b > 0 && loop_index % b as u64 == (a as u64).saturating_sub(1)
+            }
+            TrigCondition::Previous => self.previous_fired(row, row_index, step),
+            TrigCondition::Fill => self.fill,
+        };
+        condition_met && self.roll(row_index, step, trigger.probability)
+    }
+
+    fn active_step<'a, S: Step>(&self, row: &'a [S], step: u64) -> Option<&'a S> {
+        row.get((step % row.len().max(1) as u64) as usize)
+            .filter(|step| step.is_active())
+    }
+
+    fn previous_fired<S: Step>(&self, row: &[S], row_index: usize, step: u64) -> bool {
+        // A row of nothing but PRE steps has no step to start the chain from.
+        let has_anchor = row
+            .iter()
+            .any(|step| step.is_active() && step.trigger().condition != TrigCondition::Previous);
+        has_anchor
+            && (1..=row.len() as u64)
+                .filter_map(|back| step.checked_sub(back))
+                .find(|previous| self.active_step(row, *previous).is_some())
+                .is_some_and(|previous| self.fires(row, row_index, previous))
+    }
+
+    fn roll(&self, row_index: usize, step: u64, probability: u8) -> bool {
+        if probability >= MAX_PROBABILITY {
+            return true;
+        }
+        let row_id = ((self.track as u64) << 32) | row_index as u64;
+        let mut rng = StdRng::seed_from_u64(
+            self.seed
+                ^ row_id.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                ^ step.wrapping_mul(0xC2B2_AE3D_27D4_EB4F),
+        );
+        rng.gen_range(0..MAX_PROBABILITY) < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{note_step::NoteStep, step_trigger::StepTrigger};
+
+    fn context(seed: u64, fill: bool) -> TrigContext {
+        let sequence_state = SequenceState {
+            seed,
+            fill,
+            ..SequenceState::default()
+        };
+        TrigContext::new(&sequence_state, TransportTrack::Synth)
+    }
+
+    /// A row of `length` steps with the first one active under `trigger`.
+    fn row(length: usize, trigger: StepTrigger) -> Vec<NoteStep> {
+        let mut row = vec![NoteStep::default(); length];
+        row[0].active = true;
+        row[0].trigger = trigger;
+        row
+    }
+
+    fn condition(condition: TrigCondition) -> StepTrigger {
+        StepTrigger {
+            condition,
+            ..StepTrigger::default()
+        }
+    }
+
+    /// The loops out of the first `loops` in which the first step of `row` fires.
+    fn firing_loops(context: &TrigContext, row: &[NoteStep], loops: u64) -> Vec<u64> {
+        (0..loops)
+            .filter(|pass| context.fires(row, 0, pass * row.len() as u64))
+            .collect()
+    }
+
+    #[test]
+    fn ratio_plays_on_loop_a_of_every_b() {
+        let context = context(0, false);
+        let two_of_four = row(4, condition(TrigCondition::Ratio { a: 2, b: 4 }));
+        assert_eq!(firing_loops(&context, &two_of_four, 9), vec![1, 5]);
+        let one_of_two = row(3, condition(TrigCondition::Ratio { a: 1, b: 2 }));
+        assert_eq!(firing_loops(&context, &one_of_two, 6), vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn inactive_steps_never_fire() {
+        let mut row = row(4, StepTrigger::default());
+        assert!(!context(0, false).fires(&row, 0, 1));
+        row[0].active = false;
+        assert!(!context(0, false).fires(&row, 0, 0));
+    }
+
+    #[test]
+    fn probability_is_reproducible_from_the_seed() {
+        let half = row(
+            1,
+            StepTrigger {
+                probability: 50,
+                ..StepTrigger::default()
+            },
+        );
+        let first = firing_loops(&context(7, false), &half, 1000);
+        assert_eq!(first, firing_loops(&context(7, false), &half, 1000));
+        assert_ne!(first, firing_loops(&context(8, false), &half, 1000));
+        assert!((400..600).contains(&first.len()), "{} of 1000", first.len());
+
+        let never = row(
+            1,
+            StepTrigger {
+                probability: 0,
+                ..StepTrigger::default()
+            },
+        );
+        assert!(firing_loops(&context(7, false), &never, 100).is_empty());
+    }
+
+    #[test]
+    fn fill_steps_play_only_in_fill_mode() {
+        let fill = row(4, condition(TrigCondition::Fill));
+        assert!(firing_loops(&context(0, false), &fill, 4).is_empty());
+        assert_eq!(firing_loops(&context(0, true), &fill, 4), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn previous_follows_the_step_before() {
+        let mut row = row(4, condition(TrigCondition::Ratio { a: 1, b: 2 }));
+        row[2].active = true;
+        row[2].trigger = condition(TrigCondition::Previous);
+        let context = context(0, false);
+        let fired: Vec<u64> = (0..4)
+            .map(|pass| pass * 4 + 2)
+            .filter(|&step| context.fires(&row, 0, step))
+            .collect();
+        assert_eq!(fired, vec![2, 10]);
+
+        // With nothing but PRE steps there is no step to follow.
+        row[0].trigger = condition(TrigCondition::Previous);
+        assert!(!context.fires(&row, 0, 6));
+    }
+}
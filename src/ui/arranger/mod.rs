@@ -1 +1,2 @@
 pub mod pattern_buttons;
+pub mod timeline;
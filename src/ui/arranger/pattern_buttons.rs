@@ -1,25 +1,39 @@
-// use iced::{
-//     widget::{checkbox, Button, Column, Row, Text},
-//     Length,
-// };
+use iced::{
+    theme,
+    widget::{button, Button, Row, Text},
+    Length,
+};
 
-// use crate::ui::drum_machine_page::{AudioPlayer, Message};
+use crate::ui::{
+    arranger_page::{ArrangerPage, Message},
+    SequenceState,
+};
 
-// impl AudioPlayer {
-//     pub fn create_pattern_buttons(&self) -> Column<Message> {
-//         self.audio_files
-//             .chunks(4)
-//             .fold(Column::new().spacing(10), |column, chunk| {
-//                 let row = chunk.iter().fold(Row::new().spacing(10), |row, file_name| {
-//                     row.push(
-//                         Button::new(Text::new(file_name))
-//                             .on_press(Message::PlayAndAddSample(file_name.clone()))
-//                             .padding(5)
-//                             .width(Length::FillPortion(1))
-//                             .height(Length::Fixed(80.0)),
-//                     )
-//                 });
-//                 column.push(row)
-//             })
-//     }
-// }
+impl ArrangerPage {
+    pub fn create_pattern_buttons(&self, sequence_state: &SequenceState) -> Row<'_, Message> {
+        let pattern_buttons = sequence_state.patterns.iter().enumerate().fold(
+            Row::new().spacing(10),
+            |row, (index, pattern)| {
+                row.push(
+                    Button::new(Text::new(pattern.name.clone()))
+                        .on_press(Message::SelectPattern(index))
+                        .padding(5)
+                        .width(Length::Fixed(100.0))
+                        .height(Length::Fixed(50.0))
+                        .style(if index == sequence_state.current_pattern {
+                            theme::Button::Primary
+                        } else {
+                            theme::Button::Secondary
+                        }),
+                )
+            },
+        );
+
+        pattern_buttons
+            .push(button("New").on_press(Message::AddPattern))
+            .push(button("Duplicate").on_press(Message::DuplicatePattern))
+            .push(button("Delete").on_press_maybe(
+                (sequence_state.patterns.len() > 1).then_some(Message::DeletePattern),
+            ))
+    }
+}
@@ -0,0 +1,91 @@
+use iced::{
+    alignment, theme,
+    widget::{button, container, Button, Column, Row, Text},
+    Length,
+};
+
+use crate::ui::{
+    arranger_page::{ArrangerPage, Message},
+    SequenceState,
+};
+
+const CELL_WIDTH: f32 = 40.0;
+
+impl ArrangerPage {
+    pub fn create_timeline(&self, sequence_state: &SequenceState) -> Column<'_, Message> {
+        let arrangement = &sequence_state.arrangement;
+        let loop_region = arrangement.loop_region;
+
+        let bar_numbers = (0..arrangement.length_bars).fold(
+            Row::new().push(Text::new("").width(Length::Fixed(150.0))),
+            |row, bar| {
+                let in_loop = arrangement.loop_enabled
+                    && loop_region.start_bar <= bar
+                    && bar < loop_region.end_bar;
+                row.push(
+                    Text::new(if in_loop {
+                        format!("[{}]", bar + 1)
+                    } else {
+                        (bar + 1).to_string()
+                    })
+                    .size(12)
+                    .width(Length::Fixed(CELL_WIDTH))
+                    .horizontal_alignment(alignment::Horizontal::Center),
+                )
+            },
+        );
+
+        let mut column = Column::new().push(bar_numbers).spacing(5);
+        for (track_index, track) in arrangement.tracks.iter().enumerate() {
+            let cells = (0..arrangement.length_bars).fold(Row::new(), |row, bar| {
+                let (label, style) = match arrangement.clip_at(track_index, bar) {
+                    Some(clip_index) => {
+                        let clip = &track.clips[clip_index];
+                        let label = if clip.start_bar == bar {
+                            sequence_state
+                                .patterns
+                                .get(clip.pattern)
+                                .map(|pattern| pattern.name.clone())
+                                .unwrap_or_default()
+                        } else {
+                            String::new()
+                        };
+                        (label, theme::Button::Primary)
+                    }
+                    None => (String::new(), theme::Button::Secondary),
+                };
+                row.push(
+                    Button::new(Text::new(label).size(10))
+                        .on_press(Message::ToggleClip(track_index, bar))
+                        .padding(2)
+                        .width(Length::Fixed(CELL_WIDTH))
+                        .height(Length::Fixed(30.0))
+                        .style(style),
+                )
+            });
+
+            let remove_button = container(
+                button(Text::new("X").size(20))
+                    .on_press(Message::RemoveTrack(track_index))
+                    .padding(0)
+                    .style(theme::Button::Text),
+            )
+            .width(Length::Fixed(30.0))
+            .center_y();
+
+            column = column.push(
+                Row::new()
+                    .push(
+                        Text::new(track.name.clone())
+                            .size(15)
+                            .width(Length::Fixed(150.0)),
+                    )
+                    .push(cells)
+                    .push(remove_button)
+                    .align_items(alignment::Alignment::Center),
+            );
+        }
+
+        column.push(button("Add track").on_press(Message::AddTrack))
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use iced::{
+    widget::{checkbox, scrollable, slider, text_input, Column, Container, Row, Text},
+    Element, Length,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{drum_step::DrumStep, note_step::NoteStep, pattern_bank::Pattern, SequenceState};
+
+/// A pattern of the bank placed on an arranger track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clip {
+    pub pattern: usize,
+    pub start_bar: u32,
+    pub length_bars: u32,
+}
+
+impl Clip {
+    pub fn end_bar(&self) -> u32 {
+        self.start_bar + self.length_bars
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrangerTrack {
+    pub name: String,
+    pub clips: Vec<Clip>,
+}
+
+/// Bars are counted from zero, `end_bar` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoopRegion {
+    pub start_bar: u32,
+    pub end_bar: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Arrangement {
+    /// Plays the timeline instead of looping the selected pattern.
+    pub song_mode: bool,
+    pub length_bars: u32,
+    pub tracks: Vec<ArrangerTrack>,
+    pub loop_enabled: bool,
+    pub loop_region: LoopRegion,
+}
+
+impl Default for Arrangement {
+    fn default() -> Self {
+        Arrangement {
+            song_mode: false,
+            length_bars: 16,
+            tracks: vec![ArrangerTrack {
+                name: "Track 1".to_string(),
+                clips: Vec::new(),
+            }],
+            loop_enabled: false,
+            loop_region: LoopRegion {
+                start_bar: 0,
+                end_bar: 4,
+            },
+        }
+    }
+}
+
+impl Arrangement {
+    pub fn clip_at(&self, track: usize, bar: u32) -> Option<usize> {
+        self.tracks
+            .get(track)?
+            .clips
+            .iter()
+            .position(|clip| clip.start_bar <= bar && bar < clip.end_bar())
+    }
+
+    /// Places a clip starting at `bar`, shortened so it neither overlaps the next clip
+    /// of the track nor runs past the end of the song.
+    pub fn place_clip(&mut self, track: usize, bar: u32, pattern: usize, length_bars: u32) {
+        let length_bars_limit = self.length_bars;
+        let Some(track) = self.tracks.get_mut(track) else {
+            return;
+        };
+        let next_start = track
+            .clips
+            .iter()
+            .map(|clip| clip.start_bar)
+            .filter(|start_bar| *start_bar > bar)
+            .min()
+            .unwrap_or(length_bars_limit);
+        let length_bars = length_bars.min(next_start.saturating_sub(bar)).max(1);
+        track.clips.push(Clip {
+            pattern,
+            start_bar: bar,
+            length_bars,
+        });
+        track.clips.sort_by_key(|clip| clip.start_bar);
+    }
+
+    /// Sets the song length, at least one bar, and shortens the clips and the loop
+    /// region to fit in it.
+    pub fn set_length(&mut self, length_bars: u32) {
+        let length_bars = length_bars.max(1);
+        self.length_bars = length_bars;
+        for track in self.tracks.iter_mut() {
+            track.clips.retain(|clip| clip.start_bar < length_bars);
+            for clip in track.clips.iter_mut() {
+                clip.length_bars = clip.length_bars.clamp(1, length_bars - clip.start_bar);
+            }
+        }
+        let loop_region = &mut self.loop_region;
+        loop_region.end_bar = loop_region.end_bar.clamp(1, length_bars);
+        loop_region.start_bar = loop_region.start_bar.min(loop_region.end_bar - 1);
+    }
+
+    /// Drops the clips of a deleted pattern and renumbers the ones after it.
+    pub fn remove_pattern(&mut self, pattern: usize) {
+        for track in self.tracks.iter_mut() {
+            track.clips.retain(|clip| clip.pattern != pattern);
+            for clip in track.clips.iter_mut() {
+                if clip.pattern > pattern {
+                    clip.pattern -= 1;
+                }
+            }
+        }
+    }
+}
+
+/// Entry of the pattern pick list, shown by name but selected by index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternOption {
+    pub index: usize,
+    pub name: String,
+}
+
+impl fmt::Display for PatternOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SelectPattern(usize),
+    AddPattern,
+    DuplicatePattern,
+    DeletePattern,
+    RenamePattern(String),
+    ToggleClip(usize, u32),
+    AddTrack,
+    RemoveTrack(usize),
+    ToggleSongMode(bool),
+    ToggleLoop(bool),
+    UpdateLoopStart(u32),
+    UpdateLoopEnd(u32),
+    UpdateSongLength(u32),
+    EditFinished,
+}
+
+impl Message {
+    /// Slider messages arrive continuously while dragging, and a rename with every
+    /// keystroke, so each run shares one history entry.
+    pub fn is_continuous(&self) -> bool {
+        matches!(
+            self,
+            Message::UpdateLoopStart(_)
+                | Message::UpdateLoopEnd(_)
+                | Message::UpdateSongLength(_)
+                | Message::RenamePattern(_)
+        )
+    }
+}
+
+pub struct ArrangerPage {
+    sequence_state: Arc<Mutex<SequenceState>>,
+}
+
+impl ArrangerPage {
+    pub fn new(sequence_state: Arc<Mutex<SequenceState>>) -> Self {
+        ArrangerPage { sequence_state }
+    }
+
+    pub fn update(&mut self, message: Message) {
+        let mut sequence_state = self.sequence_state.lock().unwrap();
+        match message {
+            Message::SelectPattern(index) => sequence_state.select_pattern(index),
+            Message::AddPattern | Message::DuplicatePattern => {
+                sequence_state.sync_current_pattern();
+                let mut pattern = sequence_state.patterns[sequence_state.current_pattern].clone();
+                if matches!(message, Message::AddPattern) {
+                    for row in pattern.beat_pattern.iter_mut() {
+                        row.fill(DrumStep::default());
+                    }
+                    for row in pattern.note_pattern.iter_mut() {
+                        row.fill(NoteStep::default());
+                    }
+                }
+                pattern.name = format!("Pattern {}", sequence_state.patterns.len() + 1);
+                sequence_state.patterns.push(pattern);
+                let index = sequence_state.patterns.len() - 1;
+                sequence_state.select_pattern(index);
+            }
+            Message::DeletePattern => {
+                if sequence_state.patterns.len() > 1 {
+                    let index = sequence_state.current_pattern;
+                    sequence_state.patterns.remove(index);
+                    sequence_state.arrangement.remove_pattern(index);
+                    let Pattern {
+                        beat_pattern,
+                        note_pattern,
+                        ..
+                    } = sequence_state.patterns[index.saturating_sub(1)].clone();
+                    sequence_state.current_pattern = index.saturating_sub(1);
+                    sequence_state.beat_pattern = beat_pattern;
+                    sequence_state.note_pattern = note_pattern;
+                }
+            }
+            Message::RenamePattern(name) => {
+                let index = sequence_state.current_pattern;
+                sequence_state.patterns[index].name = name;
+            }
+            // Handled by the history in `MainUi`.
+            Message::EditFinished => {}
+            Message::ToggleClip(track, bar) => {
+                if let Some(clip) = sequence_state.arrangement.clip_at(track, bar) {
+                    sequence_state.arrangement.tracks[track].clips.remove(clip);
+                } else {
+                    let pattern = sequence_state.current_pattern;
+                    let length_bars = sequence_state.pattern_bars();
+                    sequence_state
+                        .arrangement
+                        .place_clip(track, bar, pattern, length_bars);
+                }
+            }
+            Message::AddTrack => {
+                let name = format!("Track {}", sequence_state.arrangement.tracks.len() + 1);
+                sequence_state.arrangement.tracks.push(ArrangerTrack {
+                    name,
+                    clips: Vec::new(),
+                });
+            }
+            Message::RemoveTrack(track) => {
+                if track < sequence_state.arrangement.tracks.len() {
+                    sequence_state.arrangement.tracks.remove(track);
+                }
+            }
+            Message::ToggleSongMode(song_mode) => sequence_state.arrangement.song_mode = song_mode,
+            Message::ToggleLoop(loop_enabled) => {
+                sequence_state.arrangement.loop_enabled = loop_enabled
+            }
+            Message::UpdateLoopStart(bar) => {
+                let loop_region = &mut sequence_state.arrangement.loop_region;
+                loop_region.start_bar = bar;
+                loop_region.end_bar = loop_region.end_bar.max(bar + 1);
+            }
+            Message::UpdateLoopEnd(bar) => {
+                let loop_region = &mut sequence_state.arrangement.loop_region;
+                loop_region.end_bar = bar.max(1);
+                loop_region.start_bar = loop_region.start_bar.min(loop_region.end_bar - 1);
+            }
+            Message::UpdateSongLength(length_bars) => {
+                sequence_state.arrangement.set_length(length_bars)
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let sequence_state = self.sequence_state.lock().unwrap();
+        let arrangement = &sequence_state.arrangement;
+        let loop_region = arrangement.loop_region;
+
+        let song_settings = Row::new()
+            .push(checkbox("Song mode", arrangement.song_mode).on_toggle(Message::ToggleSongMode))
+            .push(Text::new(format!(
+                "Song length: {} bars",
+                arrangement.length_bars
+            )))
+            .push(
+                slider(1..=64, arrangement.length_bars, Message::UpdateSongLength)
+                    .on_release(Message::EditFinished)
+                    .width(Length::Fixed(150.0)),
+            )
+            .push(checkbox("Loop", arrangement.loop_enabled).on_toggle(Message::ToggleLoop))
+            .push(Text::new(format!(
+                "Loop: bar {} to {}",
+                loop_region.start_bar + 1,
+                loop_region.end_bar
+            )))
+            .push(
+                slider(
+                    0..=arrangement.length_bars - 1,
+                    loop_region.start_bar,
+                    Message::UpdateLoopStart,
+                )
+                .on_release(Message::EditFinished)
+                .width(Length::Fixed(150.0)),
+            )
+            .push(
+                slider(
+                    1..=arrangement.length_bars,
+                    loop_region.end_bar,
+                    Message::UpdateLoopEnd,
+                )
+                .on_release(Message::EditFinished)
+                .width(Length::Fixed(150.0)),
+            )
+            .spacing(10);
+
+        let current_name = sequence_state.patterns[sequence_state.current_pattern]
+            .name
+            .clone();
+        let pattern_editor = Row::new()
+            .push(Text::new("Pattern name:"))
+            .push(
+                text_input("Pattern name", &current_name)
+                    .on_input(Message::RenamePattern)
+                    .on_submit(Message::EditFinished)
+                    .width(Length::Fixed(200.0)),
+            )
+            .spacing(10);
+
+        let content = Column::new()
+            .push(Text::new("Arranger").size(30))
+            .push(song_settings)
+            .push(Text::new("Pattern Bank").size(20))
+            .push(self.create_pattern_buttons(&sequence_state))
+            .push(pattern_editor)
+            .push(Text::new("Timeline").size(20))
+            .push(self.create_timeline(&sequence_state))
+            .spacing(20);
+
+        scrollable(Container::new(content).width(Length::Fill).padding(20))
+            .height(Length::Fill)
+            .into()
+    }
+}
@@ -1,55 +1,116 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc, Arc, Mutex, RwLock,
-    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
     thread,
     time::Duration,
 };
 
 use iced::{
+    futures::channel::oneshot,
     widget::{checkbox, scrollable, slider, Button, Column, Container, PickList, Row, Text},
     Command, Element, Length, Renderer, Theme,
 };
-use rodio::{OutputStream, OutputStreamHandle};
+use notify::RecommendedWatcher;
+use serde::{Deserialize, Serialize};
 
-use crate::scripts::record_pattern::record_pattern;
+use crate::scripts::{
+    audio_engine::{engine::EngineHandle, mixer::Channel},
+    drum_machine::{play_drum_audio::SampleLoadError, sample_cache::SampleCache},
+    euclidean::euclidean_pattern,
+    groove::{Groove, GrooveTemplate},
+    record_pattern::record_pattern,
+    transport::{Transport, TransportTrack, TICKS_PER_BEAT},
+};
 
-use super::{MainUi, Page, SequenceState};
+use super::{
+    drum_step::{Ratchet, MAX_VELOCITY},
+    step_trigger::StepTrigger,
+    track_mix::TrackMix,
+    track_timing::TrackTiming,
+    MainUi, Page, SequenceState,
+};
 
 pub struct DrumMachine {
-    output_stream: OutputStream,
-    stream_handle: Arc<rodio::OutputStreamHandle>,
+    pub engine: EngineHandle,
     pub audio_files: Vec<String>,
-    play_sender: mpsc::Sender<bool>,
-    pub is_playing: Arc<Mutex<bool>>,
+    pub transport: Arc<Mutex<Transport>>,
     pub selected_samples: Arc<RwLock<BTreeMap<usize, HashMap<String, SampleFolder>>>>,
+    pub sample_cache: SampleCache,
     pub sequence_scale_options: Vec<SequenceScale>,
-    pub sequence_scale: SequenceScale,
     pub playback_state: Arc<Mutex<PlaybackState>>,
-    pub beat_pattern_sender: crossbeam_channel::Sender<Vec<Vec<bool>>>,
-    pub beat_pattern_receiver: crossbeam_channel::Receiver<Vec<Vec<bool>>>,
     pub root_sample_folder: String,
+    /// Sample roots added by the user, scanned after `root_sample_folder`.
+    pub sample_roots: Vec<String>,
+    pub sample_root_input: String,
     pub sample_folders_options: Vec<SampleFolder>,
-    pub sample_folder: SampleFolder,
+    pub sample_folder: Option<SampleFolder>,
+    /// Samples that failed to decode, by path, each reported once.
+    pub sample_errors: BTreeMap<PathBuf, SampleLoadError>,
+    /// Paths beneath the sample roots changed on disk since the last rescan.
+    changed_paths: Arc<Mutex<HashSet<PathBuf>>>,
+    _kit_watcher: Option<RecommendedWatcher>,
+    /// Set while a recording renders, one runs at a time.
+    pub recording: bool,
     pub add_sample_on_play: bool,
+    /// Turns the steps of the grid into buttons that cycle through ratchets.
+    pub edit_ratchets: bool,
+    /// The drum row whose velocities are shown below the grid.
+    pub velocity_lane: Option<usize>,
+    /// The drum row whose groove controls are shown below it.
+    pub groove_row: Option<usize>,
+    /// The open Euclidean generator, its rhythm is only previewed until applied.
+    pub euclid: Option<EuclidEditor>,
+    pub groove_templates: Vec<GrooveTemplate>,
     pub sequence_state: Arc<Mutex<SequenceState>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The settings of the Euclidean generator of one drum row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EuclidEditor {
+    pub row: usize,
+    pub hits: u32,
+    pub rotation: u32,
+}
 
-pub enum SampleFolder {
-    NineONine,
-    EightOEight,
+impl EuclidEditor {
+    pub fn preview(&self, length: u32) -> Vec<bool> {
+        euclidean_pattern(self.hits, length, self.rotation)
+    }
 }
+
+/// A drum kit: a folder holding audio files, found by scanning the sample roots.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "String", from = "String")]
+pub struct SampleFolder(PathBuf);
+
+impl SampleFolder {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        SampleFolder(path.into())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
 impl fmt::Display for SampleFolder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            SampleFolder::NineONine => write!(f, "909"),
-            SampleFolder::EightOEight => write!(f, "TR-808 Kit"),
-        }
+        write!(f, "{}", self.0.display())
+    }
+}
+
+// Project files store the folder path, so they stay readable and survive reordering.
+impl From<SampleFolder> for String {
+    fn from(folder: SampleFolder) -> String {
+        folder.to_string()
+    }
+}
+
+impl From<String> for SampleFolder {
+    fn from(path: String) -> Self {
+        SampleFolder::new(path)
     }
 }
 
@@ -62,130 +123,268 @@ pub struct PlaybackState {
 pub enum Message {
     // ToggleDrumSequence(bool),
     UpdateBeatPattern(usize, usize, bool),
+    UpdateVelocity(usize, usize, u8),
+    /// Sets the probability and condition of a drum step.
+    UpdateTrigger(usize, usize, StepTrigger),
+    UpdateRatchet(usize, usize, Ratchet),
+    /// Sets the length and resolution of a drum track.
+    UpdateTrackTiming(usize, TrackTiming),
+    /// Sets the mute, solo, gain and pan of a drum track.
+    UpdateTrackMix(usize, TrackMix),
+    /// Opens the Euclidean generator of a drum row, or closes it if it is open.
+    ToggleEuclid(usize),
+    UpdateEuclid(EuclidEditor),
+    /// Writes the previewed Euclidean rhythm into the row.
+    ApplyEuclid,
+    ToggleEditRatchets(bool),
+    /// Shows or hides the velocity and trigger lanes of a drum row.
+    ToggleVelocityLane(usize),
+    /// Sent when a velocity or probability slider is released.
+    EditFinished,
     // UpdateBPM(u32),
     PlayAndAddSample(String),
     RecordPattern,
+    /// The render of a recording has been written, or failed.
+    PatternRecorded(Result<(), String>),
     ChangeSequenceScale(SequenceScale),
+    /// Gives the drums a groove of their own, `None` makes them follow the global groove.
+    UpdateGroove(Option<Groove>),
+    /// Gives a drum row a groove of its own, `None` makes it follow the drums' groove.
+    UpdateRowGroove(usize, Option<Groove>),
+    /// Shows or hides the groove controls of a drum row.
+    ToggleGrooveRow(usize),
     RemoveSample(usize),
     ChangeSampleFolder(SampleFolder),
+    RescanKits,
+    /// Sent periodically, rescans the kits if the sample roots changed on disk.
+    CheckKits,
+    SampleRootInputChanged(String),
+    AddSampleRoot,
+    RemoveSampleRoot(usize),
     ToggleAddSampleOnPlay(bool),
     PlaySequence,
     StopSequence,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SequenceScale {
     OneFourth,
+    OneFourthDotted,
     OneEighth,
+    OneEighthDotted,
+    OneEighthTriplet,
     OneSixteenth,
+    OneSixteenthDotted,
+    OneSixteenthTriplet,
+    OneThirtySecond,
 }
 impl fmt::Display for SequenceScale {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             SequenceScale::OneFourth => write!(f, "1/4"),
+            SequenceScale::OneFourthDotted => write!(f, "1/4."),
             SequenceScale::OneEighth => write!(f, "1/8"),
+            SequenceScale::OneEighthDotted => write!(f, "1/8."),
+            SequenceScale::OneEighthTriplet => write!(f, "1/8T"),
             SequenceScale::OneSixteenth => write!(f, "1/16"),
+            SequenceScale::OneSixteenthDotted => write!(f, "1/16."),
+            SequenceScale::OneSixteenthTriplet => write!(f, "1/16T"),
+            SequenceScale::OneThirtySecond => write!(f, "1/32"),
+        }
+    }
+}
+
+impl SequenceScale {
+    pub const ALL: [SequenceScale; 9] = [
+        SequenceScale::OneFourth,
+        SequenceScale::OneFourthDotted,
+        SequenceScale::OneEighth,
+        SequenceScale::OneEighthDotted,
+        SequenceScale::OneEighthTriplet,
+        SequenceScale::OneSixteenth,
+        SequenceScale::OneSixteenthDotted,
+        SequenceScale::OneSixteenthTriplet,
+        SequenceScale::OneThirtySecond,
+    ];
+
+    /// The length of one step in transport ticks. Every resolution is a whole number
+    /// of ticks, so steps land on exactly the same frames live and in exports.
+    pub fn step_ticks(&self) -> f64 {
+        let beat = TICKS_PER_BEAT as f64;
+        match self {
+            SequenceScale::OneFourth => beat,
+            SequenceScale::OneFourthDotted => beat * 1.5,
+            SequenceScale::OneEighth => beat / 2.0,
+            SequenceScale::OneEighthDotted => beat / 2.0 * 1.5,
+            SequenceScale::OneEighthTriplet => beat / 3.0,
+            SequenceScale::OneSixteenth => beat / 4.0,
+            SequenceScale::OneSixteenthDotted => beat / 4.0 * 1.5,
+            SequenceScale::OneSixteenthTriplet => beat / 6.0,
+            SequenceScale::OneThirtySecond => beat / 8.0,
         }
     }
+
+    pub fn step_duration(&self, bpm: f64) -> Duration {
+        Duration::from_secs_f64(60.0 / bpm * self.step_ticks() / TICKS_PER_BEAT as f64)
+    }
 }
 
 impl DrumMachine {
-    pub fn new(sequence_state: Arc<Mutex<SequenceState>>) -> (Self, Command<Message>) {
-        let (stream, stream_handle) = OutputStream::try_default().unwrap();
-        let audio_files = Self::get_audio_files("drumKits/909");
-        let (play_sender, play_receiver) = mpsc::channel();
-        let is_playing = Arc::new(Mutex::new(false));
-        let is_playing_clone = is_playing.clone();
+    pub fn new(
+        sequence_state: Arc<Mutex<SequenceState>>,
+        transport: Arc<Mutex<Transport>>,
+        engine: EngineHandle,
+        groove_templates: Vec<GrooveTemplate>,
+    ) -> (Self, Command<Message>) {
         let selected_samples = Arc::new(RwLock::new(BTreeMap::new()));
-        let selected_samples_clone = selected_samples.clone();
-        let sequence_state_clone = sequence_state.clone();
-        let sequence_scale_options = vec![
-            SequenceScale::OneFourth,
-            SequenceScale::OneEighth,
-            SequenceScale::OneSixteenth,
-        ];
-        let sequence_scale = sequence_state.lock().unwrap().drum_scale; //might cause deadlock
-
-        let (beat_pattern_sender, beat_pattern_receiver) = crossbeam_channel::unbounded();
+        let sequence_scale_options = SequenceScale::ALL.to_vec();
 
         let playback_state = Arc::new(Mutex::new(PlaybackState {
             play_sequence_on: false,
         }));
         let root_sample_folder = "drumKits".to_string();
-        let root_sample_folder_clone = root_sample_folder.clone();
-        let sample_folders_options = vec![SampleFolder::NineONine, SampleFolder::EightOEight];
-        let sample_folder = SampleFolder::NineONine;
-        thread::spawn(move || {
-            let mut stream_option: Option<(OutputStream, OutputStreamHandle)> = None;
-            loop {
-                if let Ok(should_play) = play_receiver.recv() {
-                    if should_play {
-                        *is_playing_clone.lock().unwrap() = true;
-                        if stream_option.is_none() {
-                            stream_option = OutputStream::try_default().ok();
-                        }
-                        if let Some((_, ref stream_handle)) = stream_option {
-                            Self::play_pattern(
-                                sequence_state_clone.clone(),
-                                is_playing_clone.clone(),
-                                stream_handle,
-                                selected_samples_clone.clone(),
-                                &root_sample_folder_clone,
-                            );
-                        }
-                    } else {
-                        *is_playing_clone.lock().unwrap() = false;
-                        stream_option = None;
-                    }
-                }
-            }
-        });
-        (
-            DrumMachine {
-                output_stream: stream,
-                stream_handle: Arc::new(stream_handle),
-                audio_files,
-                playback_state,
-                beat_pattern_sender,
-                beat_pattern_receiver,
-                play_sender,
-                is_playing,
-                selected_samples,
-                sequence_scale_options,
-                sequence_scale,
-                root_sample_folder,
-                sample_folders_options,
-                sample_folder,
-                add_sample_on_play: false,
-                sequence_state,
-            },
-            Command::none(),
-        )
+        let mut drum_machine = DrumMachine {
+            engine,
+            audio_files: Vec::new(),
+            transport,
+            playback_state,
+            selected_samples,
+            sample_cache: SampleCache::default(),
+            sequence_scale_options,
+            root_sample_folder,
+            sample_roots: Self::load_sample_roots(),
+            sample_root_input: String::new(),
+            sample_folders_options: Vec::new(),
+            sample_folder: None,
+            sample_errors: BTreeMap::new(),
+            changed_paths: Arc::new(Mutex::new(HashSet::new())),
+            _kit_watcher: None,
+            recording: false,
+            add_sample_on_play: false,
+            edit_ratchets: false,
+            velocity_lane: None,
+            groove_row: None,
+            euclid: None,
+            groove_templates,
+            sequence_state,
+        };
+        drum_machine.watch_kits();
+        drum_machine.rescan_kits();
+        (drum_machine, Command::none())
+    }
+
+    fn all_sample_roots(&self) -> Vec<String> {
+        std::iter::once(self.root_sample_folder.clone())
+            .chain(self.sample_roots.iter().cloned())
+            .collect()
+    }
+
+    fn watch_kits(&mut self) {
+        self._kit_watcher =
+            Self::watch_sample_roots(&self.all_sample_roots(), self.changed_paths.clone());
+    }
+
+    /// Forgets the decoded samples and errors of the kits changed on disk, so their
+    /// files are decoded afresh, and rescans when anything changed.
+    fn check_kits(&mut self) {
+        let changed_paths = std::mem::take(&mut *self.changed_paths.lock().unwrap());
+        if changed_paths.is_empty() {
+            return;
+        }
+        for path in &changed_paths {
+            self.sample_cache.evict(path);
+            let kit = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or(path)
+            };
+            self.sample_errors
+                .retain(|sample_path, _| !sample_path.starts_with(kit));
+        }
+        self.rescan_kits();
+    }
+
+    /// Moves the decode failures the cache has seen since the last call into the errors
+    /// shown with the kit.
+    fn collect_sample_errors(&mut self) {
+        for error in self.sample_cache.take_failures() {
+            self.sample_errors.insert(error.path.clone(), error);
+        }
+    }
+
+    /// Rebuilds the kit list, keeping the selected kit when it still exists.
+    fn rescan_kits(&mut self) {
+        self.sample_folders_options = Self::discover_kits(&self.all_sample_roots());
+        if !self
+            .sample_folder
+            .as_ref()
+            .is_some_and(|folder| self.sample_folders_options.contains(folder))
+        {
+            self.sample_folder = self.sample_folders_options.first().cloned();
+        }
+        self.audio_files = self
+            .sample_folder
+            .as_ref()
+            .map(|folder| Self::get_audio_files(folder.path()))
+            .unwrap_or_default();
+    }
+
+    fn set_sample_roots(&mut self, sample_roots: Vec<String>) {
+        self.sample_roots = sample_roots;
+        if let Err(e) = Self::save_sample_roots(&self.sample_roots) {
+            println!("Error saving sample roots: {:?}", e);
+        }
+        self.watch_kits();
+        self.rescan_kits();
     }
 
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::PlaySequence => {
-                let mut is_playing = self.is_playing.lock().unwrap();
-                if !*is_playing {
-                    *is_playing = true;
-                    self.play_sender.send(true).unwrap();
-                }
+                self.transport
+                    .lock()
+                    .unwrap()
+                    .play(TransportTrack::Drums, self.engine.lookahead_frame());
                 return Command::none();
             }
             Message::StopSequence => {
-                let mut is_playing = self.is_playing.lock().unwrap();
-                *is_playing = false;
-                self.play_sender.send(false).unwrap();
+                self.transport.lock().unwrap().stop(TransportTrack::Drums);
                 return Command::none();
             }
             Message::ToggleAddSampleOnPlay(checked) => {
                 self.add_sample_on_play = checked;
             }
             Message::ChangeSampleFolder(folder) => {
-                self.sample_folder = folder.clone();
-                self.audio_files =
-                    Self::get_audio_files(&format!("{}/{}", self.root_sample_folder, folder));
+                self.audio_files = Self::get_audio_files(folder.path());
+                self.sample_folder = Some(folder);
+            }
+            Message::RescanKits => {
+                // Asked for by hand, so everything is decoded afresh.
+                self.changed_paths.lock().unwrap().clear();
+                self.sample_cache.clear();
+                self.sample_errors.clear();
+                self.rescan_kits();
+            }
+            Message::CheckKits => {
+                self.check_kits();
+                self.collect_sample_errors();
+            }
+            Message::SampleRootInputChanged(input) => self.sample_root_input = input,
+            Message::AddSampleRoot => {
+                let root = self.sample_root_input.trim().to_string();
+                if !root.is_empty() && !self.sample_roots.contains(&root) {
+                    let mut sample_roots = self.sample_roots.clone();
+                    sample_roots.push(root);
+                    self.set_sample_roots(sample_roots);
+                }
+                self.sample_root_input.clear();
+            }
+            Message::RemoveSampleRoot(index) => {
+                if index < self.sample_roots.len() {
+                    let mut sample_roots = self.sample_roots.clone();
+                    sample_roots.remove(index);
+                    self.set_sample_roots(sample_roots);
+                }
             }
             Message::RemoveSample(index) => {
                 let mut selected_samples = self.selected_samples.write().unwrap();
@@ -198,39 +397,61 @@ impl DrumMachine {
                     *selected_samples = new_samples;
                 }
 
-                let mut sequence_state = self.sequence_state.lock().unwrap();
-                if index < sequence_state.beat_pattern.len() {
-                    sequence_state.beat_pattern.remove(index);
-                }
+                self.sequence_state.lock().unwrap().remove_drum_row(index);
+                self.velocity_lane = None;
+                self.groove_row = None;
+                self.euclid = None;
             }
             Message::ChangeSequenceScale(new_sequence_size) => {
                 self.sequence_state.lock().unwrap().drum_scale = new_sequence_size;
                 return Command::none();
             }
+            Message::UpdateGroove(groove) => {
+                self.sequence_state.lock().unwrap().drum_groove = groove;
+            }
+            Message::UpdateRowGroove(file_index, groove) => {
+                self.sequence_state
+                    .lock()
+                    .unwrap()
+                    .set_drum_row_groove(file_index, groove);
+            }
+            Message::ToggleGrooveRow(file_index) => {
+                self.groove_row = (self.groove_row != Some(file_index)).then_some(file_index);
+            }
             Message::RecordPattern => {
-                let sequence_state = self.sequence_state.lock().unwrap();
-                let playback_state = self.playback_state.lock().unwrap();
-                let path = self.root_sample_folder.clone() + "/";
-                let beat_scale = match self.sequence_scale {
-                    SequenceScale::OneEighth => 2,
-                    SequenceScale::OneSixteenth => 4,
-                    SequenceScale::OneFourth => 1,
-                };
+                if self.recording {
+                    return Command::none();
+                }
+                // The render takes a while, so it runs on a thread of its own from copies
+                // of the state, leaving the UI and the sequencer free to use it meanwhile.
+                let sequence_state = self.sequence_state.lock().unwrap().clone();
+                let selected_samples = self.selected_samples.read().unwrap().clone();
+                let sample_cache = self.sample_cache.clone();
                 let output_file = format!(
                     "pattern_{}.wav",
                     chrono::Local::now().format("%Y%m%d_%H%M%S")
                 );
-                if let Err(e) = record_pattern(
-                    &sequence_state.beat_pattern,
-                    &self.audio_files,
-                    self.sequence_state.lock().unwrap().bpm, //might cause freeze when recording
-                    sequence_state.sequence_length,
-                    &self.selected_samples,
-                    &output_file,
-                    &path,
-                    beat_scale,
-                ) {
-                    println!("Error recording pattern: {:?}", e);
+                let (sender, receiver) = oneshot::channel();
+                thread::spawn(move || {
+                    let result = record_pattern(
+                        &sequence_state,
+                        &selected_samples,
+                        &sample_cache,
+                        &output_file,
+                    );
+                    let _ = sender.send(result.map_err(|e| e.to_string()));
+                });
+                self.recording = true;
+                return Command::perform(receiver, |result| {
+                    Message::PatternRecorded(
+                        result.unwrap_or_else(|_| Err("the render stopped".to_string())),
+                    )
+                });
+            }
+            Message::PatternRecorded(result) => {
+                self.recording = false;
+                if let Err(e) = result {
+                    println!("Error recording pattern: {}", e);
                 }
             }
             Message::UpdateBeatPattern(file_index, beat_index, checked) => {
@@ -238,49 +459,128 @@ impl DrumMachine {
                 if file_index < sequence_state.beat_pattern.len()
                     && beat_index < sequence_state.beat_pattern[file_index].len()
                 {
-                    sequence_state.beat_pattern[file_index][beat_index] = checked;
-                    let _ = self
-                        .beat_pattern_sender
-                        .send(sequence_state.beat_pattern.clone());
+                    sequence_state.beat_pattern[file_index][beat_index].active = checked;
+                }
+            }
+            Message::UpdateVelocity(file_index, beat_index, velocity) => {
+                let mut sequence_state = self.sequence_state.lock().unwrap();
+                if let Some(drum_step) = sequence_state
+                    .beat_pattern
+                    .get_mut(file_index)
+                    .and_then(|row| row.get_mut(beat_index))
+                {
+                    drum_step.velocity = velocity.min(MAX_VELOCITY);
+                }
+            }
+            Message::UpdateRatchet(file_index, beat_index, ratchet) => {
+                let mut sequence_state = self.sequence_state.lock().unwrap();
+                if let Some(drum_step) = sequence_state
+                    .beat_pattern
+                    .get_mut(file_index)
+                    .and_then(|row| row.get_mut(beat_index))
+                {
+                    drum_step.ratchet = ratchet;
                 }
             }
+            Message::ToggleEditRatchets(edit_ratchets) => self.edit_ratchets = edit_ratchets,
+            Message::UpdateTrackMix(file_index, mix) => {
+                self.sequence_state
+                    .lock()
+                    .unwrap()
+                    .set_drum_mix(file_index, mix);
+            }
+            Message::UpdateTrackTiming(file_index, timing) => {
+                self.sequence_state
+                    .lock()
+                    .unwrap()
+                    .set_drum_timing(file_index, timing);
+            }
+            Message::UpdateTrigger(file_index, beat_index, trigger) => {
+                let mut sequence_state = self.sequence_state.lock().unwrap();
+                if let Some(drum_step) = sequence_state
+                    .beat_pattern
+                    .get_mut(file_index)
+                    .and_then(|row| row.get_mut(beat_index))
+                {
+                    drum_step.trigger = trigger;
+                }
+            }
+            Message::ToggleEuclid(file_index) => {
+                self.euclid = match self.euclid {
+                    Some(euclid) if euclid.row == file_index => None,
+                    _ => {
+                        let sequence_state = self.sequence_state.lock().unwrap();
+                        sequence_state
+                            .beat_pattern
+                            .get(file_index)
+                            .map(|row| EuclidEditor {
+                                row: file_index,
+                                hits: row.iter().filter(|drum_step| drum_step.active).count()
+                                    as u32,
+                                rotation: 0,
+                            })
+                    }
+                };
+            }
+            Message::UpdateEuclid(euclid) => self.euclid = Some(euclid),
+            Message::ApplyEuclid => {
+                if let Some(euclid) = self.euclid.take() {
+                    let mut sequence_state = self.sequence_state.lock().unwrap();
+                    if let Some(row) = sequence_state.beat_pattern.get_mut(euclid.row) {
+                        let preview = euclid.preview(row.len() as u32);
+                        for (drum_step, active) in row.iter_mut().zip(preview) {
+                            drum_step.active = active;
+                        }
+                    }
+                }
+            }
+            Message::ToggleVelocityLane(file_index) => {
+                self.velocity_lane = (self.velocity_lane != Some(file_index)).then_some(file_index);
+            }
+            // Handled by the history in `MainUi`.
+            Message::EditFinished => {}
             Message::PlayAndAddSample(sample_name) => {
+                let Some(sample_folder) = self.sample_folder.clone() else {
+                    return Command::none();
+                };
                 if self.add_sample_on_play {
                     let mut sequence_state = self.sequence_state.lock().unwrap();
-                    let sequence_length = sequence_state.sequence_length as usize;
 
                     let mut selected_samples = self.selected_samples.write().unwrap();
+                    // The same file name can exist in several kits.
                     if !selected_samples
                         .values()
-                        .any(|v| v.keys().next().unwrap() == &sample_name)
+                        .any(|v| v.get(&sample_name) == Some(&sample_folder))
                     {
                         let new_index = selected_samples.len();
                         let mut file_map = HashMap::new();
-                        file_map.insert(sample_name.clone(), self.sample_folder.clone());
+                        file_map.insert(sample_name.clone(), sample_folder.clone());
 
                         selected_samples.insert(new_index, file_map);
 
-                        sequence_state
-                            .beat_pattern
-                            .push(vec![false; sequence_length]);
+                        sequence_state.insert_drum_row(
+                            new_index,
+                            &[],
+                            TrackTiming::default(),
+                            None,
+                            TrackMix::default(),
+                        );
                     }
                     drop(selected_samples);
                     drop(sequence_state);
                 }
 
-                // Play the sample (unchanged)
-                let note_duration = Duration::from_millis((60_000 / 120) as u64);
-                let path = self.root_sample_folder.clone() + "/" + &self.sample_folder.to_string();
-                let stream_handle = Arc::new(self.stream_handle.clone());
-                thread::spawn(move || {
-                    Self::play_audio(&stream_handle, note_duration, sample_name.clone(), &path);
-                });
-                // play_audio(
-                //     &self.stream_handle,
-                //     note_duration,
-                //     sample_name.clone(),
-                //     &path,
-                // );
+                // A failure is picked up from the cache along with the others.
+                let _ = Self::play_audio(
+                    &self.engine,
+                    &self.sample_cache,
+                    self.engine.now(),
+                    Channel::Preview,
+                    1.0,
+                    &sample_name,
+                    sample_folder.path(),
+                );
+                self.collect_sample_errors();
             }
         }
         Command::none()
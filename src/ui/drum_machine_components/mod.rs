@@ -1,2 +1,3 @@
 pub mod sample_buttons;
 pub mod sequence_view;
+pub mod velocity_lane;
@@ -1,5 +1,5 @@
 use iced::{
-    widget::{checkbox, Button, Column, PickList, Row, Text},
+    widget::{button, checkbox, Button, Column, PickList, Row, Text},
     Length, Renderer, Theme,
 };
 
@@ -19,9 +19,10 @@ impl DrumMachine {
             Renderer,
         > = PickList::new(
             self.sample_folders_options.clone(),
-            Some(self.sample_folder.clone()),
+            self.sample_folder.clone(),
             Message::ChangeSampleFolder,
-        );
+        )
+        .placeholder("No drum kits found");
         let add_sample_checkbox: iced::widget::Checkbox<'_, Message, Theme, Renderer> = checkbox(
             "Add sample to pattern",
             self.add_sample_on_play,
@@ -34,16 +35,30 @@ impl DrumMachine {
             .push(
                 Row::new()
                     .push(folder_pick_list)
+                    .push(button("Rescan Kits").on_press(Message::RescanKits))
                     .push(add_sample_checkbox)
                     .spacing(10),
             )
             .spacing(10);
 
+        let column = self.sample_errors.values().fold(column, |column, error| {
+            column.push(Text::new(error.to_string()))
+        });
+
         sorted_files.chunks(4).fold(column, |column, chunk| {
             let row = chunk.iter().fold(Row::new().spacing(10), |row, file_name| {
                 let file_name = file_name.clone();
+                let failed = self.sample_folder.as_ref().is_some_and(|folder| {
+                    self.sample_errors
+                        .contains_key(&folder.path().join(&file_name))
+                });
+                let label = if failed {
+                    format!("{} (failed to decode)", file_name)
+                } else {
+                    file_name.clone()
+                };
                 row.push(
-                    Button::new(Text::new(file_name.clone()))
+                    Button::new(Text::new(label))
                         .on_press(Message::PlayAndAddSample(file_name))
                         .padding(5)
                         .width(Length::FillPortion(1))
@@ -1,21 +1,30 @@
 use iced::alignment::{self, Vertical};
 use iced::font::Style;
 use iced::futures::future::select;
-use iced::widget::{button, checkbox, container, Button, PickList};
+use iced::widget::{button, checkbox, container, pick_list, slider, Button, PickList};
 use iced::{theme, Renderer, Theme};
 use iced::{
-    widget::{Checkbox, Column, Row, Text},
+    widget::{Checkbox, Column, Row, Space, Text},
     Background, Color, Length,
 };
 
-use crate::ui::drum_machine::{self, DrumMachine, Message, SequenceScale};
+use crate::scripts::{groove::Groove, transport::TransportTrack};
+use crate::ui::drum_machine::{self, DrumMachine, EuclidEditor, Message, SequenceScale};
+use crate::ui::groove_controls::create_track_groove_controls;
+use crate::ui::track_mix::{pan_label, TrackMix, MAX_GAIN_DB, MIN_GAIN_DB};
+use crate::ui::track_timing::{length_options, TrackOption, TrackTiming};
+use crate::ui::trigger_lane::{create_trigger_lane, STEP_WIDTH};
 
 impl DrumMachine {
     pub fn create_sequence_view(&self) -> Column<Message> {
         let playback_state = self.playback_state.lock().unwrap();
         let play_sequence_on = playback_state.play_sequence_on;
         drop(playback_state);
-        let record_button = Button::new(Text::new("Record")).on_press(Message::RecordPattern);
+        let record_button = if self.recording {
+            Button::new(Text::new("Recording..."))
+        } else {
+            Button::new(Text::new("Record")).on_press(Message::RecordPattern)
+        };
         let sequence_length_pick_list: iced::widget::PickList<
             '_,
             SequenceScale,
@@ -29,25 +38,84 @@ impl DrumMachine {
             Some(self.sequence_state.lock().unwrap().drum_scale.clone()),
             Message::ChangeSequenceScale,
         );
-        let play_button = if *self.is_playing.lock().unwrap() {
+        let play_button = if self
+            .transport
+            .lock()
+            .unwrap()
+            .is_playing(TransportTrack::Drums)
+        {
             Button::new(Text::new("Stop")).on_press(Message::StopSequence)
         } else {
             Button::new(Text::new("Play")).on_press(Message::PlaySequence)
         };
+        let selected_samples = self.selected_samples.read().unwrap();
+        let sequence_state = self.sequence_state.lock().unwrap();
+        let beat_pattern = sequence_state.beat_pattern.clone();
+        let time_signature = sequence_state.time_signature;
+        let ruler = create_bar_ruler(
+            sequence_state.sequence_length,
+            sequence_state.drum_scale.step_ticks(),
+            time_signature.bar_ticks(),
+        );
+        let drum_mix: Vec<TrackMix> = (0..beat_pattern.len())
+            .map(|index| sequence_state.drum_mix(index))
+            .collect();
+        let drum_timing: Vec<(TrackTiming, f64)> = (0..beat_pattern.len())
+            .map(|index| {
+                (
+                    sequence_state.drum_timing(index),
+                    sequence_state.drum_row_step_ticks(index),
+                )
+            })
+            .collect();
+        let drums_groove = sequence_state.track_groove(TransportTrack::Drums).clone();
+        let row_grooves: Vec<Option<Groove>> = (0..beat_pattern.len())
+            .map(|index| {
+                sequence_state
+                    .drum_row_grooves
+                    .get(index)
+                    .cloned()
+                    .flatten()
+            })
+            .collect();
+        let groove_controls = create_track_groove_controls(
+            sequence_state.drum_groove.as_ref(),
+            &sequence_state.groove,
+            &self.groove_templates,
+            Message::UpdateGroove,
+            Message::EditFinished,
+        );
+        drop(sequence_state);
+
         let mut column = Column::new().push(
             Row::new()
                 .spacing(10)
                 .push(record_button)
                 .push(Text::new("Scale:").size(20))
                 .push(sequence_length_pick_list)
-                .push(play_button),
+                .push(play_button)
+                .push(
+                    checkbox("Edit ratchets", self.edit_ratchets)
+                        .on_toggle(Message::ToggleEditRatchets),
+                )
+                .push(groove_controls),
         );
+        column = column.push(ruler);
 
-        let selected_samples = self.selected_samples.read().unwrap();
-        let sequence_state = self.sequence_state.lock().unwrap();
-        let beat_pattern = sequence_state.beat_pattern.clone();
-        let sequence_length = sequence_state.sequence_length;
-        drop(sequence_state);
+        let scale_options: Vec<TrackOption<SequenceScale>> = std::iter::once(TrackOption(None))
+            .chain(
+                self.sequence_scale_options
+                    .iter()
+                    .map(|scale| TrackOption(Some(*scale))),
+            )
+            .collect();
+        let now = self.engine.now();
+        let transport = self.transport.lock().unwrap();
+        let positions: Vec<Option<u64>> = drum_timing
+            .iter()
+            .map(|(_, step_ticks)| transport.step_at(TransportTrack::Drums, now, *step_ticks))
+            .collect();
+        drop(transport);
 
         for (file_index, (_, file_map)) in selected_samples.iter().enumerate() {
             if beat_pattern.is_empty() || file_index >= beat_pattern.len() {
@@ -55,15 +123,43 @@ impl DrumMachine {
             }
 
             let sample_name = file_map.keys().next().unwrap().clone();
-            let beat_row = (0..sequence_length).fold(Row::new(), |row, beat_index| {
-                let is_active = beat_pattern[file_index][beat_index as usize];
-                let checkbox = if beat_index == 0 || beat_index % 4 == 0 {
+            let (timing, step_ticks) = drum_timing[file_index];
+            let row_length = beat_pattern[file_index].len() as u32;
+            let euclid = self.euclid.filter(|euclid| euclid.row == file_index);
+            let preview = euclid.map(|euclid| euclid.preview(row_length));
+            let beat_row = (0..row_length).fold(Row::new(), |row, beat_index| {
+                let drum_step = beat_pattern[file_index][beat_index as usize];
+                if self.edit_ratchets {
+                    // Inactive steps have nothing to repeat and stay disabled.
+                    return row.push(
+                        button(Text::new(drum_step.ratchet.to_string()).size(10))
+                            .on_press_maybe(drum_step.active.then_some(Message::UpdateRatchet(
+                                file_index,
+                                beat_index as usize,
+                                drum_step.ratchet.next(),
+                            )))
+                            .padding(2)
+                            .width(Length::Fixed(STEP_WIDTH)),
+                    );
+                }
+                let is_active = match &preview {
+                    Some(preview) => preview[beat_index as usize],
+                    None => drum_step.active,
+                };
+                let tick = beat_index as f64 * step_ticks;
+                let checkbox = if time_signature.is_downbeat(tick) {
                     checkbox("", is_active)
                         .style(theme::Checkbox::Custom(Box::new(HighlightedCheckbox)))
+                } else if time_signature.is_pulse(tick) {
+                    checkbox("", is_active).style(theme::Checkbox::Custom(Box::new(BeatCheckbox)))
                 } else {
                     checkbox("", is_active)
                 };
 
+                // The steps of a previewed row are read only until it is applied.
+                if preview.is_some() {
+                    return row.push(checkbox);
+                }
                 row.push(checkbox.on_toggle(move |checked| {
                     Message::UpdateBeatPattern(file_index, beat_index as usize, checked)
                 }))
@@ -85,6 +181,92 @@ impl DrumMachine {
                 .height(Length::Fixed(30.0))
                 .center_y();
 
+            let show_velocity = self.velocity_lane == Some(file_index);
+            let velocity_button = button(Text::new("Vel").size(15))
+                .on_press(Message::ToggleVelocityLane(file_index))
+                .style(if show_velocity {
+                    theme::Button::Primary
+                } else {
+                    theme::Button::Text
+                });
+
+            let mix = drum_mix[file_index];
+            let toggle_style = |on: bool| {
+                if on {
+                    theme::Button::Primary
+                } else {
+                    theme::Button::Text
+                }
+            };
+            let mix_controls = Row::new()
+                .spacing(2)
+                .align_items(alignment::Alignment::Center)
+                .push(
+                    button(Text::new("M").size(15))
+                        .on_press(Message::UpdateTrackMix(
+                            file_index,
+                            TrackMix {
+                                mute: !mix.mute,
+                                ..mix
+                            },
+                        ))
+                        .style(toggle_style(mix.mute)),
+                )
+                .push(
+                    button(Text::new("S").size(15))
+                        .on_press(Message::UpdateTrackMix(
+                            file_index,
+                            TrackMix {
+                                solo: !mix.solo,
+                                ..mix
+                            },
+                        ))
+                        .style(toggle_style(mix.solo)),
+                )
+                .push(
+                    slider(MIN_GAIN_DB..=MAX_GAIN_DB, mix.gain_db, move |gain_db| {
+                        Message::UpdateTrackMix(file_index, TrackMix { gain_db, ..mix })
+                    })
+                    .step(0.5)
+                    .on_release(Message::EditFinished)
+                    .width(Length::Fixed(70.0)),
+                )
+                .push(
+                    Text::new(format!("{:+.1} dB", mix.gain_db))
+                        .size(12)
+                        .width(Length::Fixed(55.0)),
+                )
+                .push(
+                    slider(-1.0..=1.0, mix.pan, move |pan| {
+                        Message::UpdateTrackMix(file_index, TrackMix { pan, ..mix })
+                    })
+                    .step(0.05)
+                    .on_release(Message::EditFinished)
+                    .width(Length::Fixed(50.0)),
+                )
+                .push(
+                    Text::new(pan_label(mix.pan))
+                        .size(12)
+                        .width(Length::Fixed(35.0)),
+                );
+
+            let show_groove = self.groove_row == Some(file_index);
+            let groove_button = button(Text::new("Grv").size(15))
+                .on_press(Message::ToggleGrooveRow(file_index))
+                .style(if show_groove || row_grooves[file_index].is_some() {
+                    theme::Button::Primary
+                } else {
+                    theme::Button::Text
+                });
+
+            let euclid_button = button(Text::new("Euc").size(15))
+                .on_press(Message::ToggleEuclid(file_index))
+                .style(if euclid.is_some() {
+                    theme::Button::Primary
+                } else {
+                    theme::Button::Text
+                });
+
             column = column.push(
                 Row::new()
                     .push(
@@ -94,15 +276,142 @@ impl DrumMachine {
                             .height(Length::Fixed(20.0)),
                     )
                     .push(beat_row)
+                    .push(
+                        pick_list(length_options(), Some(TrackOption(timing.length)), {
+                            move |length: TrackOption<u32>| {
+                                Message::UpdateTrackTiming(
+                                    file_index,
+                                    TrackTiming {
+                                        length: length.0,
+                                        ..timing
+                                    },
+                                )
+                            }
+                        })
+                        .text_size(12)
+                        .width(Length::Fixed(80.0)),
+                    )
+                    .push(
+                        pick_list(scale_options.clone(), Some(TrackOption(timing.scale)), {
+                            move |scale: TrackOption<SequenceScale>| {
+                                Message::UpdateTrackTiming(
+                                    file_index,
+                                    TrackTiming {
+                                        scale: scale.0,
+                                        ..timing
+                                    },
+                                )
+                            }
+                        })
+                        .text_size(12)
+                        .width(Length::Fixed(80.0)),
+                    )
+                    .push(
+                        Text::new(match positions[file_index] {
+                            Some(step) => {
+                                format!("{}/{}", step % row_length.max(1) as u64 + 1, row_length)
+                            }
+                            None => format!("{} steps", row_length),
+                        })
+                        .size(12)
+                        .width(Length::Fixed(60.0)),
+                    )
+                    .push(mix_controls)
+                    .push(velocity_button)
+                    .push(groove_button)
+                    .push(euclid_button)
                     .push(remove_button_container)
                     .align_items(alignment::Alignment::Center),
             );
+            if let Some(euclid) = euclid {
+                column = column.push(create_euclid_controls(euclid, row_length));
+            }
+            if show_groove {
+                column = column.push(
+                    Row::new()
+                        .push(Space::with_width(Length::Fixed(150.0)))
+                        .push(create_track_groove_controls(
+                            row_grooves[file_index].as_ref(),
+                            &drums_groove,
+                            &self.groove_templates,
+                            move |groove| Message::UpdateRowGroove(file_index, groove),
+                            Message::EditFinished,
+                        )),
+                );
+            }
+            if show_velocity {
+                column = column.push(self.create_velocity_lane(
+                    file_index,
+                    &beat_pattern[file_index],
+                    row_length,
+                ));
+                column = column.push(create_trigger_lane(
+                    &beat_pattern[file_index],
+                    row_length,
+                    150.0,
+                    move |beat_index, trigger| {
+                        Message::UpdateTrigger(file_index, beat_index, trigger)
+                    },
+                    Message::EditFinished,
+                ));
+            }
         }
 
         column
     }
 }
 
+fn create_euclid_controls(euclid: EuclidEditor, row_length: u32) -> Row<'static, Message> {
+    let max_rotation = row_length.saturating_sub(1);
+    Row::new()
+        .spacing(10)
+        .align_items(alignment::Alignment::Center)
+        .push(Space::with_width(Length::Fixed(150.0)))
+        .push(Text::new(format!("Hits: {}/{}", euclid.hits, row_length)).size(12))
+        .push(
+            slider(0..=row_length, euclid.hits, move |hits| {
+                Message::UpdateEuclid(EuclidEditor { hits, ..euclid })
+            })
+            .width(Length::Fixed(160.0)),
+        )
+        .push(Text::new(format!("Rotate: {}", euclid.rotation)).size(12))
+        .push(
+            slider(
+                0..=max_rotation,
+                euclid.rotation.min(max_rotation),
+                move |rotation| Message::UpdateEuclid(EuclidEditor { rotation, ..euclid }),
+            )
+            .width(Length::Fixed(160.0)),
+        )
+        .push(button(Text::new("Apply").size(12)).on_press(Message::ApplyEuclid))
+        .push(
+            button(Text::new("Cancel").size(12))
+                .on_press(Message::ToggleEuclid(euclid.row))
+                .style(theme::Button::Secondary),
+        )
+}
+
+/// Numbers the bars above the grid, each label sits over the step the bar starts in.
+fn create_bar_ruler(
+    sequence_length: u32,
+    step_ticks: f64,
+    bar_ticks: f64,
+) -> Row<'static, Message> {
+    (0..sequence_length).fold(
+        Row::new().push(Space::with_width(Length::Fixed(150.0))),
+        |row, step| {
+            let start = step as f64 * step_ticks;
+            let bar = (start / bar_ticks).ceil();
+            let label = if bar * bar_ticks < start + step_ticks {
+                format!("{}", bar as u32 + 1)
+            } else {
+                String::new()
+            };
+            row.push(Text::new(label).size(12).width(Length::Fixed(STEP_WIDTH)))
+        },
+    )
+}
+
 struct HighlightedCheckbox;
 
 impl checkbox::StyleSheet for HighlightedCheckbox {
@@ -135,3 +444,26 @@ impl checkbox::StyleSheet for HighlightedCheckbox {
         appearance
     }
 }
+
+/// Marks the beats inside a bar, quieter than the downbeat.
+struct BeatCheckbox;
+
+impl checkbox::StyleSheet for BeatCheckbox {
+    type Style = Theme;
+
+    fn active(&self, style: &Self::Style, is_checked: bool) -> checkbox::Appearance {
+        let mut appearance = style.active(&theme::Checkbox::default(), is_checked);
+        if !is_checked {
+            appearance.background = Background::Color(Color::from_rgb(0.35, 0.2, 0.3));
+        }
+        appearance
+    }
+
+    fn hovered(&self, style: &Self::Style, is_checked: bool) -> checkbox::Appearance {
+        let mut appearance = style.hovered(&theme::Checkbox::default(), is_checked);
+        if !is_checked {
+            appearance.background = Background::Color(Color::from_rgb(0.45, 0.25, 0.35));
+        }
+        appearance
+    }
+}
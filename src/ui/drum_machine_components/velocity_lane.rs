@@ -0,0 +1,39 @@
+use iced::{
+    widget::{container, vertical_slider, Row, Space},
+    Length,
+};
+
+use crate::ui::{
+    drum_machine::{DrumMachine, Message},
+    drum_step::{DrumStep, MAX_VELOCITY},
+    trigger_lane::STEP_WIDTH,
+};
+
+impl DrumMachine {
+    pub fn create_velocity_lane(
+        &self,
+        file_index: usize,
+        steps: &[DrumStep],
+        sequence_length: u32,
+    ) -> Row<'_, Message> {
+        let lane = Row::new().push(Space::with_width(Length::Fixed(150.0)));
+
+        steps
+            .iter()
+            .take(sequence_length as usize)
+            .enumerate()
+            .fold(lane, |lane, (beat_index, step)| {
+                let slider = vertical_slider(0..=MAX_VELOCITY, step.velocity, move |velocity| {
+                    Message::UpdateVelocity(file_index, beat_index, velocity)
+                })
+                .on_release(Message::EditFinished)
+                .height(Length::Fixed(60.0));
+
+                lane.push(
+                    container(slider)
+                        .width(Length::Fixed(STEP_WIDTH))
+                        .center_x(),
+                )
+            })
+    }
+}
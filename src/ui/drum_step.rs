@@ -0,0 +1,178 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::step_trigger::{Step, StepTrigger};
+
+pub const MAX_VELOCITY: u8 = 127;
+/// Full velocity, so a step plays at the level every hit had before velocities existed.
+pub const DEFAULT_VELOCITY: u8 = MAX_VELOCITY;
+pub const MAX_RATCHET: u8 = 4;
+/// The furthest a step can be played early or late, in ticks.
+pub const MAX_NUDGE: i8 = 6;
+
+/// How the velocity of a ratchet's repeats changes over the step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VelocityRamp {
+    #[default]
+    Flat,
+    Up,
+    Down,
+}
+
+/// Evenly spaced repeats of a drum step within its step duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ratchet {
+    /// 1 plays the step once, up to `MAX_RATCHET`.
+    pub count: u8,
+    pub ramp: VelocityRamp,
+}
+
+impl Default for Ratchet {
+    fn default() -> Self {
+        Ratchet {
+            count: 1,
+            ramp: VelocityRamp::Flat,
+        }
+    }
+}
+
+impl fmt::Display for Ratchet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ramp {
+            VelocityRamp::Flat => write!(f, "{}x", self.count),
+            VelocityRamp::Up => write!(f, "{}x↑", self.count),
+            VelocityRamp::Down => write!(f, "{}x↓", self.count),
+        }
+    }
+}
+
+impl Ratchet {
+    /// Steps through the flat counts, then the rising and the falling ramps.
+    pub fn next(self) -> Ratchet {
+        let ramp = match (self.count >= MAX_RATCHET, self.ramp) {
+            (false, ramp) => {
+                return Ratchet {
+                    count: self.count.max(1) + 1,
+                    ramp,
+                }
+            }
+            (true, VelocityRamp::Flat) => VelocityRamp::Up,
+            (true, VelocityRamp::Up) => VelocityRamp::Down,
+            (true, VelocityRamp::Down) => return Ratchet::default(),
+        };
+        Ratchet { count: 2, ramp }
+    }
+
+    /// The share of the step's velocity the repeat numbered `repeat` plays with.
+    pub fn level(&self, repeat: u8) -> f32 {
+        let count = self.count.max(1) as f32;
+        match self.ramp {
+            VelocityRamp::Flat => 1.0,
+            VelocityRamp::Up => (repeat as f32 + 1.0) / count,
+            VelocityRamp::Down => (count - repeat as f32) / count,
+        }
+    }
+}
+
+/// One step of a drum row.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredDrumStep")]
+pub struct DrumStep {
+    pub active: bool,
+    /// 0 to `MAX_VELOCITY`, scales the sample gain.
+    pub velocity: u8,
+    pub trigger: StepTrigger,
+    pub ratchet: Ratchet,
+    /// Plays the step up to `MAX_NUDGE` ticks early or late.
+    pub nudge: i8,
+}
+
+impl Default for DrumStep {
+    fn default() -> Self {
+        DrumStep {
+            active: false,
+            velocity: DEFAULT_VELOCITY,
+            trigger: StepTrigger::default(),
+            ratchet: Ratchet::default(),
+            nudge: 0,
+        }
+    }
+}
+
+impl Step for DrumStep {
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    fn trigger(&self) -> StepTrigger {
+        self.trigger
+    }
+
+    fn nudge(&self) -> i8 {
+        self.nudge
+    }
+
+    fn set_nudge(&mut self, nudge: i8) {
+        self.nudge = nudge.clamp(-MAX_NUDGE, MAX_NUDGE);
+    }
+}
+
+impl DrumStep {
+    pub fn gain(&self) -> f32 {
+        self.velocity.min(MAX_VELOCITY) as f32 / MAX_VELOCITY as f32
+    }
+}
+
+// Projects saved before velocities existed store every step as a plain bool.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredDrumStep {
+    Legacy(bool),
+    Step {
+        active: bool,
+        #[serde(default = "default_velocity")]
+        velocity: u8,
+        #[serde(default)]
+        trigger: StepTrigger,
+        #[serde(default)]
+        ratchet: Ratchet,
+        #[serde(default)]
+        nudge: i8,
+    },
+}
+
+fn default_velocity() -> u8 {
+    DEFAULT_VELOCITY
+}
+
+impl From<StoredDrumStep> for DrumStep {
+    fn from(step: StoredDrumStep) -> Self {
+        match step {
+            StoredDrumStep::Legacy(active) => DrumStep {
+                active,
+                ..DrumStep::default()
+            },
+            StoredDrumStep::Step {
+                active,
+                velocity,
+                trigger,
+                ratchet,
+                nudge,
+            } => DrumStep {
+                active,
+                velocity: velocity.min(MAX_VELOCITY),
+                trigger,
+                ratchet: Ratchet {
+                    count: ratchet.count.clamp(1, MAX_RATCHET),
+                    ..ratchet
+                },
+                nudge: nudge.clamp(-MAX_NUDGE, MAX_NUDGE),
+            },
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use iced::{
+    widget::{checkbox, pick_list, slider, Row, Text},
+    Length,
+};
+
+use crate::scripts::groove::{Groove, GrooveTemplate, SwingResolution, MAX_SWING, MIN_SWING};
+
+/// Swing amount, swing resolution and template pickers editing one groove.
+/// `on_release` is sent when the swing slider is let go.
+pub fn create_groove_controls<'a, Message: Clone + 'a>(
+    groove: &Groove,
+    groove_templates: &[GrooveTemplate],
+    on_change: impl Fn(Groove) -> Message + Clone + 'a,
+    on_release: Message,
+) -> Row<'a, Message> {
+    let swing_groove = groove.clone();
+    let on_swing = on_change.clone();
+    let resolution_groove = groove.clone();
+    let on_resolution = on_change.clone();
+    let template_groove = groove.clone();
+
+    Row::new()
+        .push(Text::new(format!("Swing: {}%", groove.swing)))
+        .push(
+            slider(MIN_SWING..=MAX_SWING, groove.swing, move |swing| {
+                on_swing(Groove {
+                    swing,
+                    ..swing_groove.clone()
+                })
+            })
+            .on_release(on_release)
+            .width(Length::Fixed(120.0)),
+        )
+        .push(pick_list(
+            [SwingResolution::Eighth, SwingResolution::Sixteenth],
+            Some(groove.resolution),
+            move |resolution| {
+                on_resolution(Groove {
+                    resolution,
+                    ..resolution_groove.clone()
+                })
+            },
+        ))
+        .push(Text::new("Groove:"))
+        .push(pick_list(
+            groove_templates.to_vec(),
+            Some(groove.template.clone()),
+            move |template| {
+                on_change(Groove {
+                    template,
+                    ..template_groove.clone()
+                })
+            },
+        ))
+        .spacing(10)
+}
+
+/// A toggle between following the global groove and a track groove of its own,
+/// with the controls of the track groove when it has one.
+pub fn create_track_groove_controls<'a, Message: Clone + 'a>(
+    track_groove: Option<&Groove>,
+    global_groove: &Groove,
+    groove_templates: &[GrooveTemplate],
+    on_change: impl Fn(Option<Groove>) -> Message + Clone + 'a,
+    on_release: Message,
+) -> Row<'a, Message> {
+    let global_groove = global_groove.clone();
+    let on_toggle = on_change.clone();
+    let row = Row::new()
+        .push(
+            checkbox("Own groove", track_groove.is_some())
+                .on_toggle(move |own| on_toggle(own.then(|| global_groove.clone()))),
+        )
+        .spacing(10);
+
+    match track_groove {
+        Some(groove) => row.push(create_groove_controls(
+            groove,
+            groove_templates,
+            move |groove| on_change(Some(groove)),
+            on_release,
+        )),
+        None => row,
+    }
+}
@@ -0,0 +1,776 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::scripts::{
+    groove::Groove,
+    synth::{
+        envelope::Envelope, filter::Filter, lfo::Modulation, oscillator::Oscillator,
+        patch::SynthPatch,
+    },
+    transport::{TimeSignature, TransportTrack},
+};
+
+use super::{
+    arranger_page::Arrangement,
+    drum_machine::{SampleFolder, SequenceScale},
+    drum_step::{DrumStep, Ratchet},
+    note_step::NoteStep,
+    pattern_bank::Pattern,
+    step_trigger::StepTrigger,
+    track_mix::{MixerState, TrackMix},
+    track_timing::TrackTiming,
+    SequenceState,
+};
+
+// Keeps memory bounded during long sessions.
+const MAX_HISTORY: usize = 500;
+
+/// A drum row together with the sample that plays it, enough to put it back in place.
+#[derive(Debug, Clone)]
+pub struct SampleRow {
+    pub index: usize,
+    pub file_name: String,
+    pub sample_folder: SampleFolder,
+    /// The row of every pattern of the bank, in bank order.
+    pub rows: Vec<Vec<DrumStep>>,
+    pub timing: TrackTiming,
+    pub groove: Option<Groove>,
+    pub mix: TrackMix,
+}
+
+/// The pattern bank, arrangement, track timing and grooves, captured whole since arranger
+/// edits touch several of their parts at once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SongSnapshot {
+    patterns: Vec<Pattern>,
+    current_pattern: usize,
+    arrangement: Arrangement,
+    beat_pattern: Vec<Vec<DrumStep>>,
+    note_pattern: Vec<Vec<NoteStep>>,
+    drum_timing: Vec<TrackTiming>,
+    drum_row_grooves: Vec<Option<Groove>>,
+    synth_length: Option<u32>,
+    drum_scale: SequenceScale,
+    synth_scale: SequenceScale,
+    groove: Groove,
+    drum_groove: Option<Groove>,
+    synth_groove: Option<Groove>,
+}
+
+impl SongSnapshot {
+    pub fn capture(sequence_state: &SequenceState) -> Self {
+        let mut sequence_state = sequence_state.clone();
+        sequence_state.sync_current_pattern();
+        SongSnapshot {
+            patterns: sequence_state.patterns,
+            current_pattern: sequence_state.current_pattern,
+            arrangement: sequence_state.arrangement,
+            beat_pattern: sequence_state.beat_pattern,
+            note_pattern: sequence_state.note_pattern,
+            drum_timing: sequence_state.drum_timing,
+            drum_row_grooves: sequence_state.drum_row_grooves,
+            synth_length: sequence_state.synth_length,
+            drum_scale: sequence_state.drum_scale,
+            synth_scale: sequence_state.synth_scale,
+            groove: sequence_state.groove,
+            drum_groove: sequence_state.drum_groove,
+            synth_groove: sequence_state.synth_groove,
+        }
+    }
+
+    fn restore(&self, sequence_state: &mut SequenceState) {
+        sequence_state.patterns = self.patterns.clone();
+        sequence_state.current_pattern = self.current_pattern;
+        sequence_state.arrangement = self.arrangement.clone();
+        sequence_state.beat_pattern = self.beat_pattern.clone();
+        sequence_state.note_pattern = self.note_pattern.clone();
+        sequence_state.drum_timing = self.drum_timing.clone();
+        sequence_state.drum_row_grooves = self.drum_row_grooves.clone();
+        sequence_state.synth_length = self.synth_length;
+        sequence_state.drum_scale = self.drum_scale;
+        sequence_state.synth_scale = self.synth_scale;
+        sequence_state.groove = self.groove.clone();
+        sequence_state.drum_groove = self.drum_groove.clone();
+        sequence_state.synth_groove = self.synth_groove.clone();
+    }
+}
+
+/// Every strip of the mixer, captured whole since removing a bus reroutes the strips
+/// that fed it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixSnapshot {
+    drum_mix: Vec<TrackMix>,
+    mixer: MixerState,
+}
+
+impl MixSnapshot {
+    pub fn capture(sequence_state: &SequenceState) -> Self {
+        MixSnapshot {
+            drum_mix: sequence_state.drum_mix.clone(),
+            mixer: sequence_state.mixer.clone(),
+        }
+    }
+
+    fn restore(&self, sequence_state: &mut SequenceState) {
+        sequence_state.drum_mix = self.drum_mix.clone();
+        sequence_state.mixer = self.mixer.clone();
+    }
+}
+
+/// The synth's patch and the sound settings of the custom patch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundSnapshot {
+    patch: SynthPatch,
+    oscillator: Oscillator,
+    envelope: Envelope,
+    filter: Filter,
+    modulation: Modulation,
+}
+
+impl SoundSnapshot {
+    pub fn capture(sequence_state: &SequenceState) -> Self {
+        SoundSnapshot {
+            patch: sequence_state.patch,
+            oscillator: sequence_state.oscillator,
+            envelope: sequence_state.envelope,
+            filter: sequence_state.filter,
+            modulation: sequence_state.modulation.clone(),
+        }
+    }
+
+    fn restore(&self, sequence_state: &mut SequenceState) {
+        sequence_state.patch = self.patch;
+        sequence_state.oscillator = self.oscillator;
+        sequence_state.envelope = self.envelope;
+        sequence_state.filter = self.filter;
+        sequence_state.modulation = self.modulation.clone();
+    }
+
+    /// Whether `other` makes the same choices, differing in slider values at most.
+    pub fn same_choices(&self, other: &SoundSnapshot) -> bool {
+        let lfo_choices = |modulation: &Modulation| {
+            modulation
+                .lfos
+                .iter()
+                .map(|lfo| (lfo.shape, lfo.sync))
+                .collect::<Vec<_>>()
+        };
+        let route_choices = |modulation: &Modulation| {
+            modulation
+                .routes
+                .iter()
+                .map(|route| (route.lfo, route.target))
+                .collect::<Vec<_>>()
+        };
+        self.patch == other.patch
+            && self.oscillator.waveform == other.oscillator.waveform
+            && self.oscillator.band_limited == other.oscillator.band_limited
+            && self.filter.enabled == other.filter.enabled
+            && self.filter.mode == other.filter.mode
+            && lfo_choices(&self.modulation) == lfo_choices(&other.modulation)
+            && route_choices(&self.modulation) == route_choices(&other.modulation)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EditCommand {
+    SetBeat {
+        track: usize,
+        step: usize,
+        old: bool,
+        new: bool,
+    },
+    SetVelocity {
+        track: usize,
+        step: usize,
+        old: u8,
+        new: u8,
+    },
+    SetNote {
+        note: usize,
+        step: usize,
+        old: bool,
+        new: bool,
+    },
+    SetRatchet {
+        track: usize,
+        step: usize,
+        old: Ratchet,
+        new: Ratchet,
+    },
+    /// A probability or condition change of a drum step or synth note.
+    SetTrigger {
+        track: TransportTrack,
+        row: usize,
+        step: usize,
+        old: StepTrigger,
+        new: StepTrigger,
+    },
+    /// Replaces a whole drum row, like a generated rhythm does.
+    SetDrumRow {
+        track: usize,
+        old: Vec<DrumStep>,
+        new: Vec<DrumStep>,
+    },
+    SetTrackMix {
+        track: usize,
+        old: TrackMix,
+        new: TrackMix,
+    },
+    AddSample(SampleRow),
+    RemoveSample(SampleRow),
+    SetSequenceLength {
+        old: u32,
+        new: u32,
+        // Shrinking truncates the rows, so the full patterns are kept to restore them.
+        old_beat_pattern: Vec<Vec<DrumStep>>,
+        old_note_pattern: Vec<Vec<NoteStep>>,
+        old_patterns: Vec<Pattern>,
+    },
+    SetBpm {
+        old: u32,
+        new: u32,
+    },
+    SetTimeSignature {
+        old: TimeSignature,
+        new: TimeSignature,
+    },
+    SetSong {
+        old: Box<SongSnapshot>,
+        new: Box<SongSnapshot>,
+    },
+    SetMixer {
+        old: Box<MixSnapshot>,
+        new: Box<MixSnapshot>,
+    },
+    SetSynthSound {
+        old: Box<SoundSnapshot>,
+        new: Box<SoundSnapshot>,
+    },
+}
+
+impl EditCommand {
+    pub fn apply(
+        &self,
+        sequence_state: &mut SequenceState,
+        selected_samples: &mut BTreeMap<usize, HashMap<String, SampleFolder>>,
+    ) {
+        match self {
+            EditCommand::SetBeat {
+                track, step, new, ..
+            } => set_drum_step(sequence_state, *track, *step, |drum_step| {
+                drum_step.active = *new
+            }),
+            EditCommand::SetVelocity {
+                track, step, new, ..
+            } => set_drum_step(sequence_state, *track, *step, |drum_step| {
+                drum_step.velocity = *new
+            }),
+            EditCommand::SetRatchet {
+                track, step, new, ..
+            } => set_drum_step(sequence_state, *track, *step, |drum_step| {
+                drum_step.ratchet = *new
+            }),
+            EditCommand::SetDrumRow { track, new, .. } => set_drum_row(sequence_state, *track, new),
+            EditCommand::SetTrackMix { track, new, .. } => {
+                sequence_state.set_drum_mix(*track, *new)
+            }
+            EditCommand::SetNote {
+                note, step, new, ..
+            } => set_note_step(sequence_state, *note, *step, |note_step| {
+                note_step.active = *new
+            }),
+            EditCommand::SetTrigger {
+                track,
+                row,
+                step,
+                new,
+                ..
+            } => set_trigger(sequence_state, *track, *row, *step, *new),
+            EditCommand::AddSample(sample_row) => {
+                insert_sample(sequence_state, selected_samples, sample_row)
+            }
+            EditCommand::RemoveSample(sample_row) => {
+                remove_sample(sequence_state, selected_samples, sample_row.index)
+            }
+            EditCommand::SetSequenceLength { new, .. } => {
+                resize_patterns(sequence_state, *new);
+            }
+            EditCommand::SetBpm { new, .. } => sequence_state.bpm = *new,
+            EditCommand::SetTimeSignature { new, .. } => sequence_state.time_signature = *new,
+            EditCommand::SetSong { new, .. } => new.restore(sequence_state),
+            EditCommand::SetMixer { new, .. } => new.restore(sequence_state),
+            EditCommand::SetSynthSound { new, .. } => new.restore(sequence_state),
+        }
+    }
+
+    pub fn revert(
+        &self,
+        sequence_state: &mut SequenceState,
+        selected_samples: &mut BTreeMap<usize, HashMap<String, SampleFolder>>,
+    ) {
+        match self {
+            EditCommand::SetBeat {
+                track, step, old, ..
+            } => set_drum_step(sequence_state, *track, *step, |drum_step| {
+                drum_step.active = *old
+            }),
+            EditCommand::SetVelocity {
+                track, step, old, ..
+            } => set_drum_step(sequence_state, *track, *step, |drum_step| {
+                drum_step.velocity = *old
+            }),
+            EditCommand::SetRatchet {
+                track, step, old, ..
+            } => set_drum_step(sequence_state, *track, *step, |drum_step| {
+                drum_step.ratchet = *old
+            }),
+            EditCommand::SetDrumRow { track, old, .. } => set_drum_row(sequence_state, *track, old),
+            EditCommand::SetTrackMix { track, old, .. } => {
+                sequence_state.set_drum_mix(*track, *old)
+            }
+            EditCommand::SetNote {
+                note, step, old, ..
+            } => set_note_step(sequence_state, *note, *step, |note_step| {
+                note_step.active = *old
+            }),
+            EditCommand::SetTrigger {
+                track,
+                row,
+                step,
+                old,
+                ..
+            } => set_trigger(sequence_state, *track, *row, *step, *old),
+            EditCommand::AddSample(sample_row) => {
+                remove_sample(sequence_state, selected_samples, sample_row.index)
+            }
+            EditCommand::RemoveSample(sample_row) => {
+                insert_sample(sequence_state, selected_samples, sample_row)
+            }
+            EditCommand::SetSequenceLength {
+                old,
+                old_beat_pattern,
+                old_note_pattern,
+                old_patterns,
+                ..
+            } => {
+                sequence_state.sequence_length = *old;
+                sequence_state.beat_pattern = old_beat_pattern.clone();
+                sequence_state.note_pattern = old_note_pattern.clone();
+                sequence_state.patterns = old_patterns.clone();
+            }
+            EditCommand::SetBpm { old, .. } => sequence_state.bpm = *old,
+            EditCommand::SetTimeSignature { old, .. } => sequence_state.time_signature = *old,
+            EditCommand::SetSong { old, .. } => old.restore(sequence_state),
+            EditCommand::SetMixer { old, .. } => old.restore(sequence_state),
+            EditCommand::SetSynthSound { old, .. } => old.restore(sequence_state),
+        }
+    }
+
+    /// Folds a follow-up edit into this one, used while a slider is being dragged.
+    fn merge(&mut self, next: &EditCommand) -> bool {
+        match (self, next) {
+            (
+                EditCommand::SetVelocity {
+                    track, step, new, ..
+                },
+                EditCommand::SetVelocity {
+                    track: next_track,
+                    step: next_step,
+                    new: next_new,
+                    ..
+                },
+            ) if track == next_track && step == next_step => {
+                *new = *next_new;
+                true
+            }
+            (
+                EditCommand::SetTrigger {
+                    track,
+                    row,
+                    step,
+                    new,
+                    ..
+                },
+                EditCommand::SetTrigger {
+                    track: next_track,
+                    row: next_row,
+                    step: next_step,
+                    new: next_new,
+                    ..
+                },
+            ) if track == next_track && row == next_row && step == next_step => {
+                *new = *next_new;
+                true
+            }
+            (
+                EditCommand::SetTrackMix { track, new, .. },
+                EditCommand::SetTrackMix {
+                    track: next_track,
+                    new: next_new,
+                    ..
+                },
+            ) if track == next_track => {
+                *new = *next_new;
+                true
+            }
+            (EditCommand::SetBpm { new, .. }, EditCommand::SetBpm { new: next_new, .. }) => {
+                *new = *next_new;
+                true
+            }
+            (
+                EditCommand::SetSequenceLength { new, .. },
+                EditCommand::SetSequenceLength { new: next_new, .. },
+            ) => {
+                *new = *next_new;
+                true
+            }
+            (EditCommand::SetSong { new, .. }, EditCommand::SetSong { new: next_new, .. }) => {
+                *new = next_new.clone();
+                true
+            }
+            (EditCommand::SetMixer { new, .. }, EditCommand::SetMixer { new: next_new, .. }) => {
+                *new = next_new.clone();
+                true
+            }
+            (
+                EditCommand::SetSynthSound { new, .. },
+                EditCommand::SetSynthSound { new: next_new, .. },
+            ) => {
+                *new = next_new.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+pub fn resize_patterns(sequence_state: &mut SequenceState, sequence_length: u32) {
+    sequence_state.sequence_length = sequence_length;
+    sequence_state.resize_rows();
+}
+
+fn set_drum_step(
+    sequence_state: &mut SequenceState,
+    track: usize,
+    step: usize,
+    update: impl FnOnce(&mut DrumStep),
+) {
+    if let Some(drum_step) = sequence_state
+        .beat_pattern
+        .get_mut(track)
+        .and_then(|row| row.get_mut(step))
+    {
+        update(drum_step);
+    }
+}
+
+fn set_drum_row(sequence_state: &mut SequenceState, track: usize, steps: &[DrumStep]) {
+    if let Some(row) = sequence_state.beat_pattern.get_mut(track) {
+        *row = steps.to_vec();
+    }
+}
+
+fn set_note_step(
+    sequence_state: &mut SequenceState,
+    note: usize,
+    step: usize,
+    update: impl FnOnce(&mut NoteStep),
+) {
+    if let Some(note_step) = sequence_state
+        .note_pattern
+        .get_mut(note)
+        .and_then(|row| row.get_mut(step))
+    {
+        update(note_step);
+    }
+}
+
+fn set_trigger(
+    sequence_state: &mut SequenceState,
+    track: TransportTrack,
+    row: usize,
+    step: usize,
+    trigger: StepTrigger,
+) {
+    match track {
+        TransportTrack::Drums => set_drum_step(sequence_state, row, step, |drum_step| {
+            drum_step.trigger = trigger
+        }),
+        TransportTrack::Synth => set_note_step(sequence_state, row, step, |note_step| {
+            note_step.trigger = trigger
+        }),
+    }
+}
+
+fn insert_sample(
+    sequence_state: &mut SequenceState,
+    selected_samples: &mut BTreeMap<usize, HashMap<String, SampleFolder>>,
+    sample_row: &SampleRow,
+) {
+    let mut samples: Vec<_> = selected_samples.values().cloned().collect();
+    let index = sample_row.index.min(samples.len());
+    samples.insert(
+        index,
+        HashMap::from([(
+            sample_row.file_name.clone(),
+            sample_row.sample_folder.clone(),
+        )]),
+    );
+    *selected_samples = samples.into_iter().enumerate().collect();
+
+    sequence_state.insert_drum_row(
+        index,
+        &sample_row.rows,
+        sample_row.timing,
+        sample_row.groove.clone(),
+        sample_row.mix,
+    );
+}
+
+fn remove_sample(
+    sequence_state: &mut SequenceState,
+    selected_samples: &mut BTreeMap<usize, HashMap<String, SampleFolder>>,
+    index: usize,
+) {
+    if selected_samples.remove(&index).is_some() {
+        *selected_samples = selected_samples.values().cloned().enumerate().collect();
+    }
+    sequence_state.remove_drum_row(index);
+}
+
+struct HistoryEntry {
+    command: EditCommand,
+    // An open entry keeps absorbing edits of the same kind until it is closed.
+    open: bool,
+}
+
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<EditCommand>,
+}
+
+impl History {
+    /// Records an edit that has already been applied. With `coalesce` set, consecutive
+    /// edits of the same kind end up in one entry until `close` is called.
+    pub fn record(&mut self, command: EditCommand, coalesce: bool) {
+        self.redo_stack.clear();
+        if let Some(entry) = self.undo_stack.last_mut() {
+            if entry.open && coalesce && entry.command.merge(&command) {
+                return;
+            }
+            entry.open = false;
+        }
+        self.undo_stack.push(HistoryEntry {
+            command,
+            open: coalesce,
+        });
+        if self.undo_stack.len() > MAX_HISTORY {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    pub fn close(&mut self) {
+        if let Some(entry) = self.undo_stack.last_mut() {
+            entry.open = false;
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<EditCommand> {
+        let command = self.undo_stack.pop()?.command;
+        self.redo_stack.push(command.clone());
+        Some(command)
+    }
+
+    pub fn redo(&mut self) -> Option<EditCommand> {
+        let command = self.redo_stack.pop()?;
+        self.undo_stack.push(HistoryEntry {
+            command: command.clone(),
+            open: false,
+        });
+        Some(command)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Session {
+        history: History,
+        sequence_state: SequenceState,
+        selected_samples: BTreeMap<usize, HashMap<String, SampleFolder>>,
+    }
+
+    impl Session {
+        fn new() -> Self {
+            let mut sequence_state = SequenceState {
+                beat_pattern: vec![vec![DrumStep::default(); 16]; 2],
+                ..SequenceState::default()
+            };
+            sequence_state.ensure_pattern_bank();
+            Session {
+                history: History::default(),
+                sequence_state,
+                selected_samples: BTreeMap::new(),
+            }
+        }
+
+        fn set_velocity(&mut self, step: usize, velocity: u8, coalesce: bool) {
+            let drum_step = &mut self.sequence_state.beat_pattern[0][step];
+            let old = std::mem::replace(&mut drum_step.velocity, velocity);
+            self.history.record(
+                EditCommand::SetVelocity {
+                    track: 0,
+                    step,
+                    old,
+                    new: velocity,
+                },
+                coalesce,
+            );
+        }
+
+        fn velocity(&self, step: usize) -> u8 {
+            self.sequence_state.beat_pattern[0][step].velocity
+        }
+
+        fn undo(&mut self) -> bool {
+            let Some(command) = self.history.undo() else {
+                return false;
+            };
+            command.revert(&mut self.sequence_state, &mut self.selected_samples);
+            true
+        }
+
+        fn redo(&mut self) -> bool {
+            let Some(command) = self.history.redo() else {
+                return false;
+            };
+            command.apply(&mut self.sequence_state, &mut self.selected_samples);
+            true
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_step_through_edits() {
+        let mut session = Session::new();
+        let original = session.velocity(0);
+        session.set_velocity(0, 10, false);
+        session.set_velocity(0, 20, false);
+        assert!(session.history.can_undo());
+        assert!(!session.history.can_redo());
+
+        assert!(session.undo());
+        assert_eq!(session.velocity(0), 10);
+        assert!(session.undo());
+        assert_eq!(session.velocity(0), original);
+        assert!(!session.undo());
+
+        assert!(session.redo());
+        assert_eq!(session.velocity(0), 10);
+
+        // A new edit drops what could have been redone.
+        session.set_velocity(1, 30, false);
+        assert!(!session.history.can_redo());
+        assert!(!session.redo());
+        assert!(session.undo());
+        assert!(session.undo());
+        assert_eq!(session.velocity(0), original);
+    }
+
+    #[test]
+    fn slider_drags_share_one_entry_until_closed() {
+        let mut session = Session::new();
+        let original = session.velocity(0);
+        for velocity in [40, 50, 60] {
+            session.set_velocity(0, velocity, true);
+        }
+        session.history.close();
+        session.set_velocity(0, 70, true);
+        // Another step does not merge, even while the entry is open.
+        session.set_velocity(1, 80, true);
+
+        assert!(session.undo());
+        assert_eq!(session.velocity(1), DrumStep::default().velocity);
+        assert!(session.undo());
+        assert_eq!(session.velocity(0), 60);
+        assert!(session.undo());
+        assert_eq!(session.velocity(0), original);
+        assert!(!session.undo());
+    }
+
+    #[test]
+    fn edits_that_do_not_coalesce_stay_separate() {
+        let mut session = Session::new();
+        session.set_velocity(0, 40, false);
+        session.set_velocity(0, 50, true);
+        session.set_velocity(0, 60, false);
+        assert!(session.undo());
+        assert_eq!(session.velocity(0), 50);
+        assert!(session.undo());
+        assert_eq!(session.velocity(0), 40);
+    }
+
+    #[test]
+    fn the_oldest_entries_are_dropped_past_the_limit() {
+        let mut session = Session::new();
+        let extra = 10;
+        for bpm in 0..(MAX_HISTORY + extra) as u32 {
+            let old = std::mem::replace(&mut session.sequence_state.bpm, bpm + 1);
+            session
+                .history
+                .record(EditCommand::SetBpm { old, new: bpm + 1 }, false);
+        }
+        let mut undone = 0;
+        while session.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_HISTORY);
+        assert_eq!(session.sequence_state.bpm, extra as u32);
+    }
+
+    #[test]
+    fn undoing_a_shorter_sequence_restores_the_cut_steps() {
+        let mut session = Session::new();
+        session.sequence_state.beat_pattern[1][12].active = true;
+        session.sequence_state.note_pattern[3][14].active = true;
+        session.sequence_state.sync_current_pattern();
+
+        let sequence_state = &mut session.sequence_state;
+        let command = EditCommand::SetSequenceLength {
+            old: sequence_state.sequence_length,
+            new: 8,
+            old_beat_pattern: sequence_state.beat_pattern.clone(),
+            old_note_pattern: sequence_state.note_pattern.clone(),
+            old_patterns: sequence_state.patterns.clone(),
+        };
+        resize_patterns(sequence_state, 8);
+        session.history.record(command, true);
+        assert_eq!(session.sequence_state.beat_pattern[1].len(), 8);
+        assert_eq!(session.sequence_state.patterns[0].beat_pattern[1].len(), 8);
+
+        assert!(session.undo());
+        let sequence_state = &session.sequence_state;
+        assert_eq!(sequence_state.sequence_length, 16);
+        assert!(sequence_state.beat_pattern[1][12].active);
+        assert!(sequence_state.note_pattern[3][14].active);
+        assert!(sequence_state.patterns[0].beat_pattern[1][12].active);
+
+        assert!(session.redo());
+        assert_eq!(session.sequence_state.sequence_length, 8);
+        assert_eq!(session.sequence_state.beat_pattern[1].len(), 8);
+    }
+}
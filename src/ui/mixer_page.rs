@@ -0,0 +1,348 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use iced::{
+    theme,
+    widget::{
+        button, container, pick_list, scrollable, slider, vertical_slider, Column, Row, Space, Text,
+    },
+    Alignment, Background, Color, Element, Length, Theme,
+};
+
+use crate::scripts::audio_engine::mixer::{MeterBank, MeterReading, MAX_BUSES};
+
+use super::{
+    drum_machine::SampleFolder,
+    track_mix::{pan_label, TrackMix, MAX_GAIN_DB, MIN_GAIN_DB},
+    SequenceState,
+};
+
+const STRIP_WIDTH: f32 = 100.0;
+const FADER_HEIGHT: f32 = 180.0;
+
+/// A strip of the mixer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strip {
+    Drum(usize),
+    Synth,
+    Bus(usize),
+    Master,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SetMute(Strip, bool),
+    SetSolo(Strip, bool),
+    SetGain(Strip, f32),
+    SetPan(Strip, f32),
+    SetRoute(Strip, RouteOption),
+    AddBus,
+    RemoveBus(usize),
+    /// Sent when a fader or pan slider is released.
+    EditFinished,
+}
+
+impl Message {
+    /// Whether the message is one step of a slider drag, recorded as one undo entry.
+    pub fn is_continuous(&self) -> bool {
+        matches!(self, Message::SetGain(..) | Message::SetPan(..))
+    }
+}
+
+/// Pick list entry for where a strip goes, `None` is the master.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteOption {
+    pub bus: Option<usize>,
+    pub name: String,
+}
+
+impl fmt::Display for RouteOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct MixerPage {
+    sequence_state: Arc<Mutex<SequenceState>>,
+    selected_samples: Arc<RwLock<BTreeMap<usize, HashMap<String, SampleFolder>>>>,
+    meters: Arc<MeterBank>,
+}
+
+impl MixerPage {
+    pub fn new(
+        sequence_state: Arc<Mutex<SequenceState>>,
+        selected_samples: Arc<RwLock<BTreeMap<usize, HashMap<String, SampleFolder>>>>,
+        meters: Arc<MeterBank>,
+    ) -> Self {
+        MixerPage {
+            sequence_state,
+            selected_samples,
+            meters,
+        }
+    }
+
+    pub fn update(&mut self, message: Message) {
+        let mut sequence_state = self.sequence_state.lock().unwrap();
+        match message {
+            Message::SetGain(Strip::Master, gain_db) => {
+                sequence_state.mixer.master_gain_db = gain_db;
+            }
+            Message::SetMute(strip, mute) => {
+                update_strip(&mut sequence_state, strip, |mix| mix.mute = mute)
+            }
+            Message::SetSolo(strip, solo) => {
+                update_strip(&mut sequence_state, strip, |mix| mix.solo = solo)
+            }
+            Message::SetGain(strip, gain_db) => {
+                update_strip(&mut sequence_state, strip, |mix| mix.gain_db = gain_db)
+            }
+            Message::SetPan(strip, pan) => {
+                update_strip(&mut sequence_state, strip, |mix| mix.pan = pan)
+            }
+            Message::SetRoute(strip, route) => {
+                update_strip(&mut sequence_state, strip, |mix| mix.bus = route.bus)
+            }
+            Message::AddBus => sequence_state.add_bus(),
+            Message::RemoveBus(index) => sequence_state.remove_bus(index),
+            // Handled by the history in `MainUi`.
+            Message::EditFinished => {}
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let sequence_state = self.sequence_state.lock().unwrap();
+        let selected_samples = self.selected_samples.read().unwrap();
+        let route_options: Vec<RouteOption> = std::iter::once(RouteOption {
+            bus: None,
+            name: "Master".to_string(),
+        })
+        .chain(
+            sequence_state
+                .mixer
+                .buses
+                .iter()
+                .enumerate()
+                .map(|(index, bus)| RouteOption {
+                    bus: Some(index),
+                    name: bus.name.clone(),
+                }),
+        )
+        .collect();
+
+        let drum_strips = (0..sequence_state.beat_pattern.len()).map(|index| {
+            let name = selected_samples
+                .get(&index)
+                .and_then(|file_map| file_map.keys().next().cloned())
+                .unwrap_or_else(|| format!("Drum {}", index + 1));
+            let meter = self.meters.drums.get(index).map(|meter| meter.read());
+            create_strip(
+                Strip::Drum(index),
+                name,
+                sequence_state.drum_mix(index),
+                meter,
+                Some(&route_options),
+            )
+        });
+        let synth_strip = create_strip(
+            Strip::Synth,
+            "Synth".to_string(),
+            sequence_state.mixer.synth,
+            Some(self.meters.synth.read()),
+            Some(&route_options),
+        );
+        let strips = drum_strips
+            .chain(std::iter::once(synth_strip))
+            .fold(Row::new().spacing(5), |row, strip| row.push(strip));
+
+        let buses = sequence_state
+            .mixer
+            .buses
+            .iter()
+            .enumerate()
+            .fold(Row::new().spacing(5), |row, (index, bus)| {
+                let meter = self.meters.buses.get(index).map(|meter| meter.read());
+                let strip = create_strip(Strip::Bus(index), bus.name.clone(), bus.mix, meter, None)
+                    .push(
+                        button(Text::new("Remove").size(12))
+                            .on_press(Message::RemoveBus(index))
+                            .style(theme::Button::Secondary),
+                    );
+                row.push(strip)
+            })
+            .push(button(Text::new("+ Bus")).on_press_maybe(
+                (sequence_state.mixer.buses.len() < MAX_BUSES).then_some(Message::AddBus),
+            ));
+
+        let master_gain = sequence_state.mixer.master_gain_db;
+        let master = Column::new()
+            .spacing(5)
+            .width(Length::Fixed(STRIP_WIDTH))
+            .align_items(Alignment::Center)
+            .push(Text::new("Master"))
+            .push(create_fader(
+                Strip::Master,
+                master_gain,
+                Some(self.meters.master.read()),
+            ))
+            .push(Text::new(format!("{:+.1} dB", master_gain)).size(12));
+
+        Column::new()
+            .spacing(20)
+            .padding(10)
+            .push(Text::new("Tracks"))
+            .push(
+                scrollable(strips).direction(scrollable::Direction::Horizontal(
+                    scrollable::Properties::default(),
+                )),
+            )
+            .push(
+                Row::new()
+                    .spacing(40)
+                    .push(
+                        Column::new()
+                            .spacing(10)
+                            .push(Text::new("Buses"))
+                            .push(buses),
+                    )
+                    .push(master),
+            )
+            .into()
+    }
+}
+
+fn update_strip(
+    sequence_state: &mut SequenceState,
+    strip: Strip,
+    update: impl FnOnce(&mut TrackMix),
+) {
+    match strip {
+        Strip::Drum(index) => {
+            let mut mix = sequence_state.drum_mix(index);
+            update(&mut mix);
+            sequence_state.set_drum_mix(index, mix);
+        }
+        Strip::Synth => update(&mut sequence_state.mixer.synth),
+        Strip::Bus(index) => {
+            if let Some(bus) = sequence_state.mixer.buses.get_mut(index) {
+                update(&mut bus.mix);
+            }
+        }
+        Strip::Master => {}
+    }
+}
+
+/// One channel strip. Buses pass no `route_options` as they always feed the master, and
+/// have no solo.
+fn create_strip<'a>(
+    strip: Strip,
+    name: String,
+    mix: TrackMix,
+    meter: Option<MeterReading>,
+    route_options: Option<&[RouteOption]>,
+) -> Column<'a, Message> {
+    let toggle_style = |on: bool| {
+        if on {
+            theme::Button::Primary
+        } else {
+            theme::Button::Secondary
+        }
+    };
+    let mut toggles = Row::new().spacing(5).push(
+        button(Text::new("M").size(12))
+            .on_press(Message::SetMute(strip, !mix.mute))
+            .style(toggle_style(mix.mute)),
+    );
+    if route_options.is_some() {
+        toggles = toggles.push(
+            button(Text::new("S").size(12))
+                .on_press(Message::SetSolo(strip, !mix.solo))
+                .style(toggle_style(mix.solo)),
+        );
+    }
+
+    let mut column = Column::new()
+        .spacing(5)
+        .width(Length::Fixed(STRIP_WIDTH))
+        .align_items(Alignment::Center)
+        .push(Text::new(name).size(12).height(Length::Fixed(30.0)))
+        .push(create_fader(strip, mix.gain_db, meter))
+        .push(Text::new(format!("{:+.1} dB", mix.gain_db)).size(12))
+        .push(
+            slider(-1.0..=1.0, mix.pan, move |pan| Message::SetPan(strip, pan))
+                .step(0.05)
+                .on_release(Message::EditFinished)
+                .width(Length::Fixed(STRIP_WIDTH - 20.0)),
+        )
+        .push(Text::new(pan_label(mix.pan)).size(12))
+        .push(toggles);
+    if let Some(route_options) = route_options {
+        let selected = route_options
+            .iter()
+            .find(|option| option.bus == mix.bus)
+            .cloned();
+        column = column.push(
+            pick_list(route_options.to_vec(), selected, move |route| {
+                Message::SetRoute(strip, route)
+            })
+            .text_size(12)
+            .width(Length::Fixed(STRIP_WIDTH - 10.0)),
+        );
+    }
+    column
+}
+
+/// The gain fader of a strip with its peak and RMS meter beside it.
+fn create_fader<'a>(strip: Strip, gain_db: f32, meter: Option<MeterReading>) -> Row<'a, Message> {
+    let meter = meter.unwrap_or_default();
+    Row::new()
+        .spacing(5)
+        .height(Length::Fixed(FADER_HEIGHT))
+        .push(
+            vertical_slider(MIN_GAIN_DB..=MAX_GAIN_DB, gain_db, move |gain_db| {
+                Message::SetGain(strip, gain_db)
+            })
+            .step(0.5)
+            .on_release(Message::EditFinished),
+        )
+        .push(create_meter_bar(meter.peak))
+        .push(create_meter_bar(meter.rms))
+}
+
+/// A bar filling up from the bottom over the meter's -60 to 0 dBFS range, red once
+/// the level clips.
+fn create_meter_bar<'a>(level: f32) -> Column<'a, Message> {
+    let db = 20.0 * level.max(1e-6).log10();
+    let fill = ((db - MIN_GAIN_DB) / -MIN_GAIN_DB).clamp(0.0, 1.0);
+    let color = if level >= 1.0 {
+        Color::from_rgb(0.9, 0.2, 0.2)
+    } else {
+        Color::from_rgb(0.3, 0.8, 0.4)
+    };
+    Column::new()
+        .width(Length::Fixed(6.0))
+        .push(Space::with_height(Length::FillPortion(
+            ((1.0 - fill) * 1000.0) as u16 + 1,
+        )))
+        .push(
+            container(Space::new(Length::Fill, Length::Fill))
+                .width(Length::Fill)
+                .height(Length::FillPortion((fill * 1000.0) as u16 + 1))
+                .style(theme::Container::Custom(Box::new(MeterBar(color)))),
+        )
+}
+
+struct MeterBar(Color);
+
+impl container::StyleSheet for MeterBar {
+    type Style = Theme;
+
+    fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+        container::Appearance {
+            background: Some(Background::Color(self.0)),
+            ..Default::default()
+        }
+    }
+}
@@ -1,32 +1,87 @@
 // mod.rs
 
 pub mod arranger;
+pub mod arranger_page;
 pub mod drum_machine;
 pub mod drum_machine_components;
+pub mod drum_step;
+pub mod groove_controls;
+pub mod history;
+pub mod mixer_page;
+pub mod modulation_controls;
+pub mod note_step;
+pub mod pattern_bank;
+pub mod pattern_tools;
 pub mod settings_components;
 pub mod settings_page;
+pub mod step_trigger;
 pub mod synth;
 pub mod synth_components;
 pub mod top_bar;
+pub mod track_mix;
+pub mod track_timing;
+pub mod trigger_lane;
 
-use std::sync::{Arc, Mutex};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
+use crate::scripts::{
+    audio_engine::mixer::MixGraph,
+    audio_engine::{
+        engine::{AudioEngine, EngineHandle},
+        engine_core::VoiceGroup,
+    },
+    groove::{load_groove_templates, Groove, GrooveTemplate},
+    project_file::{load_project, save_project},
+    sequencer::spawn_sequencer,
+    synth::{
+        envelope::Envelope, filter::Filter, lfo::Modulation, oscillator::Oscillator,
+        patch::SynthPatch,
+    },
+    transport::{TimeSignature, Transport, TransportState, TransportTrack},
+};
+use arranger_page::{Arrangement, ArrangerPage};
 use drum_machine::{DrumMachine, SequenceScale};
-use iced::{
-    command,
-    widget::{Column, Text},
-    Application, Command, Element, Theme,
+use drum_step::DrumStep;
+use history::{
+    resize_patterns, EditCommand, History, MixSnapshot, SampleRow, SongSnapshot, SoundSnapshot,
 };
+use iced::{command, keyboard, widget::Column, Application, Command, Element, Subscription, Theme};
+use mixer_page::MixerPage;
+use note_step::NoteStep;
+use pattern_bank::Pattern;
+use pattern_tools::PatternTools;
+use serde::{Deserialize, Serialize};
 use settings_page::SettingsPage;
+use step_trigger::StepTrigger;
 use synth::Synth;
+use track_mix::{MixerState, TrackMix};
+use track_timing::TrackTiming;
 
 pub struct MainUi {
+    _audio_engine: AudioEngine,
+    engine: EngineHandle,
+    pub transport: Arc<Mutex<Transport>>,
     current_page: Page,
     drum_machine: DrumMachine,
+    arranger_page: ArrangerPage,
+    mixer_page: MixerPage,
+    /// The gains last sent to the engine.
+    mix_graph: MixGraph,
     settings_page: SettingsPage,
     pub sequence_state: Arc<Mutex<SequenceState>>,
     synth: Synth,
     pub is_dark_theme: bool,
+    /// The file the project was last opened from or saved to.
+    pub project_path: Option<PathBuf>,
+    pub project_path_input: String,
+    pub project_status: Option<String>,
+    pub history: History,
+    pub groove_templates: Vec<GrooveTemplate>,
+    pub pattern_tools: PatternTools,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -34,20 +89,68 @@ pub enum Page {
     DrumMachine,
     Synth,
     Arranger,
+    Mixer,
     Settings,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SequenceState {
     pub sequence_length: u32,
-    pub beat_pattern: Vec<Vec<bool>>,
-    pub note_pattern: Vec<Vec<bool>>,
+    pub beat_pattern: Vec<Vec<DrumStep>>,
+    pub note_pattern: Vec<Vec<NoteStep>>,
     pub bpm: u32,
+    #[serde(default)]
+    pub time_signature: TimeSignature,
     pub drum_scale: SequenceScale,
     pub synth_scale: SequenceScale,
-    pub drum_sequence_on: bool,
-    pub synth_sequence_on: bool,
     pub octave: u32,
     pub frequency: f32,
+    /// The pattern bank. The entry at `current_pattern` is edited through
+    /// `beat_pattern` and `note_pattern` and synced back when switching patterns.
+    #[serde(default)]
+    pub patterns: Vec<Pattern>,
+    #[serde(default)]
+    pub current_pattern: usize,
+    #[serde(default)]
+    pub arrangement: Arrangement,
+    #[serde(default)]
+    pub groove: Groove,
+    /// Per-track grooves, a track without one follows `groove`.
+    #[serde(default)]
+    pub drum_groove: Option<Groove>,
+    #[serde(default)]
+    pub synth_groove: Option<Groove>,
+    /// Per drum track lengths and resolutions, indexed like the rows of `beat_pattern`.
+    #[serde(default)]
+    pub drum_timing: Vec<TrackTiming>,
+    /// Per drum track grooves, indexed like the rows of `beat_pattern`. A track
+    /// without one follows `drum_groove`.
+    #[serde(default)]
+    pub drum_row_grooves: Vec<Option<Groove>>,
+    /// The synth's own length, `None` follows `sequence_length`.
+    #[serde(default)]
+    pub synth_length: Option<u32>,
+    /// Per drum track mute, solo, gain and pan, indexed like the rows of `beat_pattern`.
+    #[serde(default)]
+    pub drum_mix: Vec<TrackMix>,
+    #[serde(default)]
+    pub mixer: MixerState,
+    #[serde(default)]
+    pub patch: SynthPatch,
+    #[serde(default)]
+    pub oscillator: Oscillator,
+    #[serde(default)]
+    pub envelope: Envelope,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub modulation: Modulation,
+    /// Seeds the probability rolls of conditional steps, so renders are reproducible.
+    #[serde(default)]
+    pub seed: u64,
+    /// Fill mode, plays the FILL steps. Only held while performing, never saved.
+    #[serde(skip)]
+    pub fill: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -56,9 +159,61 @@ pub enum Message {
     ChangePage(Page),
     ToggleTheme(bool),
     SynthMessage(synth::Message),
+    ArrangerMessage(arranger_page::Message),
+    PatternToolsMessage(pattern_tools::Message),
+    MixerMessage(mixer_page::Message),
     UpdateSequenceLength(u32),
     UpdateBpm(u32),
+    UpdateTimeSignature(TimeSignature),
+    UpdateGroove(Groove),
+    ToggleFill(bool),
+    UpdateSeed(u64),
     StartBothSequences(bool),
+    PauseTransport,
+    TransportTick,
+    ProjectPathChanged(String),
+    OpenProject,
+    SaveProject,
+    SaveProjectAs,
+    Undo,
+    Redo,
+    /// Sent when a slider is released, ends the history entry the drag was building.
+    EditFinished,
+}
+
+// An empty project: 120 BPM, no drum rows and an empty note grid.
+impl Default for SequenceState {
+    fn default() -> Self {
+        SequenceState {
+            sequence_length: 16,
+            beat_pattern: vec![vec![DrumStep::default(); 16]; 0],
+            note_pattern: vec![vec![NoteStep::default(); 32]; 12],
+            bpm: 120,
+            time_signature: TimeSignature::default(),
+            drum_scale: SequenceScale::OneFourth,
+            synth_scale: SequenceScale::OneFourth,
+            octave: 0,
+            frequency: 440.0,
+            patterns: Vec::new(),
+            current_pattern: 0,
+            arrangement: Arrangement::default(),
+            groove: Groove::default(),
+            drum_groove: None,
+            synth_groove: None,
+            drum_timing: Vec::new(),
+            drum_row_grooves: Vec::new(),
+            drum_mix: Vec::new(),
+            mixer: MixerState::default(),
+            patch: SynthPatch::default(),
+            oscillator: Oscillator::default(),
+            envelope: Envelope::default(),
+            filter: Filter::default(),
+            modulation: Modulation::default(),
+            synth_length: None,
+            seed: 0,
+            fill: false,
+        }
+    }
 }
 
 impl Application for MainUi {
@@ -68,26 +223,59 @@ impl Application for MainUi {
     type Flags = ();
 
     fn new(_flags: ()) -> (Self, Command<Message>) {
-        let sequence_state = Arc::new(Mutex::new(SequenceState {
-            sequence_length: 16,
-            beat_pattern: vec![vec![false; 16]; 0],
-            note_pattern: vec![vec![false; 32]; 12],
-            bpm: 120,
-            drum_scale: SequenceScale::OneFourth,
-            synth_scale: SequenceScale::OneFourth,
-            drum_sequence_on: false,
-            synth_sequence_on: false,
-            octave: 0,
-            frequency: 440.0,
-        }));
+        let mut sequence_state = SequenceState::default();
+        sequence_state.ensure_pattern_bank();
+        let sequence_state = Arc::new(Mutex::new(sequence_state));
 
-        let (drum_machine, drum_machine_command) = DrumMachine::new(sequence_state.clone());
-        let synth = Synth::new(sequence_state.clone());
+        let audio_engine = AudioEngine::new();
+        let engine = audio_engine.handle();
+        let transport = Arc::new(Mutex::new(Transport::new(
+            sequence_state.lock().unwrap().bpm,
+            engine.sample_rate,
+        )));
+        let groove_templates = load_groove_templates();
+        let (drum_machine, drum_machine_command) = DrumMachine::new(
+            sequence_state.clone(),
+            transport.clone(),
+            engine.clone(),
+            groove_templates.clone(),
+        );
+        let synth = Synth::new(
+            sequence_state.clone(),
+            transport.clone(),
+            engine.clone(),
+            groove_templates.clone(),
+        );
+        let arranger_page = ArrangerPage::new(sequence_state.clone());
+        let mixer_page = MixerPage::new(
+            sequence_state.clone(),
+            drum_machine.selected_samples.clone(),
+            engine.meters.clone(),
+        );
+        spawn_sequencer(
+            transport.clone(),
+            sequence_state.clone(),
+            drum_machine.selected_samples.clone(),
+            drum_machine.sample_cache.clone(),
+            engine.clone(),
+        );
 
         (
             MainUi {
+                _audio_engine: audio_engine,
+                engine,
+                transport,
+                project_path: None,
+                project_path_input: "projects/project.json".to_string(),
+                project_status: None,
+                history: History::default(),
+                groove_templates,
+                pattern_tools: PatternTools::default(),
                 current_page: Page::DrumMachine,
                 drum_machine,
+                arranger_page,
+                mixer_page,
+                mix_graph: MixGraph::default(),
                 settings_page: SettingsPage::new(true),
                 synth,
                 is_dark_theme: true,
@@ -110,37 +298,460 @@ impl Application for MainUi {
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
-        match message {
+        let command = match message {
             Message::StartBothSequences(play_sequence) => {
-                if play_sequence {
-                    let _ = self
-                        .drum_machine
-                        .update(drum_machine::Message::PlaySequence)
-                        .map(Message::DrumMachineMessage);
-                    let _ = self.synth.update(synth::Message::PlaySequence);
-                    //migth cause deadlock
-                    self.sequence_state.lock().unwrap().drum_sequence_on = true;
-                    self.sequence_state.lock().unwrap().synth_sequence_on = true;
-                } else {
-                    let _ = self
-                        .drum_machine
-                        .update(drum_machine::Message::StopSequence)
-                        .map(Message::DrumMachineMessage);
-                    let _ = self.synth.update(synth::Message::StopSequence);
-                    //migth cause deadlock
-                    self.sequence_state.lock().unwrap().drum_sequence_on = false;
-                    self.sequence_state.lock().unwrap().synth_sequence_on = false;
+                // Both tracks are armed under one lock so they start on the same tick.
+                let mut transport = self.transport.lock().unwrap();
+                let start_frame = self.engine.lookahead_frame();
+                for track in [TransportTrack::Drums, TransportTrack::Synth] {
+                    if play_sequence {
+                        transport.play(track, start_frame);
+                    } else {
+                        transport.stop(track);
+                    }
                 }
                 Command::none()
             }
+            Message::PauseTransport => {
+                let mut transport = self.transport.lock().unwrap();
+                match transport.state {
+                    TransportState::Playing => {
+                        transport.pause(self.engine.now());
+                        self.engine.clear_pending(VoiceGroup::Drums);
+                        self.engine.clear_pending(VoiceGroup::Synth);
+                    }
+                    TransportState::Paused => transport.resume(self.engine.lookahead_frame()),
+                    TransportState::Stopped => {}
+                }
+                Command::none()
+            }
+            Message::TransportTick => Command::none(),
+            Message::ProjectPathChanged(path) => {
+                self.project_path_input = path;
+                Command::none()
+            }
+            Message::OpenProject => {
+                let path = PathBuf::from(&self.project_path_input);
+                self.open_project(&path);
+                Command::none()
+            }
+            Message::SaveProject => {
+                let path = self
+                    .project_path
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(&self.project_path_input));
+                self.save_project(&path);
+                Command::none()
+            }
+            Message::SaveProjectAs => {
+                let path = PathBuf::from(&self.project_path_input);
+                self.save_project(&path);
+                Command::none()
+            }
+            Message::ToggleFill(fill) => {
+                self.sequence_state.lock().unwrap().fill = fill;
+                Command::none()
+            }
+            Message::UpdateSeed(seed) => {
+                self.sequence_state.lock().unwrap().seed = seed;
+                Command::none()
+            }
+            Message::UpdateGroove(groove) => {
+                let coalesce = self
+                    .sequence_state
+                    .lock()
+                    .unwrap()
+                    .groove
+                    .same_feel(&groove);
+                // Swing drags share one entry, picking a template or resolution does not.
+                self.record_song_edit(coalesce, |main_ui| {
+                    main_ui.sequence_state.lock().unwrap().groove = groove;
+                    Command::none()
+                })
+            }
             Message::UpdateBpm(bpm) => {
-                self.sequence_state.lock().unwrap().bpm = bpm;
+                let old = std::mem::replace(&mut self.sequence_state.lock().unwrap().bpm, bpm);
+                self.transport.lock().unwrap().set_bpm(bpm);
+                self.history
+                    .record(EditCommand::SetBpm { old, new: bpm }, true);
+                Command::none()
+            }
+            Message::UpdateTimeSignature(time_signature) => {
+                let old = std::mem::replace(
+                    &mut self.sequence_state.lock().unwrap().time_signature,
+                    time_signature,
+                );
+                self.transport
+                    .lock()
+                    .unwrap()
+                    .set_time_signature(time_signature);
+                self.history.record(
+                    EditCommand::SetTimeSignature {
+                        old,
+                        new: time_signature,
+                    },
+                    false,
+                );
+                Command::none()
+            }
+            Message::Undo => {
+                if let Some(command) = self.history.undo() {
+                    self.apply_history(command, true);
+                }
+                Command::none()
+            }
+            Message::Redo => {
+                if let Some(command) = self.history.redo() {
+                    self.apply_history(command, false);
+                }
+                Command::none()
+            }
+            Message::EditFinished => {
+                self.history.close();
+                Command::none()
+            }
+            Message::SynthMessage(synth::Message::ToggleNote(note, step, checked)) => {
+                let old = self.sequence_state.lock().unwrap().note_pattern[note][step].active;
+                let command = self
+                    .synth
+                    .update(synth::Message::ToggleNote(note, step, checked))
+                    .map(Message::SynthMessage);
+                if old != checked {
+                    self.history.record(
+                        EditCommand::SetNote {
+                            note,
+                            step,
+                            old,
+                            new: checked,
+                        },
+                        false,
+                    );
+                }
+                command
+            }
+            Message::SynthMessage(synth::Message::UpdateLength(length)) => {
+                self.record_song_edit(false, |main_ui| {
+                    main_ui
+                        .synth
+                        .update(synth::Message::UpdateLength(length))
+                        .map(Message::SynthMessage)
+                })
+            }
+            Message::SynthMessage(synth::Message::UpdateTrigger(note, step, trigger)) => {
+                self.record_trigger(TransportTrack::Synth, note, step, trigger);
+                self.synth
+                    .update(synth::Message::UpdateTrigger(note, step, trigger))
+                    .map(Message::SynthMessage)
+            }
+            Message::SynthMessage(
+                msg @ (synth::Message::UpdateGroove(_) | synth::Message::ChangeSequenceScale(_)),
+            ) => {
+                let coalesce = match &msg {
+                    synth::Message::UpdateGroove(groove) => {
+                        matches!(
+                            (&self.sequence_state.lock().unwrap().synth_groove, groove),
+                            (Some(old), Some(new)) if old.same_feel(new)
+                        )
+                    }
+                    _ => false,
+                };
+                self.record_song_edit(coalesce, |main_ui| {
+                    main_ui.synth.update(msg).map(Message::SynthMessage)
+                })
+            }
+            Message::SynthMessage(
+                msg @ (synth::Message::UpdatePatch(_)
+                | synth::Message::UpdateOscillator(_)
+                | synth::Message::UpdateEnvelope(_)
+                | synth::Message::UpdateFilter(_)
+                | synth::Message::UpdateModulation(_)),
+            ) => {
+                let old = SoundSnapshot::capture(&self.sequence_state.lock().unwrap());
+                let command = self.synth.update(msg).map(Message::SynthMessage);
+                let new = SoundSnapshot::capture(&self.sequence_state.lock().unwrap());
+                if old != new {
+                    // Only slider drags share an entry, each changed choice is an edit of its own.
+                    let coalesce = old.same_choices(&new);
+                    self.history.record(
+                        EditCommand::SetSynthSound {
+                            old: Box::new(old),
+                            new: Box::new(new),
+                        },
+                        coalesce,
+                    );
+                }
+                command
+            }
+            Message::SynthMessage(synth::Message::EditFinished) => {
+                self.history.close();
                 Command::none()
             }
             Message::SynthMessage(msg) => {
                 self.synth.update(msg);
                 Command::none()
             }
+            Message::PatternToolsMessage(pattern_tools::Message::Apply(tool)) => {
+                let track = match self.current_page {
+                    Page::Synth => TransportTrack::Synth,
+                    _ => TransportTrack::Drums,
+                };
+                let selection = self.pattern_tools.selection();
+                let seed = self.pattern_tools.seed;
+                self.record_song_edit(false, |main_ui| {
+                    main_ui
+                        .sequence_state
+                        .lock()
+                        .unwrap()
+                        .apply_pattern_tool(track, &selection, &tool, seed);
+                    Command::none()
+                })
+            }
+            Message::PatternToolsMessage(msg) => {
+                self.pattern_tools.update(msg);
+                Command::none()
+            }
+            Message::MixerMessage(mixer_page::Message::EditFinished) => {
+                self.history.close();
+                Command::none()
+            }
+            Message::MixerMessage(msg) => {
+                let coalesce = msg.is_continuous();
+                let old = MixSnapshot::capture(&self.sequence_state.lock().unwrap());
+                self.mixer_page.update(msg);
+                let new = MixSnapshot::capture(&self.sequence_state.lock().unwrap());
+                if old != new {
+                    self.history.record(
+                        EditCommand::SetMixer {
+                            old: Box::new(old),
+                            new: Box::new(new),
+                        },
+                        coalesce,
+                    );
+                }
+                Command::none()
+            }
+            Message::ArrangerMessage(arranger_page::Message::EditFinished) => {
+                self.history.close();
+                Command::none()
+            }
+            Message::ArrangerMessage(msg) => {
+                let coalesce = msg.is_continuous();
+                self.record_song_edit(coalesce, |main_ui| {
+                    main_ui.arranger_page.update(msg);
+                    Command::none()
+                })
+            }
+            Message::DrumMachineMessage(drum_machine::Message::UpdateTrackMix(track, mix)) => {
+                let old = self.sequence_state.lock().unwrap().drum_mix(track);
+                let command = self
+                    .drum_machine
+                    .update(drum_machine::Message::UpdateTrackMix(track, mix))
+                    .map(Message::DrumMachineMessage);
+                if old != mix {
+                    // Only slider drags share an entry, each toggle is an edit of its own.
+                    let coalesce = old.mute == mix.mute && old.solo == mix.solo;
+                    self.history.record(
+                        EditCommand::SetTrackMix {
+                            track,
+                            old,
+                            new: mix,
+                        },
+                        coalesce,
+                    );
+                }
+                command
+            }
+            Message::DrumMachineMessage(drum_machine::Message::UpdateTrackTiming(
+                track,
+                timing,
+            )) => self.record_song_edit(false, |main_ui| {
+                main_ui
+                    .drum_machine
+                    .update(drum_machine::Message::UpdateTrackTiming(track, timing))
+                    .map(Message::DrumMachineMessage)
+            }),
+            Message::DrumMachineMessage(drum_machine::Message::UpdateBeatPattern(
+                track,
+                step,
+                checked,
+            )) => {
+                let old = self
+                    .sequence_state
+                    .lock()
+                    .unwrap()
+                    .beat_pattern
+                    .get(track)
+                    .and_then(|row| row.get(step))
+                    .map(|drum_step| drum_step.active);
+                let command = self
+                    .drum_machine
+                    .update(drum_machine::Message::UpdateBeatPattern(
+                        track, step, checked,
+                    ))
+                    .map(Message::DrumMachineMessage);
+                if let Some(old) = old.filter(|old| *old != checked) {
+                    self.history.record(
+                        EditCommand::SetBeat {
+                            track,
+                            step,
+                            old,
+                            new: checked,
+                        },
+                        false,
+                    );
+                }
+                command
+            }
+            Message::DrumMachineMessage(drum_machine::Message::UpdateRatchet(
+                track,
+                step,
+                ratchet,
+            )) => {
+                let old = self
+                    .sequence_state
+                    .lock()
+                    .unwrap()
+                    .beat_pattern
+                    .get(track)
+                    .and_then(|row| row.get(step))
+                    .map(|drum_step| drum_step.ratchet);
+                let command = self
+                    .drum_machine
+                    .update(drum_machine::Message::UpdateRatchet(track, step, ratchet))
+                    .map(Message::DrumMachineMessage);
+                if let Some(old) = old.filter(|old| *old != ratchet) {
+                    self.history.record(
+                        EditCommand::SetRatchet {
+                            track,
+                            step,
+                            old,
+                            new: ratchet,
+                        },
+                        false,
+                    );
+                }
+                command
+            }
+            Message::DrumMachineMessage(drum_machine::Message::ApplyEuclid) => {
+                let row = |main_ui: &Self, track: usize| {
+                    main_ui
+                        .sequence_state
+                        .lock()
+                        .unwrap()
+                        .beat_pattern
+                        .get(track)
+                        .cloned()
+                };
+                let track = self.drum_machine.euclid.map(|euclid| euclid.row);
+                let old = track.and_then(|track| row(self, track));
+                let command = self
+                    .drum_machine
+                    .update(drum_machine::Message::ApplyEuclid)
+                    .map(Message::DrumMachineMessage);
+                if let (Some(track), Some(old)) = (track, old) {
+                    if let Some(new) = row(self, track).filter(|new| *new != old) {
+                        self.history
+                            .record(EditCommand::SetDrumRow { track, old, new }, false);
+                    }
+                }
+                command
+            }
+            Message::DrumMachineMessage(drum_machine::Message::UpdateVelocity(
+                track,
+                step,
+                velocity,
+            )) => {
+                let old = self
+                    .sequence_state
+                    .lock()
+                    .unwrap()
+                    .beat_pattern
+                    .get(track)
+                    .and_then(|row| row.get(step))
+                    .map(|drum_step| drum_step.velocity);
+                let command = self
+                    .drum_machine
+                    .update(drum_machine::Message::UpdateVelocity(track, step, velocity))
+                    .map(Message::DrumMachineMessage);
+                if let Some(old) = old.filter(|old| *old != velocity) {
+                    self.history.record(
+                        EditCommand::SetVelocity {
+                            track,
+                            step,
+                            old,
+                            new: velocity,
+                        },
+                        true,
+                    );
+                }
+                command
+            }
+            Message::DrumMachineMessage(drum_machine::Message::UpdateTrigger(
+                track,
+                step,
+                trigger,
+            )) => {
+                self.record_trigger(TransportTrack::Drums, track, step, trigger);
+                self.drum_machine
+                    .update(drum_machine::Message::UpdateTrigger(track, step, trigger))
+                    .map(Message::DrumMachineMessage)
+            }
+            Message::DrumMachineMessage(
+                msg @ (drum_machine::Message::UpdateGroove(_)
+                | drum_machine::Message::UpdateRowGroove(..)
+                | drum_machine::Message::ChangeSequenceScale(_)),
+            ) => {
+                let sequence_state = self.sequence_state.lock().unwrap();
+                let coalesce = match &msg {
+                    drum_machine::Message::UpdateGroove(groove) => {
+                        // Only swing drags share an entry, as long as the track keeps its
+                        // own groove.
+                        matches!(
+                            (&sequence_state.drum_groove, groove),
+                            (Some(old), Some(new)) if old.same_feel(new)
+                        )
+                    }
+                    drum_machine::Message::UpdateRowGroove(index, groove) => matches!(
+                        (sequence_state.drum_row_grooves.get(*index), groove),
+                        (Some(Some(old)), Some(new)) if old.same_feel(new)
+                    ),
+                    _ => false,
+                };
+                drop(sequence_state);
+                self.record_song_edit(coalesce, |main_ui| {
+                    main_ui
+                        .drum_machine
+                        .update(msg)
+                        .map(Message::DrumMachineMessage)
+                })
+            }
+            Message::DrumMachineMessage(drum_machine::Message::EditFinished) => {
+                self.history.close();
+                Command::none()
+            }
+            Message::DrumMachineMessage(drum_machine::Message::RemoveSample(index)) => {
+                let removed = self.sample_row(index);
+                let command = self
+                    .drum_machine
+                    .update(drum_machine::Message::RemoveSample(index))
+                    .map(Message::DrumMachineMessage);
+                if let Some(removed) = removed {
+                    self.history
+                        .record(EditCommand::RemoveSample(removed), false);
+                }
+                command
+            }
+            Message::DrumMachineMessage(msg @ drum_machine::Message::PlayAndAddSample(_)) => {
+                let sample_count = self.drum_machine.selected_samples.read().unwrap().len();
+                let command = self
+                    .drum_machine
+                    .update(msg)
+                    .map(Message::DrumMachineMessage);
+                if let Some(added) = self.sample_row(sample_count) {
+                    self.history.record(EditCommand::AddSample(added), false);
+                }
+                command
+            }
             Message::DrumMachineMessage(msg) => self
                 .drum_machine
                 .update(msg) // Pass current_page here
@@ -156,24 +767,254 @@ impl Application for MainUi {
                 Command::none()
             }
             Message::UpdateSequenceLength(length) => {
-                self.sequence_state.lock().unwrap().sequence_length = length * 2;
-                for pattern in &mut self.sequence_state.lock().unwrap().beat_pattern {
-                    pattern.resize((length * 2) as usize, false);
-                }
+                let mut sequence_state = self.sequence_state.lock().unwrap();
+                let command = EditCommand::SetSequenceLength {
+                    old: sequence_state.sequence_length,
+                    new: length * 2,
+                    old_beat_pattern: sequence_state.beat_pattern.clone(),
+                    old_note_pattern: sequence_state.note_pattern.clone(),
+                    old_patterns: sequence_state.patterns.clone(),
+                };
+                resize_patterns(&mut sequence_state, length * 2);
+                drop(sequence_state);
+                self.history.record(command, true);
                 Command::none()
             }
-        }
+        };
+        // Mixer edits, undo, opening a project and removing a track all change the gains.
+        self.sync_mix();
+        command
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let shortcuts = keyboard::on_key_press(|key, modifiers| match key.as_ref() {
+            keyboard::Key::Character(c) if c.eq_ignore_ascii_case("z") && modifiers.command() => {
+                Some(if modifiers.shift() {
+                    Message::Redo
+                } else {
+                    Message::Undo
+                })
+            }
+            _ => None,
+        });
+
+        // Keeps the position display moving while the transport runs.
+        let transport_tick = if self.transport.lock().unwrap().state == TransportState::Playing {
+            iced::time::every(Duration::from_millis(50)).map(|_| Message::TransportTick)
+        } else {
+            Subscription::none()
+        };
+
+        // Picks up kits added or removed on disk.
+        let kit_check = iced::time::every(Duration::from_secs(1))
+            .map(|_| Message::DrumMachineMessage(drum_machine::Message::CheckKits));
+
+        // Keeps the meters moving.
+        let meter_tick = if self.current_page == Page::Mixer {
+            iced::time::every(Duration::from_millis(50)).map(|_| Message::TransportTick)
+        } else {
+            Subscription::none()
+        };
+
+        Subscription::batch([shortcuts, transport_tick, kit_check, meter_tick])
     }
 
     fn view(&self) -> Element<Message> {
         let top_bar = self.create_top_bar();
         let content = match self.current_page {
-            Page::DrumMachine => self.drum_machine.view().map(Message::DrumMachineMessage),
-            Page::Arranger => Text::new("Arranger page (TODO)").into(),
-            Page::Synth => self.synth.view().map(Message::SynthMessage),
-            Page::Settings => self.settings_page.view(),
+            Page::DrumMachine => Column::new()
+                .push(self.drum_machine.view().map(Message::DrumMachineMessage))
+                .push(self.create_pattern_tools(TransportTrack::Drums))
+                .into(),
+            Page::Arranger => self.arranger_page.view().map(Message::ArrangerMessage),
+            Page::Mixer => self.mixer_page.view().map(Message::MixerMessage),
+            Page::Synth => Column::new()
+                .push(self.synth.view().map(Message::SynthMessage))
+                .push(self.create_pattern_tools(TransportTrack::Synth))
+                .into(),
+            Page::Settings => self.settings_page.view(
+                Element::from(self.drum_machine.create_sample_root_settings())
+                    .map(Message::DrumMachineMessage),
+            ),
         };
 
         Column::new().push(top_bar).push(content).into()
     }
 }
+
+impl MainUi {
+    fn save_project(&mut self, path: &Path) {
+        let sequence_state = self.sequence_state.lock().unwrap();
+        let selected_samples = self.drum_machine.selected_samples.read().unwrap();
+        self.project_status = Some(
+            match save_project(path, &sequence_state, &selected_samples) {
+                Ok(()) => {
+                    self.project_path = Some(path.to_path_buf());
+                    format!("Saved {}", path.display())
+                }
+                Err(e) => format!("Error saving {}: {}", path.display(), e),
+            },
+        );
+    }
+
+    fn open_project(&mut self, path: &Path) {
+        let project = match load_project(path, &self.drum_machine.root_sample_folder) {
+            Ok(project) => project,
+            Err(e) => {
+                self.project_status = Some(format!("Error opening {}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        let mut transport = self.transport.lock().unwrap();
+        transport.set_bpm(project.sequence_state.bpm);
+        transport.set_time_signature(project.sequence_state.time_signature);
+        drop(transport);
+        *self.sequence_state.lock().unwrap() = project.sequence_state;
+        self.drum_machine
+            .sample_cache
+            .preload(&project.selected_samples);
+        *self.drum_machine.selected_samples.write().unwrap() = project.selected_samples;
+        self.project_path = Some(path.to_path_buf());
+        self.history.clear();
+
+        self.project_status = Some(if project.missing_samples.is_empty() {
+            format!("Opened {}", path.display())
+        } else {
+            let missing: Vec<String> = project
+                .missing_samples
+                .iter()
+                .map(|sample| sample.display().to_string())
+                .collect();
+            format!(
+                "Opened {}, missing samples: {}",
+                path.display(),
+                missing.join(", ")
+            )
+        });
+    }
+
+    /// Captures a drum row so its removal, or addition, can be replayed later.
+    fn sample_row(&self, index: usize) -> Option<SampleRow> {
+        let selected_samples = self.drum_machine.selected_samples.read().unwrap();
+        let (file_name, sample_folder) = selected_samples.get(&index)?.iter().next()?;
+        let sequence_state = self.sequence_state.lock().unwrap();
+        Some(SampleRow {
+            index,
+            file_name: file_name.clone(),
+            sample_folder: sample_folder.clone(),
+            rows: sequence_state.drum_rows(index),
+            timing: sequence_state.drum_timing(index),
+            groove: sequence_state
+                .drum_row_grooves
+                .get(index)
+                .cloned()
+                .flatten(),
+            mix: sequence_state.drum_mix(index),
+        })
+    }
+
+    /// Sends the gains of the mixer to the engine when an edit changed them.
+    fn sync_mix(&mut self) {
+        let mix_graph = self.sequence_state.lock().unwrap().mix_graph();
+        if mix_graph != self.mix_graph {
+            self.engine.set_mix(mix_graph.clone());
+            self.mix_graph = mix_graph;
+        }
+    }
+
+    fn create_pattern_tools(&self, track: TransportTrack) -> Element<'_, Message> {
+        let sequence_state = self.sequence_state.lock().unwrap();
+        let (row_names, length) = match track {
+            TransportTrack::Drums => (
+                self.drum_machine
+                    .selected_samples
+                    .read()
+                    .unwrap()
+                    .values()
+                    .filter_map(|file_map| file_map.keys().next().cloned())
+                    .collect(),
+                sequence_state.beat_pattern.iter().map(Vec::len).max(),
+            ),
+            TransportTrack::Synth => (
+                self.synth.notes.clone(),
+                sequence_state.note_pattern.iter().map(Vec::len).max(),
+            ),
+        };
+        drop(sequence_state);
+        self.pattern_tools
+            .view(track, &row_names, length.unwrap_or_default() as u32)
+            .map(Message::PatternToolsMessage)
+    }
+
+    /// Runs an edit of the pattern bank, arrangement or track timing and records the
+    /// change it made as one history entry.
+    fn record_song_edit(
+        &mut self,
+        coalesce: bool,
+        update: impl FnOnce(&mut Self) -> Command<Message>,
+    ) -> Command<Message> {
+        let old = SongSnapshot::capture(&self.sequence_state.lock().unwrap());
+        let command = update(self);
+        let new = SongSnapshot::capture(&self.sequence_state.lock().unwrap());
+        if old != new {
+            self.history.record(
+                EditCommand::SetSong {
+                    old: Box::new(old),
+                    new: Box::new(new),
+                },
+                coalesce,
+            );
+        }
+        command
+    }
+
+    /// Records a trigger edit before it is applied. Dragging a probability slider
+    /// shares one history entry, every condition change gets its own.
+    fn record_trigger(&mut self, track: TransportTrack, row: usize, step: usize, new: StepTrigger) {
+        let sequence_state = self.sequence_state.lock().unwrap();
+        let old = match track {
+            TransportTrack::Drums => sequence_state
+                .beat_pattern
+                .get(row)
+                .and_then(|row| row.get(step))
+                .map(|drum_step| drum_step.trigger),
+            TransportTrack::Synth => sequence_state
+                .note_pattern
+                .get(row)
+                .and_then(|row| row.get(step))
+                .map(|note_step| note_step.trigger),
+        };
+        drop(sequence_state);
+        if let Some(old) = old.filter(|old| *old != new) {
+            self.history.record(
+                EditCommand::SetTrigger {
+                    track,
+                    row,
+                    step,
+                    old,
+                    new,
+                },
+                old.condition == new.condition,
+            );
+        }
+    }
+
+    fn apply_history(&mut self, command: EditCommand, undo: bool) {
+        let (bpm, time_signature) = {
+            let mut sequence_state = self.sequence_state.lock().unwrap();
+            let mut selected_samples = self.drum_machine.selected_samples.write().unwrap();
+            if undo {
+                command.revert(&mut sequence_state, &mut selected_samples);
+            } else {
+                command.apply(&mut sequence_state, &mut selected_samples);
+            }
+            (sequence_state.bpm, sequence_state.time_signature)
+        };
+        // Locked only once the state guards are gone, the sequencer thread takes the
+        // transport first.
+        let mut transport = self.transport.lock().unwrap();
+        transport.set_bpm(bpm);
+        transport.set_time_signature(time_signature);
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+
+use iced::{
+    theme,
+    widget::{button, checkbox, pick_list, slider, Column, Row, Text},
+    Alignment, Length,
+};
+
+use crate::scripts::synth::lfo::{
+    Lfo, LfoShape, ModRoute, ModTarget, Modulation, SyncDivision, MAX_LFOS, MAX_LFO_RATE_HZ,
+    MIN_LFO_RATE_HZ,
+};
+
+/// Pick list entry for the LFO a route reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LfoOption(pub usize);
+
+impl fmt::Display for LfoOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LFO {}", self.0 + 1)
+    }
+}
+
+/// The LFOs of the synth and the routes from them to its parameters. `on_release`
+/// is sent when a rate or depth slider is let go.
+pub fn create_modulation_controls<'a, Message: Clone + 'a>(
+    modulation: &Modulation,
+    on_change: impl Fn(Modulation) -> Message + Clone + 'a,
+    on_release: Message,
+) -> Column<'a, Message> {
+    // Every control sends the whole modulation with its one change made.
+    let editor = |index: usize| {
+        let modulation = modulation.clone();
+        let on_change = on_change.clone();
+        move |update: &dyn Fn(&mut Lfo)| {
+            let mut modulation = modulation.clone();
+            update(&mut modulation.lfos[index]);
+            on_change(modulation)
+        }
+    };
+
+    let lfos = modulation.lfos.iter().enumerate().fold(
+        Row::new().spacing(20).align_items(Alignment::Start),
+        |row, (index, lfo)| {
+            let lfo = *lfo;
+            let on_shape = editor(index);
+            let on_sync = editor(index);
+            let on_rate = editor(index);
+            let mut column = Column::new()
+                .spacing(5)
+                .push(Text::new(LfoOption(index).to_string()))
+                .push(pick_list(LfoShape::ALL, Some(lfo.shape), move |shape| {
+                    on_shape(&|lfo| lfo.shape = shape)
+                }))
+                .push(checkbox("Sync", lfo.sync.is_some()).on_toggle(move |sync| {
+                    on_sync(&|lfo| lfo.sync = sync.then_some(SyncDivision::default()))
+                }));
+            column = match lfo.sync {
+                Some(division) => column.push(pick_list(
+                    SyncDivision::ALL,
+                    Some(division),
+                    move |division| on_rate(&|lfo| lfo.sync = Some(division)),
+                )),
+                None => column
+                    .push(Text::new(format!("{:.2} Hz", lfo.rate_hz)).size(12))
+                    .push(
+                        slider(
+                            MIN_LFO_RATE_HZ.log2()..=MAX_LFO_RATE_HZ.log2(),
+                            lfo.rate_hz.log2(),
+                            move |octave| on_rate(&|lfo| lfo.rate_hz = 2.0_f32.powf(octave)),
+                        )
+                        .step(0.01)
+                        .on_release(on_release.clone())
+                        .width(Length::Fixed(120.0)),
+                    ),
+            };
+            let mut removed = modulation.clone();
+            removed.remove_lfo(index);
+            row.push(
+                column.push(
+                    button(Text::new("Remove").size(12))
+                        .on_press(on_change(removed))
+                        .style(theme::Button::Secondary),
+                ),
+            )
+        },
+    );
+    let mut added = modulation.clone();
+    added.add_lfo();
+    let lfos = lfos.push(
+        button(Text::new("+ LFO"))
+            .on_press_maybe((modulation.lfos.len() < MAX_LFOS).then(|| on_change(added))),
+    );
+
+    let lfo_options: Vec<LfoOption> = (0..modulation.lfos.len()).map(LfoOption).collect();
+    let routes = modulation.routes.iter().enumerate().fold(
+        Column::new().spacing(5),
+        |column, (index, route)| {
+            let route = *route;
+            let route_editor = || {
+                let modulation = modulation.clone();
+                let on_change = on_change.clone();
+                move |route: ModRoute| {
+                    let mut modulation = modulation.clone();
+                    modulation.routes[index] = route;
+                    on_change(modulation)
+                }
+            };
+            let (on_lfo, on_target, on_depth) = (route_editor(), route_editor(), route_editor());
+            let mut removed = modulation.clone();
+            removed.routes.remove(index);
+            column.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(pick_list(
+                        lfo_options.clone(),
+                        Some(LfoOption(route.lfo)),
+                        move |lfo| {
+                            on_lfo(ModRoute {
+                                lfo: lfo.0,
+                                ..route
+                            })
+                        },
+                    ))
+                    .push(Text::new("to"))
+                    .push(pick_list(
+                        ModTarget::ALL,
+                        Some(route.target),
+                        move |target| on_target(ModRoute { target, ..route }),
+                    ))
+                    .push(Text::new(format!("Depth: {:+.0}%", route.depth * 100.0)))
+                    .push(
+                        slider(-1.0..=1.0, route.depth, move |depth| {
+                            on_depth(ModRoute { depth, ..route })
+                        })
+                        .step(0.01)
+                        .on_release(on_release.clone())
+                        .width(Length::Fixed(150.0)),
+                    )
+                    .push(
+                        button(Text::new("Remove").size(12))
+                            .on_press(on_change(removed))
+                            .style(theme::Button::Secondary),
+                    ),
+            )
+        },
+    );
+    let mut routed = modulation.clone();
+    routed.routes.push(ModRoute {
+        lfo: 0,
+        target: ModTarget::default(),
+        depth: 0.0,
+    });
+    let routes = routes.push(
+        button(Text::new("+ Route"))
+            .on_press_maybe((!modulation.lfos.is_empty()).then(|| on_change(routed))),
+    );
+
+    Column::new()
+        .spacing(10)
+        .push(Text::new("LFOs:"))
+        .push(lfos)
+        .push(Text::new("Routes:"))
+        .push(routes)
+}
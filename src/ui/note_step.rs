@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    drum_step::MAX_NUDGE,
+    step_trigger::{Step, StepTrigger},
+};
+
+/// One step of a synth note row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredNoteStep")]
+pub struct NoteStep {
+    pub active: bool,
+    pub trigger: StepTrigger,
+    /// Plays the note up to `MAX_NUDGE` ticks early or late.
+    pub nudge: i8,
+}
+
+impl Step for NoteStep {
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    fn trigger(&self) -> StepTrigger {
+        self.trigger
+    }
+
+    fn nudge(&self) -> i8 {
+        self.nudge
+    }
+
+    fn set_nudge(&mut self, nudge: i8) {
+        self.nudge = nudge.clamp(-MAX_NUDGE, MAX_NUDGE);
+    }
+}
+
+// Projects saved before note triggers existed store every note as a plain bool.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredNoteStep {
+    Legacy(bool),
+    Step {
+        active: bool,
+        #[serde(default)]
+        trigger: StepTrigger,
+        #[serde(default)]
+        nudge: i8,
+    },
+}
+
+impl From<StoredNoteStep> for NoteStep {
+    fn from(step: StoredNoteStep) -> Self {
+        match step {
+            StoredNoteStep::Legacy(active) => NoteStep {
+                active,
+                ..NoteStep::default()
+            },
+            StoredNoteStep::Step {
+                active,
+                trigger,
+                nudge,
+            } => NoteStep {
+                active,
+                trigger,
+                nudge: nudge.clamp(-MAX_NUDGE, MAX_NUDGE),
+            },
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+use crate::scripts::groove::Groove;
+
+use super::{
+    drum_step::DrumStep, note_step::NoteStep, track_mix::TrackMix, track_timing::TrackTiming,
+    SequenceState,
+};
+
+/// One named drum + synth pattern of the bank.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pattern {
+    pub name: String,
+    pub beat_pattern: Vec<Vec<DrumStep>>,
+    pub note_pattern: Vec<Vec<NoteStep>>,
+}
+
+/// The drum rows and synth rows of one pattern.
+pub type PatternRows<'a> = (&'a [Vec<DrumStep>], &'a [Vec<NoteStep>]);
+
+impl SequenceState {
+    /// The bank entry of the pattern being edited goes stale while its working copy
+    /// lives in `beat_pattern` and `note_pattern`, this writes the working copy back.
+    pub fn sync_current_pattern(&mut self) {
+        let (beat_pattern, note_pattern) = (self.beat_pattern.clone(), self.note_pattern.clone());
+        if let Some(pattern) = self.patterns.get_mut(self.current_pattern) {
+            pattern.beat_pattern = beat_pattern;
+            pattern.note_pattern = note_pattern;
+        }
+    }
+
+    pub fn ensure_pattern_bank(&mut self) {
+        if self.patterns.is_empty() {
+            self.patterns.push(Pattern {
+                name: "Pattern 1".to_string(),
+                beat_pattern: self.beat_pattern.clone(),
+                note_pattern: self.note_pattern.clone(),
+            });
+        }
+        self.current_pattern = self.current_pattern.min(self.patterns.len() - 1);
+    }
+
+    pub fn select_pattern(&mut self, index: usize) {
+        if index >= self.patterns.len() || index == self.current_pattern {
+            return;
+        }
+        self.sync_current_pattern();
+        self.current_pattern = index;
+        self.beat_pattern = self.patterns[index].beat_pattern.clone();
+        self.note_pattern = self.patterns[index].note_pattern.clone();
+    }
+
+    /// Returns the up to date drum and synth rows of a bank entry.
+    pub fn pattern_rows(&self, index: usize) -> Option<PatternRows<'_>> {
+        if index == self.current_pattern {
+            Some((&self.beat_pattern, &self.note_pattern))
+        } else {
+            let pattern = self.patterns.get(index)?;
+            Some((&pattern.beat_pattern, &pattern.note_pattern))
+        }
+    }
+
+    /// Adds a drum track at `index` to every pattern of the bank. `rows` holds the row
+    /// of each pattern, missing ones start empty.
+    pub fn insert_drum_row(
+        &mut self,
+        index: usize,
+        rows: &[Vec<DrumStep>],
+        timing: TrackTiming,
+        groove: Option<Groove>,
+        mix: TrackMix,
+    ) {
+        self.sync_current_pattern();
+        if self.drum_timing.len() < index {
+            self.drum_timing.resize(index, TrackTiming::default());
+        }
+        self.drum_timing.insert(index, timing);
+        if self.drum_row_grooves.len() < index {
+            self.drum_row_grooves.resize(index, None);
+        }
+        self.drum_row_grooves.insert(index, groove);
+        if self.drum_mix.len() < index {
+            self.drum_mix.resize(index, TrackMix::default());
+        }
+        self.drum_mix.insert(index, mix);
+        let length = self.drum_row_length(index) as usize;
+        for (pattern_index, pattern) in self.patterns.iter_mut().enumerate() {
+            let mut row = rows.get(pattern_index).cloned().unwrap_or_default();
+            row.resize(length, DrumStep::default());
+            let index = index.min(pattern.beat_pattern.len());
+            pattern.beat_pattern.insert(index, row);
+        }
+        self.beat_pattern = self.patterns[self.current_pattern].beat_pattern.clone();
+    }
+
+    /// Removes the drum track at `index` from every pattern and returns the removed rows.
+    pub fn remove_drum_row(&mut self, index: usize) -> Vec<Vec<DrumStep>> {
+        self.sync_current_pattern();
+        if index < self.drum_timing.len() {
+            self.drum_timing.remove(index);
+        }
+        if index < self.drum_row_grooves.len() {
+            self.drum_row_grooves.remove(index);
+        }
+        if index < self.drum_mix.len() {
+            self.drum_mix.remove(index);
+        }
+        let rows = self
+            .patterns
+            .iter_mut()
+            .map(|pattern| {
+                if index < pattern.beat_pattern.len() {
+                    pattern.beat_pattern.remove(index)
+                } else {
+                    Vec::new()
+                }
+            })
+            .collect();
+        self.beat_pattern = self.patterns[self.current_pattern].beat_pattern.clone();
+        rows
+    }
+
+    /// The drum row at `index` of every pattern, in bank order.
+    pub fn drum_rows(&self, index: usize) -> Vec<Vec<DrumStep>> {
+        (0..self.patterns.len())
+            .map(|pattern_index| {
+                self.pattern_rows(pattern_index)
+                    .and_then(|(beat_pattern, _)| beat_pattern.get(index).cloned())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    pub fn pattern_bars(&self) -> u32 {
+        (self.pattern_ticks() / self.time_signature.bar_ticks())
+            .ceil()
+            .max(1.0) as u32
+    }
+
+    pub fn resize_pattern_bank(&mut self) {
+        let drum_lengths: Vec<usize> = (0..self.drum_timing.len().max(self.beat_pattern.len()))
+            .map(|index| self.drum_row_length(index) as usize)
+            .collect();
+        let synth_length = self.synth_row_length() as usize;
+        for pattern in self.patterns.iter_mut() {
+            for (row, length) in pattern.beat_pattern.iter_mut().zip(drum_lengths.iter()) {
+                row.resize(*length, DrumStep::default());
+            }
+            for row in pattern.note_pattern.iter_mut() {
+                row.resize(synth_length, NoteStep::default());
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+
+use iced::{
+    widget::{button, pick_list, slider, text_input, Column, Row, Text},
+    Element, Length,
+};
+
+use crate::scripts::{
+    pattern_tools::{PatternTool, Selection, DEFAULT_DENSITY},
+    transport::TransportTrack,
+};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SelectRow(RowOption),
+    UpdateStart(u32),
+    UpdateEnd(u32),
+    /// Sets the random fill density of a row of a track.
+    UpdateDensity(TransportTrack, usize, u8),
+    UpdateAmount(u8),
+    UpdateVelocity(u8),
+    UpdateTiming(u8),
+    UpdateSeed(String),
+    /// Runs a tool over the selection, handled by `MainUi` so it can be undone.
+    Apply(PatternTool),
+}
+
+/// A row of the selection, `None` standing for every row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowOption {
+    pub index: Option<usize>,
+    pub name: String,
+}
+
+impl fmt::Display for RowOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// The selection and settings of the generative pattern tools shown below the drum
+/// grid and the synth sequence.
+pub struct PatternTools {
+    pub row: Option<usize>,
+    /// One based and inclusive, like the step numbers of the grid.
+    pub start: u32,
+    pub end: u32,
+    /// The random fill density of each row, of the drum and of the synth track.
+    pub drum_densities: Vec<u8>,
+    pub synth_densities: Vec<u8>,
+    pub amount: u8,
+    pub velocity: u8,
+    pub timing: u8,
+    pub seed: u64,
+}
+
+impl Default for PatternTools {
+    fn default() -> Self {
+        PatternTools {
+            row: None,
+            start: 1,
+            end: 64,
+            drum_densities: Vec::new(),
+            synth_densities: Vec::new(),
+            amount: 10,
+            velocity: 20,
+            timing: 50,
+            seed: 0,
+        }
+    }
+}
+
+impl PatternTools {
+    pub fn update(&mut self, message: Message) {
+        match message {
+            Message::SelectRow(option) => self.row = option.index,
+            Message::UpdateStart(start) => {
+                self.start = start;
+                self.end = self.end.max(start);
+            }
+            Message::UpdateEnd(end) => {
+                self.end = end;
+                self.start = self.start.min(end);
+            }
+            Message::UpdateDensity(track, row, density) => {
+                let densities = match track {
+                    TransportTrack::Drums => &mut self.drum_densities,
+                    TransportTrack::Synth => &mut self.synth_densities,
+                };
+                if densities.len() <= row {
+                    densities.resize(row + 1, DEFAULT_DENSITY);
+                }
+                densities[row] = density;
+            }
+            Message::UpdateAmount(amount) => self.amount = amount,
+            Message::UpdateVelocity(velocity) => self.velocity = velocity,
+            Message::UpdateTiming(timing) => self.timing = timing,
+            Message::UpdateSeed(seed) => {
+                if let Ok(seed) = seed.trim().parse() {
+                    self.seed = seed;
+                } else if seed.trim().is_empty() {
+                    self.seed = 0;
+                }
+            }
+            Message::Apply(_) => {}
+        }
+    }
+
+    /// The random fill density of every row of `track`.
+    fn densities(&self, track: TransportTrack, rows: usize) -> Vec<u8> {
+        let densities = match track {
+            TransportTrack::Drums => &self.drum_densities,
+            TransportTrack::Synth => &self.synth_densities,
+        };
+        (0..rows)
+            .map(|row| densities.get(row).copied().unwrap_or(DEFAULT_DENSITY))
+            .collect()
+    }
+
+    pub fn selection(&self) -> Selection {
+        Selection {
+            row: self.row,
+            steps: self.start.max(1) as usize - 1..=self.end.max(1) as usize - 1,
+        }
+    }
+
+    /// `row_names` names the rows of `track`, the track being edited, `length` is the
+    /// length of its longest row.
+    pub fn view(
+        &self,
+        track: TransportTrack,
+        row_names: &[String],
+        length: u32,
+    ) -> Element<'_, Message> {
+        let length = length.max(1);
+        let row_options: Vec<RowOption> = std::iter::once(RowOption {
+            index: None,
+            name: "All rows".to_string(),
+        })
+        .chain(row_names.iter().enumerate().map(|(index, name)| RowOption {
+            index: Some(index),
+            name: name.clone(),
+        }))
+        .collect();
+        let selected_row = row_options
+            .iter()
+            .find(|option| option.index == self.row)
+            .cloned();
+        let start = self.start.clamp(1, length);
+        let end = self.end.clamp(start, length);
+
+        let selection_row = Row::new()
+            .spacing(10)
+            .push(Text::new("Tools:"))
+            .push(pick_list(row_options, selected_row, Message::SelectRow).text_size(12))
+            .push(Text::new(format!("Steps {}-{}", start, end)).size(12))
+            .push(slider(1..=length, start, Message::UpdateStart).width(Length::Fixed(120.0)))
+            .push(slider(1..=length, end, Message::UpdateEnd).width(Length::Fixed(120.0)))
+            .push(Text::new("Seed:").size(12))
+            .push(
+                text_input("0", &self.seed.to_string())
+                    .on_input(Message::UpdateSeed)
+                    .size(12)
+                    .width(Length::Fixed(100.0)),
+            );
+
+        // One density per row, four rows to a line.
+        let densities = self.densities(track, row_names.len());
+        let density_rows = densities.chunks(4).enumerate().fold(
+            Column::new().spacing(5),
+            |column, (line, chunk)| {
+                let row = chunk.iter().enumerate().fold(
+                    Row::new().spacing(10),
+                    |row, (offset, &density)| {
+                        let index = line * 4 + offset;
+                        row.push(Text::new(format!("{} {}%", row_names[index], density)).size(12))
+                            .push(
+                                slider(0..=100, density, move |density| {
+                                    Message::UpdateDensity(track, index, density)
+                                })
+                                .width(Length::Fixed(80.0)),
+                            )
+                    },
+                );
+                column.push(row)
+            },
+        );
+
+        let tool_row = Row::new()
+            .spacing(10)
+            .push(tool_button(
+                "Random fill",
+                PatternTool::RandomFill { densities },
+            ))
+            .push(Text::new(format!("Amount {}%", self.amount)).size(12))
+            .push(slider(0..=100, self.amount, Message::UpdateAmount).width(Length::Fixed(80.0)))
+            .push(tool_button(
+                "Mutate",
+                PatternTool::Mutate {
+                    amount: self.amount,
+                },
+            ))
+            .push(tool_button("<<", PatternTool::Rotate { right: false }))
+            .push(tool_button(">>", PatternTool::Rotate { right: true }))
+            .push(tool_button("Reverse", PatternTool::Reverse))
+            .push(Text::new(format!("Vel {}%", self.velocity)).size(12))
+            .push(
+                slider(0..=100, self.velocity, Message::UpdateVelocity).width(Length::Fixed(80.0)),
+            )
+            .push(Text::new(format!("Time {}%", self.timing)).size(12))
+            .push(slider(0..=100, self.timing, Message::UpdateTiming).width(Length::Fixed(80.0)))
+            .push(tool_button(
+                "Humanize",
+                PatternTool::Humanize {
+                    velocity: self.velocity,
+                    timing: self.timing,
+                },
+            ));
+
+        Column::new()
+            .spacing(5)
+            .push(selection_row)
+            .push(density_rows)
+            .push(tool_row)
+            .into()
+    }
+}
+
+fn tool_button(label: &str, tool: PatternTool) -> button::Button<'_, Message> {
+    button(Text::new(label).size(12)).on_press(Message::Apply(tool))
+}
@@ -1 +1,2 @@
+pub mod sample_root_settings;
 pub mod theme_settings;
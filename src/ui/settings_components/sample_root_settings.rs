@@ -0,0 +1,43 @@
+use iced::{
+    widget::{button, text_input, Column, Row, Text},
+    Length,
+};
+
+use crate::ui::drum_machine::{DrumMachine, Message};
+
+impl DrumMachine {
+    pub fn create_sample_root_settings(&self) -> Column<'_, Message> {
+        let root_input = Row::new()
+            .push(
+                text_input("Folder with drum kits", &self.sample_root_input)
+                    .on_input(Message::SampleRootInputChanged)
+                    .on_submit(Message::AddSampleRoot)
+                    .width(Length::Fixed(300.0)),
+            )
+            .push(button("Add").on_press(Message::AddSampleRoot))
+            .push(button("Rescan Kits").on_press(Message::RescanKits))
+            .spacing(10);
+
+        let column = Column::new()
+            .push(Text::new("Sample Folders").size(20))
+            .push(Text::new(format!(
+                "{} (built in), {} kits found",
+                self.root_sample_folder,
+                self.sample_folders_options.len()
+            )))
+            .spacing(10);
+
+        self.sample_roots
+            .iter()
+            .enumerate()
+            .fold(column, |column, (index, root)| {
+                column.push(
+                    Row::new()
+                        .push(Text::new(root.clone()).width(Length::Fixed(300.0)))
+                        .push(button("Remove").on_press(Message::RemoveSampleRoot(index)))
+                        .spacing(10),
+                )
+            })
+            .push(root_input)
+    }
+}
@@ -13,13 +13,14 @@ impl SettingsPage {
         SettingsPage { is_dark_theme }
     }
 
-    pub fn view(&self) -> Element<Message> {
+    pub fn view<'a>(&'a self, sample_root_settings: Element<'a, Message>) -> Element<'a, Message> {
         let theme_checkbox =
             checkbox("Dark Theme", self.is_dark_theme).on_toggle(Message::ToggleTheme);
         let content = Column::new()
             .spacing(20)
             .push(Text::new("Settings").size(30))
-            .push(theme_checkbox);
+            .push(theme_checkbox)
+            .push(sample_root_settings);
 
         Container::new(content)
             .width(Length::Fill)
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub const MAX_PROBABILITY: u8 = 100;
+
+/// Elektron-style trig conditions deciding in which loops a step may play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TrigCondition {
+    #[default]
+    Always,
+    /// Plays on loop `a` of every `b` loops, counted from 1.
+    Ratio { a: u8, b: u8 },
+    /// Plays only if the previous active step of the same row played.
+    Previous,
+    /// Plays only while fill mode is on.
+    Fill,
+}
+
+impl fmt::Display for TrigCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrigCondition::Always => write!(f, "-"),
+            TrigCondition::Ratio { a, b } => write!(f, "{}:{}", a, b),
+            TrigCondition::Previous => write!(f, "PRE"),
+            TrigCondition::Fill => write!(f, "FILL"),
+        }
+    }
+}
+
+impl TrigCondition {
+    pub fn options() -> Vec<TrigCondition> {
+        let mut options = vec![TrigCondition::Always];
+        for b in [2, 4] {
+            options.extend((1..=b).map(|a| TrigCondition::Ratio { a, b }));
+        }
+        options.push(TrigCondition::Previous);
+        options.push(TrigCondition::Fill);
+        options
+    }
+
+    /// The condition after this one in `options`, wrapping around.
+    pub fn next(self) -> TrigCondition {
+        let options = TrigCondition::options();
+        let index = options
+            .iter()
+            .position(|option| *option == self)
+            .unwrap_or(0);
+        options[(index + 1) % options.len()]
+    }
+}
+
+/// When an active step actually plays: its chance in percent and its condition.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StepTrigger {
+    #[serde(default = "default_probability")]
+    pub probability: u8,
+    #[serde(default)]
+    pub condition: TrigCondition,
+}
+
+fn default_probability() -> u8 {
+    MAX_PROBABILITY
+}
+
+impl Default for StepTrigger {
+    fn default() -> Self {
+        StepTrigger {
+            probability: MAX_PROBABILITY,
+            condition: TrigCondition::Always,
+        }
+    }
+}
+
+/// A sequencer step that can be switched on and carries a trigger.
+pub trait Step {
+    fn is_active(&self) -> bool;
+    fn set_active(&mut self, active: bool);
+    fn trigger(&self) -> StepTrigger;
+    fn nudge(&self) -> i8;
+    fn set_nudge(&mut self, nudge: i8);
+}
@@ -1,20 +1,41 @@
 use iced::{
-    widget::{Button, Checkbox, Column, PickList, Row, Text},
+    widget::{checkbox, pick_list, slider, Button, Checkbox, Column, PickList, Row, Text},
     Command, Element, Length, Renderer, Theme,
 };
-use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
-use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
 
-use super::{drum_machine::SequenceScale, drum_machine_components::sequence_view, SequenceState};
+use crate::scripts::{
+    audio_engine::engine::EngineHandle,
+    groove::{Groove, GrooveTemplate},
+    synth::{
+        envelope::{Envelope, MAX_ATTACK_MS, MAX_DECAY_MS, MAX_RELEASE_MS},
+        filter::{Filter, FilterMode, MAX_CUTOFF_HZ, MAX_ENVELOPE_OCTAVES, MIN_CUTOFF_HZ},
+        lfo::Modulation,
+        oscillator::{Oscillator, Waveform, MAX_PULSE_WIDTH, MIN_PULSE_WIDTH},
+        patch::SynthPatch,
+    },
+    transport::{Transport, TransportTrack},
+};
+
+use super::{
+    drum_machine::SequenceScale,
+    drum_machine_components::sequence_view,
+    groove_controls::create_track_groove_controls,
+    modulation_controls::create_modulation_controls,
+    step_trigger::StepTrigger,
+    track_timing::{length_options, TrackOption},
+    SequenceState,
+};
 
 pub struct Synth {
     sequence_state: Arc<Mutex<SequenceState>>,
     pub notes: Vec<String>,
-    pub is_playing: Arc<Mutex<bool>>,
-    play_sender: mpsc::Sender<bool>,
+    pub transport: Arc<Mutex<Transport>>,
+    engine: EngineHandle,
     pub sequence_scale_options: Vec<SequenceScale>,
+    pub groove_templates: Vec<GrooveTemplate>,
+    /// The note row whose probabilities and conditions are shown below it.
+    pub trigger_lane: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,10 +46,28 @@ pub enum Message {
     PlaybackFinished,
     ChangeSequenceScale(SequenceScale),
     ChangeFrequency(f32),
+    UpdatePatch(SynthPatch),
+    UpdateOscillator(Oscillator),
+    UpdateEnvelope(Envelope),
+    UpdateFilter(Filter),
+    UpdateModulation(Modulation),
+    /// Sets the synth's own length, `None` follows the pattern length.
+    UpdateLength(Option<u32>),
+    UpdateGroove(Option<Groove>),
+    /// Sets the probability and condition of a note step.
+    UpdateTrigger(usize, usize, StepTrigger),
+    ToggleTriggerLane(usize),
+    /// Sent when a probability or sound slider is released.
+    EditFinished,
 }
 
 impl Synth {
-    pub fn new(sequence_state: Arc<Mutex<SequenceState>>) -> Self {
+    pub fn new(
+        sequence_state: Arc<Mutex<SequenceState>>,
+        transport: Arc<Mutex<Transport>>,
+        engine: EngineHandle,
+        groove_templates: Vec<GrooveTemplate>,
+    ) -> Self {
         let notes: Vec<_> = vec![
             "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
         ]
@@ -36,47 +75,16 @@ impl Synth {
         .map(String::from)
         .collect();
 
-        let (play_sender, play_receiver) = mpsc::channel();
-
-        let is_playing = Arc::new(Mutex::new(false));
-        let is_playing_clone = is_playing.clone();
-        let sequence_state_clone = sequence_state.clone();
-        let sequence_scale_options = vec![
-            SequenceScale::OneFourth,
-            SequenceScale::OneEighth,
-            SequenceScale::OneSixteenth,
-        ];
-
-        thread::spawn(move || {
-            let mut stream_option: Option<(OutputStream, OutputStreamHandle)> = None;
-            loop {
-                if let Ok(should_play) = play_receiver.recv() {
-                    if should_play {
-                        *is_playing_clone.lock().unwrap() = true;
-                        if stream_option.is_none() {
-                            stream_option = OutputStream::try_default().ok();
-                        }
-                        if let Some((_, ref stream_handle)) = stream_option {
-                            Self::play_sequence(
-                                sequence_state_clone.clone(),
-                                is_playing_clone.clone(),
-                                stream_handle,
-                            );
-                        }
-                    } else {
-                        *is_playing_clone.lock().unwrap() = false;
-                        stream_option = None;
-                    }
-                }
-            }
-        });
+        let sequence_scale_options = SequenceScale::ALL.to_vec();
 
         Synth {
             sequence_state,
             notes,
-            is_playing,
-            play_sender,
+            transport,
+            engine,
             sequence_scale_options,
+            groove_templates,
+            trigger_lane: None,
         }
     }
 
@@ -87,38 +95,97 @@ impl Synth {
                 sequence_state.frequency = frequency;
                 return Command::none();
             }
+            Message::UpdatePatch(patch) => {
+                self.sequence_state.lock().unwrap().patch = patch;
+                Command::none()
+            }
+            Message::UpdateOscillator(oscillator) => {
+                self.sequence_state.lock().unwrap().oscillator = oscillator;
+                Command::none()
+            }
+            Message::UpdateEnvelope(envelope) => {
+                self.sequence_state.lock().unwrap().envelope = envelope;
+                Command::none()
+            }
+            Message::UpdateFilter(filter) => {
+                self.sequence_state.lock().unwrap().filter = filter;
+                Command::none()
+            }
+            Message::UpdateModulation(modulation) => {
+                self.sequence_state.lock().unwrap().modulation = modulation;
+                Command::none()
+            }
+            Message::UpdateLength(length) => {
+                self.sequence_state.lock().unwrap().set_synth_length(length);
+                Command::none()
+            }
+            Message::UpdateGroove(groove) => {
+                self.sequence_state.lock().unwrap().synth_groove = groove;
+                Command::none()
+            }
             Message::ChangeSequenceScale(new_sequence_size) => {
                 self.sequence_state.lock().unwrap().synth_scale = new_sequence_size;
                 return Command::none();
             }
-            Message::ToggleNote(note_index, beat_index, checked) => {
+            Message::UpdateTrigger(note_index, beat_index, trigger) => {
                 let mut sequence_state = self.sequence_state.lock().unwrap();
-                sequence_state.note_pattern[note_index][beat_index] = checked;
+                if let Some(note_step) = sequence_state
+                    .note_pattern
+                    .get_mut(note_index)
+                    .and_then(|row| row.get_mut(beat_index))
+                {
+                    note_step.trigger = trigger;
+                }
                 Command::none()
             }
-            Message::PlaySequence => {
-                let mut is_playing = self.is_playing.lock().unwrap();
-                if !*is_playing {
-                    *is_playing = true;
-                    self.play_sender.send(true).unwrap();
-                }
+            Message::ToggleTriggerLane(note_index) => {
+                self.trigger_lane = (self.trigger_lane != Some(note_index)).then_some(note_index);
+                Command::none()
+            }
+            // Handled by the history in `MainUi`.
+            Message::EditFinished => Command::none(),
+            Message::ToggleNote(note_index, beat_index, checked) => {
+                let mut sequence_state = self.sequence_state.lock().unwrap();
+                sequence_state.note_pattern[note_index][beat_index].active = checked;
                 Command::none()
             }
-            Message::StopSequence => {
-                let mut is_playing = self.is_playing.lock().unwrap();
-                *is_playing = false;
-                self.play_sender.send(false).unwrap();
+            Message::PlaySequence => {
+                self.transport
+                    .lock()
+                    .unwrap()
+                    .play(TransportTrack::Synth, self.engine.lookahead_frame());
                 Command::none()
             }
-            Message::PlaybackFinished => {
-                let mut is_playing = self.is_playing.lock().unwrap();
-                *is_playing = false;
+            Message::StopSequence | Message::PlaybackFinished => {
+                self.transport.lock().unwrap().stop(TransportTrack::Synth);
                 Command::none()
             }
         }
     }
 
     pub fn view(&self) -> Element<Message> {
+        // Read before the transport is locked, the sequencer thread locks the transport
+        // first and the state second.
+        let (synth_length, step_ticks) = {
+            let sequence_state = self.sequence_state.lock().unwrap();
+            (
+                sequence_state.synth_row_length(),
+                sequence_state.synth_step_ticks(),
+            )
+        };
+        let transport = self.transport.lock().unwrap();
+        let position = match transport.step_at(TransportTrack::Synth, self.engine.now(), step_ticks)
+        {
+            Some(step) => format!("{}/{}", step % synth_length.max(1) as u64 + 1, synth_length),
+            None => format!("{} steps", synth_length),
+        };
+        let play_button = if transport.is_playing(TransportTrack::Synth) {
+            Button::new(Text::new("Stop")).on_press(Message::StopSequence)
+        } else {
+            Button::new(Text::new("Play")).on_press(Message::PlaySequence)
+        };
+        drop(transport);
+
         let sequence_state = self.sequence_state.lock().unwrap();
         let sequence_length_pick_list: iced::widget::PickList<
             '_,
@@ -140,17 +207,34 @@ impl Synth {
                 sequence_state.frequency,
                 Message::ChangeFrequency,
             );
-        let play_button = if *self.is_playing.lock().unwrap() {
-            Button::new(Text::new("Stop")).on_press(Message::StopSequence)
-        } else {
-            Button::new(Text::new("Play")).on_press(Message::PlaySequence)
-        };
-
+        let groove_controls = create_track_groove_controls(
+            sequence_state.synth_groove.as_ref(),
+            &sequence_state.groove,
+            &self.groove_templates,
+            Message::UpdateGroove,
+            Message::EditFinished,
+        );
+        let length_pick_list = pick_list(
+            length_options(),
+            Some(TrackOption(sequence_state.synth_length)),
+            |length| Message::UpdateLength(length.0),
+        );
         let sequence_view = Self::create_synth_sequence(&self, &sequence_state);
+        let sound_controls = create_sound_controls(&sequence_state);
 
         Column::new()
-            .push(Row::new().push(sequence_length_pick_list).push(play_button))
+            .push(
+                Row::new()
+                    .push(sequence_length_pick_list)
+                    .push(Text::new("Length:"))
+                    .push(length_pick_list)
+                    .push(play_button)
+                    .push(Text::new(position))
+                    .push(groove_controls)
+                    .spacing(10),
+            )
             .push(sequence_view)
+            .push(sound_controls)
             .push(
                 Row::new()
                     .push(Text::new("frequency: "))
@@ -162,3 +246,252 @@ impl Synth {
             .into()
     }
 }
+
+/// The patch picker, with the oscillator, envelope, filter and LFO editors below it
+/// only while a patch that plays them is picked.
+fn create_sound_controls<'a>(sequence_state: &SequenceState) -> Column<'a, Message> {
+    let column =
+        Column::new()
+            .spacing(10)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Text::new("Patch:"))
+                    .push(pick_list(
+                        SynthPatch::ALL,
+                        Some(sequence_state.patch),
+                        Message::UpdatePatch,
+                    )),
+            );
+    if !sequence_state.patch.uses_sound_settings() {
+        return column.push(
+            Text::new(
+                "Built-in patch, pick Custom to edit the oscillator, envelope, filter and LFOs.",
+            )
+            .size(12),
+        );
+    }
+    column
+        .push(create_oscillator_controls(sequence_state.oscillator))
+        .push(create_envelope_controls(
+            "Envelope",
+            sequence_state.envelope,
+            Message::UpdateEnvelope,
+        ))
+        .push(create_filter_controls(sequence_state.filter))
+        .push(create_modulation_controls(
+            &sequence_state.modulation,
+            Message::UpdateModulation,
+            Message::EditFinished,
+        ))
+}
+
+/// Waveform, pulse width and band limiting of the synth's oscillator.
+fn create_oscillator_controls<'a>(oscillator: Oscillator) -> Row<'a, Message> {
+    let mut row = Row::new()
+        .spacing(10)
+        .push(Text::new("Wave:"))
+        .push(pick_list(
+            Waveform::ALL,
+            Some(oscillator.waveform),
+            move |waveform| {
+                Message::UpdateOscillator(Oscillator {
+                    waveform,
+                    ..oscillator
+                })
+            },
+        ));
+    if oscillator.waveform == Waveform::Square {
+        row = row
+            .push(Text::new(format!(
+                "Pulse width: {:.0}%",
+                oscillator.pulse_width * 100.0
+            )))
+            .push(
+                slider(
+                    MIN_PULSE_WIDTH..=MAX_PULSE_WIDTH,
+                    oscillator.pulse_width,
+                    move |pulse_width| {
+                        Message::UpdateOscillator(Oscillator {
+                            pulse_width,
+                            ..oscillator
+                        })
+                    },
+                )
+                .step(0.01)
+                .on_release(Message::EditFinished)
+                .width(Length::Fixed(150.0)),
+            );
+    }
+    row.push(
+        checkbox("Band-limited", oscillator.band_limited).on_toggle(move |band_limited| {
+            Message::UpdateOscillator(Oscillator {
+                band_limited,
+                ..oscillator
+            })
+        }),
+    )
+}
+
+/// Attack, decay, sustain and release sliders for one of the synth's envelopes.
+fn create_envelope_controls<'a>(
+    label: &str,
+    envelope: Envelope,
+    on_change: impl Fn(Envelope) -> Message + Copy + 'a,
+) -> Row<'a, Message> {
+    let stage = |label: String, slider| {
+        Column::new()
+            .spacing(5)
+            .push(Text::new(label).size(12))
+            .push(slider)
+    };
+    Row::new()
+        .spacing(20)
+        .push(Text::new(format!("{}:", label)))
+        .push(stage(
+            format!("Attack: {:.0} ms", envelope.attack_ms),
+            slider(0.0..=MAX_ATTACK_MS, envelope.attack_ms, move |attack_ms| {
+                on_change(Envelope {
+                    attack_ms,
+                    ..envelope
+                })
+            })
+            .step(1.0)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(120.0)),
+        ))
+        .push(stage(
+            format!("Decay: {:.0} ms", envelope.decay_ms),
+            slider(0.0..=MAX_DECAY_MS, envelope.decay_ms, move |decay_ms| {
+                on_change(Envelope {
+                    decay_ms,
+                    ..envelope
+                })
+            })
+            .step(1.0)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(120.0)),
+        ))
+        .push(stage(
+            format!("Sustain: {:.0}%", envelope.sustain * 100.0),
+            slider(0.0..=1.0, envelope.sustain, move |sustain| {
+                on_change(Envelope {
+                    sustain,
+                    ..envelope
+                })
+            })
+            .step(0.01)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(120.0)),
+        ))
+        .push(stage(
+            format!("Release: {:.0} ms", envelope.release_ms),
+            slider(
+                0.0..=MAX_RELEASE_MS,
+                envelope.release_ms,
+                move |release_ms| {
+                    on_change(Envelope {
+                        release_ms,
+                        ..envelope
+                    })
+                },
+            )
+            .step(1.0)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(120.0)),
+        ))
+}
+
+/// Mode, cutoff, resonance, key tracking and envelope depth of the synth's filter, with
+/// the filter envelope below them while the filter is on.
+fn create_filter_controls<'a>(filter: Filter) -> Column<'a, Message> {
+    let mut row = Row::new().spacing(10).push(
+        checkbox("Filter", filter.enabled)
+            .on_toggle(move |enabled| Message::UpdateFilter(Filter { enabled, ..filter })),
+    );
+    if !filter.enabled {
+        return Column::new().push(row);
+    }
+
+    // The cutoff slider moves in octaves, so it spends as long on the lows as the highs.
+    let min_octave = MIN_CUTOFF_HZ.log2();
+    let max_octave = MAX_CUTOFF_HZ.log2();
+    row = row
+        .push(pick_list(FilterMode::ALL, Some(filter.mode), move |mode| {
+            Message::UpdateFilter(Filter { mode, ..filter })
+        }))
+        .push(Text::new(format!("Cutoff: {:.0} Hz", filter.cutoff_hz)))
+        .push(
+            slider(
+                min_octave..=max_octave,
+                filter.cutoff_hz.log2(),
+                move |octave| {
+                    Message::UpdateFilter(Filter {
+                        cutoff_hz: 2.0_f32.powf(octave),
+                        ..filter
+                    })
+                },
+            )
+            .step(0.01)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(150.0)),
+        )
+        .push(Text::new(format!(
+            "Resonance: {:.0}%",
+            filter.resonance * 100.0
+        )))
+        .push(
+            slider(0.0..=1.0, filter.resonance, move |resonance| {
+                Message::UpdateFilter(Filter {
+                    resonance,
+                    ..filter
+                })
+            })
+            .step(0.01)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(100.0)),
+        )
+        .push(Text::new(format!(
+            "Key track: {:.0}%",
+            filter.key_tracking * 100.0
+        )))
+        .push(
+            slider(0.0..=1.0, filter.key_tracking, move |key_tracking| {
+                Message::UpdateFilter(Filter {
+                    key_tracking,
+                    ..filter
+                })
+            })
+            .step(0.01)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(100.0)),
+        )
+        .push(Text::new(format!(
+            "Env depth: {:+.1} oct",
+            filter.envelope_octaves
+        )))
+        .push(
+            slider(
+                -MAX_ENVELOPE_OCTAVES..=MAX_ENVELOPE_OCTAVES,
+                filter.envelope_octaves,
+                move |envelope_octaves| {
+                    Message::UpdateFilter(Filter {
+                        envelope_octaves,
+                        ..filter
+                    })
+                },
+            )
+            .step(0.1)
+            .on_release(Message::EditFinished)
+            .width(Length::Fixed(100.0)),
+        );
+
+    Column::new()
+        .spacing(10)
+        .push(row)
+        .push(create_envelope_controls(
+            "Filter envelope",
+            filter.envelope,
+            move |envelope| Message::UpdateFilter(Filter { envelope, ..filter }),
+        ))
+}
@@ -2,12 +2,14 @@ use std::sync::{Arc, MutexGuard};
 
 use iced::{
     futures::lock::Mutex,
-    widget::{Checkbox, Column, Row, Text},
+    theme,
+    widget::{button, Checkbox, Column, Row, Text},
     Length,
 };
 
 use crate::ui::{
     synth::{Message, Synth},
+    trigger_lane::create_trigger_lane,
     SequenceState,
 };
 
@@ -24,24 +26,43 @@ impl Synth {
                 .enumerate()
                 .fold(Column::new(), |column, (note_index, note_name)| {
                     let beat_row =
-                        (0..sequence_state.sequence_length).fold(Row::new(), |row, beat_index| {
+                        (0..note_pattern[note_index].len()).fold(Row::new(), |row, beat_index| {
                             row.push(
-                                Checkbox::new("", note_pattern[note_index][beat_index as usize])
+                                Checkbox::new("", note_pattern[note_index][beat_index].active)
                                     .on_toggle(move |checked| {
-                                        Message::ToggleNote(
-                                            note_index,
-                                            beat_index as usize,
-                                            checked,
-                                        )
+                                        Message::ToggleNote(note_index, beat_index, checked)
                                     }),
                             )
                         });
 
-                    column.push(
+                    let show_triggers = self.trigger_lane == Some(note_index);
+                    let trigger_button = button(Text::new("Trig").size(15))
+                        .on_press(Message::ToggleTriggerLane(note_index))
+                        .style(if show_triggers {
+                            theme::Button::Primary
+                        } else {
+                            theme::Button::Text
+                        });
+
+                    let column = column.push(
                         Row::new()
                             .push(Text::new(note_name).width(Length::Fixed(30.0)))
-                            .push(beat_row),
-                    )
+                            .push(beat_row)
+                            .push(trigger_button),
+                    );
+                    if show_triggers {
+                        column.push(create_trigger_lane(
+                            &note_pattern[note_index],
+                            note_pattern[note_index].len() as u32,
+                            30.0,
+                            move |beat_index, trigger| {
+                                Message::UpdateTrigger(note_index, beat_index, trigger)
+                            },
+                            Message::EditFinished,
+                        ))
+                    } else {
+                        column
+                    }
                 });
         return sequence_view;
     }
@@ -1,7 +1,11 @@
-use super::{drum_machine, MainUi, Message, Page};
+use super::{
+    arranger_page, arranger_page::PatternOption, drum_machine,
+    groove_controls::create_groove_controls, MainUi, Message, Page,
+};
+use crate::scripts::transport::{TimeSignature, TransportState, TransportTrack};
 use iced::{
     theme,
-    widget::{button, checkbox, slider, Button, Column, Row, Text},
+    widget::{button, checkbox, pick_list, slider, text_input, Button, Column, Row, Text},
     Length, Renderer, Theme,
 };
 
@@ -11,10 +15,32 @@ impl MainUi {
         let playback_state = self.drum_machine.playback_state.lock().unwrap();
         let sequence_length = state.sequence_length;
         let bpm = state.bpm;
-        let drum_sequence_on = state.drum_sequence_on.clone();
-        let synth_sequence_on = state.synth_sequence_on.clone();
-        let synth_drum_sequence_on = self.synth.is_playing.lock().unwrap().clone();
+        let time_signature = state.time_signature;
+        let seed = state.seed;
+        let fill = state.fill;
+        let pattern_options: Vec<PatternOption> = state
+            .patterns
+            .iter()
+            .enumerate()
+            .map(|(index, pattern)| PatternOption {
+                index,
+                name: pattern.name.clone(),
+            })
+            .collect();
+        let selected_pattern = pattern_options.get(state.current_pattern).cloned();
+        let groove_controls = create_groove_controls(
+            &state.groove,
+            &self.groove_templates,
+            Message::UpdateGroove,
+            Message::EditFinished,
+        );
         drop(state);
+        let transport = self.transport.lock().unwrap();
+        let drum_sequence_on = transport.is_playing(TransportTrack::Drums);
+        let synth_sequence_on = transport.is_playing(TransportTrack::Synth);
+        let transport_state = transport.state;
+        let position = transport.position(self.engine.now());
+        drop(transport);
         let interface_buttons = Row::new()
             .push(
                 button("Drum Machine")
@@ -43,6 +69,15 @@ impl MainUi {
                         theme::Button::Secondary
                     }),
             )
+            .push(
+                button("Mixer")
+                    .on_press(Message::ChangePage(Page::Mixer))
+                    .style(if self.current_page == Page::Mixer {
+                        theme::Button::Primary
+                    } else {
+                        theme::Button::Secondary
+                    }),
+            )
             .push(
                 button("Settings")
                     .on_press(Message::ChangePage(Page::Settings))
@@ -56,25 +91,87 @@ impl MainUi {
 
         let is_checked = drum_sequence_on && synth_sequence_on;
         let play_button: iced::widget::Button<'_, Message, Theme, Renderer> =
-            if drum_sequence_on && synth_drum_sequence_on {
+            if drum_sequence_on && synth_sequence_on {
                 Button::new(Text::new("Stop Both")).on_press(Message::StartBothSequences(false))
             } else {
                 Button::new(Text::new("Play Both")).on_press(Message::StartBothSequences(true))
             };
-        let top_bar = Column::new().push(interface_buttons).push(
-            Row::new()
-                .push(Text::new(format!("Sequence Length: {}", sequence_length)))
-                .push(slider(1..=64, sequence_length, |value| {
-                    Message::UpdateSequenceLength(value)
-                }))
-                .push(Text::new(format!("BPM: {}", bpm)))
-                .push(slider(60..=240, bpm, |value| Message::UpdateBpm(value)))
-                .push(
-                    play_button, // checkbox("Play Both", is_checked)
-                                 //     .on_toggle(move |value| Message::StartBothSequences(value)),
-                )
-                .spacing(20),
+        let pause_button = Button::new(Text::new(if transport_state == TransportState::Paused {
+            "Resume"
+        } else {
+            "Pause"
+        }))
+        .on_press_maybe(
+            (transport_state != TransportState::Stopped).then_some(Message::PauseTransport),
         );
+        let project_row = Row::new()
+            .push(
+                text_input("Project file", &self.project_path_input)
+                    .on_input(Message::ProjectPathChanged)
+                    .width(Length::Fixed(300.0)),
+            )
+            .push(button("Open").on_press(Message::OpenProject))
+            .push(button("Save").on_press(Message::SaveProject))
+            .push(button("Save As").on_press(Message::SaveProjectAs))
+            .push(button("Undo").on_press_maybe(self.history.can_undo().then_some(Message::Undo)))
+            .push(button("Redo").on_press_maybe(self.history.can_redo().then_some(Message::Redo)))
+            .push(Text::new(self.project_status.clone().unwrap_or_default()))
+            .spacing(10);
+        let top_bar = Column::new()
+            .push(interface_buttons)
+            .push(project_row)
+            .push(
+                Row::new()
+                    .push(Text::new(format!("Sequence Length: {}", sequence_length)))
+                    .push(
+                        slider(1..=64, sequence_length, |value| {
+                            Message::UpdateSequenceLength(value)
+                        })
+                        .on_release(Message::EditFinished),
+                    )
+                    .push(Text::new(format!("BPM: {}", bpm)))
+                    .push(
+                        slider(60..=240, bpm, |value| Message::UpdateBpm(value))
+                            .on_release(Message::EditFinished),
+                    )
+                    .push(Text::new("Time:"))
+                    .push(pick_list(
+                        TimeSignature::OPTIONS,
+                        Some(time_signature),
+                        Message::UpdateTimeSignature,
+                    ))
+                    .push(
+                        play_button, // checkbox("Play Both", is_checked)
+                                     //     .on_toggle(move |value| Message::StartBothSequences(value)),
+                    )
+                    .push(pause_button)
+                    .push(Text::new(format!("Position: {}", position)))
+                    .push(Text::new("Pattern:"))
+                    .push(pick_list(pattern_options, selected_pattern, |option| {
+                        Message::ArrangerMessage(arranger_page::Message::SelectPattern(
+                            option.index,
+                        ))
+                    }))
+                    .spacing(20),
+            )
+            .push(
+                Row::new()
+                    .push(groove_controls)
+                    .push(checkbox("Fill", fill).on_toggle(Message::ToggleFill))
+                    .push(Text::new("Seed:"))
+                    .push(
+                        text_input("Seed", &seed.to_string())
+                            .on_input(move |input| {
+                                Message::UpdateSeed(if input.is_empty() {
+                                    0
+                                } else {
+                                    input.parse().unwrap_or(seed)
+                                })
+                            })
+                            .width(Length::Fixed(200.0)),
+                    )
+                    .spacing(20),
+            );
 
         top_bar
     }
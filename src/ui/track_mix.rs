@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use crate::scripts::audio_engine::mixer::{MixGraph, Route, StripGains, MAX_BUSES};
+
+use super::SequenceState;
+
+/// The bottom of the gain slider, a track at this gain is silent.
+pub const MIN_GAIN_DB: f32 = -60.0;
+pub const MAX_GAIN_DB: f32 = 6.0;
+
+/// The channel strip of a drum track, the synth or a bus: mute, solo, gain and pan.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct TrackMix {
+    pub mute: bool,
+    pub solo: bool,
+    pub gain_db: f32,
+    /// -1 is hard left, 1 hard right.
+    pub pan: f32,
+    /// The group bus the strip feeds, `None` feeds the master. Unused on buses.
+    #[serde(default)]
+    pub bus: Option<usize>,
+}
+
+impl TrackMix {
+    pub fn amplitude(&self) -> f32 {
+        db_to_amplitude(self.gain_db)
+    }
+}
+
+pub fn db_to_amplitude(gain_db: f32) -> f32 {
+    if gain_db <= MIN_GAIN_DB {
+        0.0
+    } else {
+        10.0_f32.powf(gain_db.min(MAX_GAIN_DB) / 20.0)
+    }
+}
+
+pub fn pan_label(pan: f32) -> String {
+    let amount = (pan.abs() * 100.0).round();
+    if amount == 0.0 {
+        "C".to_string()
+    } else if pan < 0.0 {
+        format!("L{}", amount)
+    } else {
+        format!("R{}", amount)
+    }
+}
+
+/// A group bus, summing the strips routed to it before the master.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bus {
+    pub name: String,
+    pub mix: TrackMix,
+}
+
+/// The synth strip, the group buses and the master. The drum strips live in
+/// `SequenceState::drum_mix`, next to the rows they belong to.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct MixerState {
+    #[serde(default)]
+    pub synth: TrackMix,
+    #[serde(default)]
+    pub buses: Vec<Bus>,
+    #[serde(default)]
+    pub master_gain_db: f32,
+}
+
+impl SequenceState {
+    pub fn drum_mix(&self, index: usize) -> TrackMix {
+        self.drum_mix.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn set_drum_mix(&mut self, index: usize, mix: TrackMix) {
+        if self.drum_mix.len() <= index {
+            self.drum_mix.resize(index + 1, TrackMix::default());
+        }
+        self.drum_mix[index] = mix;
+    }
+
+    pub fn add_bus(&mut self) {
+        if self.mixer.buses.len() < MAX_BUSES {
+            let name = format!("Bus {}", self.mixer.buses.len() + 1);
+            self.mixer.buses.push(Bus {
+                name,
+                mix: TrackMix::default(),
+            });
+        }
+    }
+
+    /// Removes a bus, the strips it fed go to the master.
+    pub fn remove_bus(&mut self, index: usize) {
+        if index >= self.mixer.buses.len() {
+            return;
+        }
+        self.mixer.buses.remove(index);
+        let strips = self
+            .drum_mix
+            .iter_mut()
+            .chain(std::iter::once(&mut self.mixer.synth));
+        for mix in strips {
+            mix.bus = match mix.bus {
+                Some(bus) if bus == index => None,
+                Some(bus) if bus > index => Some(bus - 1),
+                bus => bus,
+            };
+        }
+    }
+
+    /// The gains the engine mixes with. A muted strip is silent, and once any drum
+    /// track or the synth is soloed only the soloed ones are heard.
+    pub fn mix_graph(&self) -> MixGraph {
+        let drum_mix: Vec<TrackMix> = (0..self.beat_pattern.len())
+            .map(|index| self.drum_mix(index))
+            .collect();
+        let any_solo = drum_mix
+            .iter()
+            .chain(std::iter::once(&self.mixer.synth))
+            .any(|mix| mix.solo);
+        let buses = self.mixer.buses.len();
+        let strip = |mix: &TrackMix| {
+            let audible = !mix.mute && (mix.solo || !any_solo);
+            let route = match mix.bus {
+                Some(bus) if bus < buses => Route::Bus(bus),
+                _ => Route::Master,
+            };
+            StripGains::new(if audible { mix.amplitude() } else { 0.0 }, mix.pan, route)
+        };
+
+        MixGraph {
+            drums: drum_mix.iter().map(strip).collect(),
+            synth: strip(&self.mixer.synth),
+            buses: self
+                .mixer
+                .buses
+                .iter()
+                .map(|bus| {
+                    let amplitude = if bus.mix.mute {
+                        0.0
+                    } else {
+                        bus.mix.amplitude()
+                    };
+                    StripGains::new(amplitude, bus.mix.pan, Route::Master)
+                })
+                .collect(),
+            master: db_to_amplitude(self.mixer.master_gain_db),
+        }
+    }
+}
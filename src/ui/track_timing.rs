@@ -0,0 +1,181 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{drum_machine::SequenceScale, drum_step::DrumStep, note_step::NoteStep, SequenceState};
+
+pub const MAX_TRACK_LENGTH: u32 = 64;
+/// The longest a pattern of tracks with different lengths plays before it counts as
+/// one pass, even if its tracks have not lined up again by then.
+pub const MAX_PATTERN_BARS: u32 = 64;
+
+/// The length and step resolution of one drum track. A track without its own
+/// value follows the pattern's `sequence_length` and `drum_scale`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TrackTiming {
+    pub length: Option<u32>,
+    pub scale: Option<SequenceScale>,
+}
+
+/// Pick list entry for a track value, `None` follows the pattern's value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackOption<T>(pub Option<T>);
+
+impl<T: fmt::Display> fmt::Display for TrackOption<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(value) => write!(f, "{}", value),
+            None => write!(f, "Pattern"),
+        }
+    }
+}
+
+pub fn length_options() -> Vec<TrackOption<u32>> {
+    std::iter::once(TrackOption(None))
+        .chain((1..=MAX_TRACK_LENGTH).map(|length| TrackOption(Some(length))))
+        .collect()
+}
+
+impl SequenceState {
+    pub fn drum_timing(&self, index: usize) -> TrackTiming {
+        self.drum_timing.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn drum_row_length(&self, index: usize) -> u32 {
+        self.drum_timing(index)
+            .length
+            .unwrap_or(self.sequence_length)
+    }
+
+    pub fn drum_row_scale(&self, index: usize) -> SequenceScale {
+        self.drum_timing(index).scale.unwrap_or(self.drum_scale)
+    }
+
+    /// The ticks between two steps of a drum track.
+    pub fn drum_row_step_ticks(&self, index: usize) -> f64 {
+        self.drum_row_scale(index).step_ticks()
+    }
+
+    pub fn synth_row_length(&self) -> u32 {
+        self.synth_length.unwrap_or(self.sequence_length)
+    }
+
+    pub fn synth_step_ticks(&self) -> f64 {
+        self.synth_scale.step_ticks()
+    }
+
+    pub fn set_drum_timing(&mut self, index: usize, timing: TrackTiming) {
+        if self.drum_timing.len() <= index {
+            self.drum_timing.resize(index + 1, TrackTiming::default());
+        }
+        self.drum_timing[index] = timing;
+        self.resize_rows();
+    }
+
+    pub fn set_synth_length(&mut self, length: Option<u32>) {
+        self.synth_length = length;
+        self.resize_rows();
+    }
+
+    /// Gives every row of the working pattern and of the bank one step per step of
+    /// its track.
+    pub fn resize_rows(&mut self) {
+        let drum_lengths: Vec<usize> = (0..self.beat_pattern.len())
+            .map(|index| self.drum_row_length(index) as usize)
+            .collect();
+        for (row, length) in self.beat_pattern.iter_mut().zip(drum_lengths) {
+            row.resize(length, DrumStep::default());
+        }
+        let synth_length = self.synth_row_length() as usize;
+        for row in self.note_pattern.iter_mut() {
+            row.resize(synth_length, NoteStep::default());
+        }
+        self.resize_pattern_bank();
+    }
+
+    /// The length of one pass over a pattern in ticks: until its tracks of different
+    /// lengths line up again, so a 12 step row against a 16 step one plays three bars.
+    /// Capped at `MAX_PATTERN_BARS`, or the longest track if that is longer still.
+    pub fn pattern_ticks(&self) -> f64 {
+        let drum_tracks = (0..self.beat_pattern.len().max(1))
+            .map(|index| (self.drum_row_length(index), self.drum_row_scale(index)));
+        // Every resolution is a whole number of ticks.
+        let track_ticks: Vec<u64> = drum_tracks
+            .chain(std::iter::once((self.synth_row_length(), self.synth_scale)))
+            .map(|(length, scale)| (length as f64 * scale.step_ticks()).round() as u64)
+            .filter(|ticks| *ticks > 0)
+            .collect();
+        let longest = track_ticks.iter().copied().max().unwrap_or_default();
+        let cap = (MAX_PATTERN_BARS as f64 * self.time_signature.bar_ticks()) as u64;
+        let cycle = track_ticks
+            .into_iter()
+            .try_fold(1, |cycle, ticks| {
+                let cycle = lcm(cycle, ticks);
+                (cycle <= cap).then_some(cycle)
+            })
+            .unwrap_or(cap);
+        cycle.max(longest) as f64
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_rows(lengths: &[u32]) -> SequenceState {
+        let mut sequence_state = SequenceState {
+            drum_scale: SequenceScale::OneSixteenth,
+            synth_scale: SequenceScale::OneSixteenth,
+            beat_pattern: vec![Vec::new(); lengths.len()],
+            ..SequenceState::default()
+        };
+        for (index, length) in lengths.iter().enumerate() {
+            sequence_state.set_drum_timing(
+                index,
+                TrackTiming {
+                    length: Some(*length),
+                    scale: None,
+                },
+            );
+        }
+        sequence_state
+    }
+
+    #[test]
+    fn patterns_last_until_their_tracks_line_up() {
+        // 16 sixteenths are a bar of 384 ticks, the 12 step row lines up after three.
+        let sequence_state = with_rows(&[16, 12]);
+        assert_eq!(sequence_state.pattern_ticks(), 3.0 * 384.0);
+        assert_eq!(sequence_state.pattern_bars(), 3);
+
+        let even = with_rows(&[16, 8, 4]);
+        assert_eq!(even.pattern_ticks(), 384.0);
+
+        // Triplets against straight sixteenths.
+        let mut triplets = with_rows(&[16, 12]);
+        triplets.set_drum_timing(
+            1,
+            TrackTiming {
+                length: Some(12),
+                scale: Some(SequenceScale::OneSixteenthTriplet),
+            },
+        );
+        assert_eq!(triplets.pattern_ticks(), 384.0);
+    }
+
+    #[test]
+    fn patterns_that_never_line_up_are_capped() {
+        let sequence_state = with_rows(&[61, 59, 53]);
+        assert_eq!(sequence_state.pattern_bars(), MAX_PATTERN_BARS);
+    }
+}
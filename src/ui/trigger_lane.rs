@@ -0,0 +1,72 @@
+use iced::{
+    widget::{button, container, vertical_slider, Column, Row, Space, Text},
+    Length,
+};
+
+use super::step_trigger::{Step, StepTrigger, MAX_PROBABILITY};
+
+// An unlabeled checkbox is 20px wide plus 10px of label spacing, so each control lines
+// up under its step.
+pub const STEP_WIDTH: f32 = 30.0;
+
+/// Probability sliders and condition buttons for the steps of one row, indented by
+/// `indent` to sit under the row's checkboxes. Pressing a condition button moves on
+/// to the next condition.
+pub fn create_trigger_lane<'a, S: Step, Message: Clone + 'a>(
+    steps: &[S],
+    sequence_length: u32,
+    indent: f32,
+    on_change: impl Fn(usize, StepTrigger) -> Message + Clone + 'a,
+    on_release: Message,
+) -> Column<'a, Message> {
+    let steps = steps.iter().take(sequence_length as usize).enumerate();
+    let probability_row = Row::new().push(Space::with_width(Length::Fixed(indent)));
+    let condition_row = Row::new().push(Space::with_width(Length::Fixed(indent)));
+
+    let (probability_row, condition_row) = steps.fold(
+        (probability_row, condition_row),
+        |(probability_row, condition_row), (beat_index, step)| {
+            let trigger = step.trigger();
+            let on_probability = on_change.clone();
+            let slider = vertical_slider(
+                0..=MAX_PROBABILITY,
+                trigger.probability,
+                move |probability| {
+                    on_probability(
+                        beat_index,
+                        StepTrigger {
+                            probability,
+                            ..trigger
+                        },
+                    )
+                },
+            )
+            .on_release(on_release.clone())
+            .height(Length::Fixed(40.0));
+            let condition_button = button(Text::new(trigger.condition.to_string()).size(10))
+                .on_press(on_change(
+                    beat_index,
+                    StepTrigger {
+                        condition: trigger.condition.next(),
+                        ..trigger
+                    },
+                ))
+                .padding(2)
+                .width(Length::Fixed(STEP_WIDTH));
+
+            (
+                probability_row.push(
+                    container(slider)
+                        .width(Length::Fixed(STEP_WIDTH))
+                        .center_x(),
+                ),
+                condition_row.push(condition_button),
+            )
+        },
+    );
+
+    Column::new()
+        .push(probability_row)
+        .push(condition_row)
+        .spacing(5)
+}